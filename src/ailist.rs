@@ -0,0 +1,563 @@
+//! An intrusive list whose nodes use atomic reference counts, so they can be shared between
+//! threads.
+//!
+//! Unlike `INode`, an `AINode` can't move itself around: every operation that reads or changes
+//! the links goes through the `AIList` the node is in. The list itself isn't `Sync`, so to share
+//! one between threads it has to be put behind a lock such as a `Mutex`. The node handles can be
+//! freely cloned and sent between threads, and used to get at the data without the lock.
+
+use core::marker::{self, Unsize};
+use core::cell::Cell;
+use core::{fmt, mem, ops};
+use core::sync::atomic::{self, AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use core::intrinsics::drop_in_place;
+use alloc::boxed::{Box, into_raw};
+use alloc::heap::deallocate;
+
+use core::nonzero::NonZero;
+
+use raw::Raw;
+
+/**
+ * A reference-counted node for use in an `AIList`. An `AINode` can only be in one list at a
+ * time.
+ */
+#[unsafe_no_drop_flag]
+pub struct AINode<T: ?Sized> {
+    __ptr: NonZero<*mut Node<T>>
+}
+
+// The count and the owner are atomic, and the links are only ever touched by the list that
+// owns the node, so the handles can be shared as freely as the data can.
+unsafe impl<T: ?Sized + Send + Sync> marker::Send for AINode<T> {}
+unsafe impl<T: ?Sized + Send + Sync> marker::Sync for AINode<T> {}
+
+struct Node<T: ?Sized, U: ?Sized=T> {
+    count: AtomicUsize,
+    // The id of the list this node is in, or 0. A list claims a node by swapping its id in, and
+    // only the list that owns a node reads or writes its links.
+    owner: AtomicUsize,
+    next: Cell<Raw<Node<U>>>,
+    prev: Cell<Raw<Node<U>>>,
+    data: T
+}
+
+impl<T: ?Sized> AINode<T> {
+    pub fn new<U: Unsize<T>>(value: U) -> AINode<T> {
+        unsafe {
+            let node : Box<Node<U, T>> = box Node {
+                count: AtomicUsize::new(1),
+                owner: AtomicUsize::new(0),
+                next: Cell::new(Raw::null()),
+                prev: Cell::new(Raw::null()),
+                data: value
+            };
+
+            let node : Box<Node<T, T>> = node;
+            let ptr = into_raw(node);
+
+            AINode {
+                __ptr: NonZero::new(ptr)
+            }
+        }
+    }
+
+    /**
+     * Returns a reference to the node's data. This is the same as dereferencing the node.
+     */
+    pub fn as_ref<'a>(&'a self) -> &'a T {
+        &self.node().data
+    }
+
+    /**
+     * Returns whether the two handles refer to the same node.
+     */
+    pub fn ptr_eq(a: &AINode<T>, b: &AINode<T>) -> bool {
+        a.to_raw() == b.to_raw()
+    }
+
+    /**
+     * Returns whether or not this node is in a list. As another thread may be adding or
+     * removing the node, this can be out of date as soon as it returns.
+     */
+    pub fn in_list(&self) -> bool {
+        self.node().owner.load(Ordering::Relaxed) != 0
+    }
+
+    /**
+     * Returns the number of references keeping this node alive, including the reference held by
+     * the list the node is in, if any.
+     */
+    pub fn strong_count(&self) -> usize {
+        self.node().count.load(Ordering::SeqCst)
+    }
+
+    fn node(&self) -> &Node<T> {
+        unsafe {
+            &**self.__ptr
+        }
+    }
+
+    fn into_raw(self) -> Raw<Node<T>> {
+        let raw = self.to_raw();
+        mem::forget(self);
+        raw
+    }
+
+    fn to_raw(&self) -> Raw<Node<T>> {
+        Raw::new(*self.__ptr)
+    }
+
+    /**
+     * Takes over a reference that has already been counted.
+     */
+    unsafe fn from_counted(raw: Raw<Node<T>>) -> AINode<T> {
+        AINode { __ptr: NonZero::new(raw.ptr()) }
+    }
+
+    fn from_raw(raw: Raw<Node<T>>) -> AINode<T> {
+        unsafe {
+            // As with `Arc`, a new reference can only be made from an existing one, so nothing
+            // needs to be synchronised here
+            (*raw.ptr()).count.fetch_add(1, Ordering::Relaxed);
+            AINode::from_counted(raw)
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for AINode<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let ptr = *self.__ptr;
+
+            let vp = ptr as *const ();
+
+            if !vp.is_null() && vp as usize != mem::POST_DROP_USIZE {
+                if (*ptr).count.fetch_sub(1, Ordering::Release) != 1 {
+                    return;
+                }
+
+                // Make sure every other thread is done with the node before it's freed
+                atomic::fence(Ordering::Acquire);
+
+                drop_in_place(&mut (*ptr).data);
+                deallocate(ptr as *mut u8,
+                           mem::size_of_val(&*ptr),
+                           mem::min_align_of_val(&*ptr));
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> Clone for AINode<T> {
+    fn clone(&self) -> AINode<T> {
+        AINode::from_raw(self.to_raw())
+    }
+}
+
+impl<T: ?Sized> ops::Deref for AINode<T> {
+    type Target = T;
+
+    fn deref<'a>(&'a self) -> &'a T {
+        self.as_ref()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AINode<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_ref(), f)
+    }
+}
+
+static NEXT_LIST_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/**
+ * An intrusive doubly-linked list of `AINode`s.
+ *
+ * The list holds a reference to each of its nodes. It can be sent to another thread, but can't
+ * be shared between threads without a lock, as all of its methods change the links through
+ * `&self`.
+ */
+pub struct AIList<T: ?Sized> {
+    head: Cell<Raw<Node<T>>>,
+    tail: Cell<Raw<Node<T>>>,
+    len: Cell<usize>,
+    id: usize
+}
+
+// Only the list touches its nodes' links, and it can't be shared, so it can be sent anywhere
+// its nodes can.
+unsafe impl<T: ?Sized + Send + Sync> marker::Send for AIList<T> {}
+
+impl<T: ?Sized> AIList<T> {
+    pub fn new() -> AIList<T> {
+        AIList {
+            head: Cell::new(Raw::null()),
+            tail: Cell::new(Raw::null()),
+            len: Cell::new(0),
+            // Ids are never reused, and 0 means a node isn't in a list
+            id: NEXT_LIST_ID.fetch_add(1, Ordering::Relaxed) + 1
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /**
+     * Returns whether the given node is in this list.
+     */
+    pub fn contains(&self, node: &AINode<T>) -> bool {
+        node.node().owner.load(Ordering::Acquire) == self.id
+    }
+
+    /**
+     * Pushes the given node to the front of the list. If the node is already in this list, it's
+     * moved.
+     *
+     * Panics if the node is in another list.
+     */
+    pub fn push_front(&self, node: AINode<T>) {
+        let raw = self.claim(node);
+        let head = self.head.get();
+
+        unsafe {
+            (*raw.ptr()).next.set(head);
+            (*raw.ptr()).prev.set(Raw::null());
+        }
+
+        match head.as_ref() {
+            Some(head) => head.prev.set(raw),
+            None => self.tail.set(raw)
+        }
+        self.head.set(raw);
+        self.len.set(self.len.get() + 1);
+    }
+
+    /**
+     * Pushes the given node to the back of the list. If the node is already in this list, it's
+     * moved.
+     *
+     * Panics if the node is in another list.
+     */
+    pub fn push_back(&self, node: AINode<T>) {
+        let raw = self.claim(node);
+        let tail = self.tail.get();
+
+        unsafe {
+            (*raw.ptr()).prev.set(tail);
+            (*raw.ptr()).next.set(Raw::null());
+        }
+
+        match tail.as_ref() {
+            Some(tail) => tail.next.set(raw),
+            None => self.head.set(raw)
+        }
+        self.tail.set(raw);
+        self.len.set(self.len.get() + 1);
+    }
+
+    /**
+     * Returns the head of the list, if there is one.
+     */
+    pub fn head(&self) -> Option<AINode<T>> {
+        self.handle(self.head.get())
+    }
+
+    /**
+     * Returns the tail of the list, if there is one.
+     */
+    pub fn tail(&self) -> Option<AINode<T>> {
+        self.handle(self.tail.get())
+    }
+
+    /**
+     * Returns the node after the given one, if there is one.
+     *
+     * Panics if the node isn't in this list.
+     */
+    pub fn next(&self, node: &AINode<T>) -> Option<AINode<T>> {
+        assert!(self.contains(node));
+        self.handle(node.node().next.get())
+    }
+
+    /**
+     * Returns the node before the given one, if there is one.
+     *
+     * Panics if the node isn't in this list.
+     */
+    pub fn prev(&self, node: &AINode<T>) -> Option<AINode<T>> {
+        assert!(self.contains(node));
+        self.handle(node.node().prev.get())
+    }
+
+    /**
+     * Removes the head of the list and returns it, if there is one. The list's reference to the
+     * node is handed over to the returned `AINode`.
+     */
+    pub fn pop_front(&self) -> Option<AINode<T>> {
+        let head = self.head.get();
+        if head.is_null() {
+            None
+        } else {
+            unsafe { Some(self.unlink(head)) }
+        }
+    }
+
+    /**
+     * Removes the tail of the list and returns it, if there is one. The list's reference to the
+     * node is handed over to the returned `AINode`.
+     */
+    pub fn pop_back(&self) -> Option<AINode<T>> {
+        let tail = self.tail.get();
+        if tail.is_null() {
+            None
+        } else {
+            unsafe { Some(self.unlink(tail)) }
+        }
+    }
+
+    /**
+     * Removes the given node from this list. Returns false, leaving the node where it is, if the
+     * node isn't in this list.
+     */
+    pub fn remove(&self, node: &AINode<T>) -> bool {
+        if self.contains(node) {
+            unsafe { self.unlink(node.to_raw()); }
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn iter<'a>(&'a self) -> Iter<'a, T> {
+        Iter {
+            list: self,
+            current: self.head()
+        }
+    }
+
+    /**
+     * Takes a node for this list, unlinking it first if it's already in it. Returns the node,
+     * still holding its reference, which the caller must link in.
+     */
+    fn claim(&self, node: AINode<T>) -> Raw<Node<T>> {
+        if self.contains(&node) {
+            unsafe { self.unlink(node.to_raw()); }
+        }
+
+        // Acquire the links as the previous owner left them
+        let prev = node.node().owner.compare_and_swap(0, self.id, Ordering::Acquire);
+        assert!(prev == 0, "node is already in another list");
+
+        node.into_raw()
+    }
+
+    /**
+     * Unlinks a node in this list, handing the list's reference to it over to the returned
+     * handle.
+     */
+    unsafe fn unlink(&self, raw: Raw<Node<T>>) -> AINode<T> {
+        let node = &*raw.ptr();
+        let prev = node.prev.get();
+        let next = node.next.get();
+
+        match prev.as_ref() {
+            Some(prev) => prev.next.set(next),
+            None => self.head.set(next)
+        }
+        match next.as_ref() {
+            Some(next) => next.prev.set(prev),
+            None => self.tail.set(prev)
+        }
+
+        node.next.set(Raw::null());
+        node.prev.set(Raw::null());
+        self.len.set(self.len.get() - 1);
+
+        // Publish the cleared links to whichever list claims the node next
+        node.owner.store(0, Ordering::Release);
+
+        AINode::from_counted(raw)
+    }
+
+    fn handle(&self, raw: Raw<Node<T>>) -> Option<AINode<T>> {
+        if raw.is_null() {
+            None
+        } else {
+            Some(AINode::from_raw(raw))
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for AIList<T> {
+    fn drop(&mut self) {
+        while let Some(_) = self.pop_front() {}
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AIList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, T: ?Sized + 'a> {
+    list: &'a AIList<T>,
+    current: Option<AINode<T>>
+}
+
+impl<'a, T: ?Sized> Iterator for Iter<'a, T> {
+    type Item = AINode<T>;
+
+    fn next(&mut self) -> Option<AINode<T>> {
+        let node = self.current.take();
+
+        if let Some(ref n) = node {
+            // The node may have been removed since it was reached, which ends the iteration
+            if self.list.contains(n) {
+                self.current = self.list.next(n);
+            }
+        }
+
+        node
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::prelude::v1::*;
+    use std::any::Any;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use super::*;
+
+    #[test]
+    fn smoketest() {
+        let list : AIList<[i32]> = AIList::new();
+        let a : AINode<[i32]> = AINode::new([1]);
+        let b : AINode<[i32]> = AINode::new([2, 2]);
+
+        list.push_back(a.clone());
+        list.push_back(b.clone());
+        list.push_front(AINode::new([0; 0]));
+        assert_eq!(list.len(), 3);
+        assert_eq!(a.strong_count(), 2);
+
+        let lens : Vec<usize> = list.iter().map(|n| n.len()).collect();
+        assert_eq!(lens, [0, 1, 2]);
+        assert!(AINode::ptr_eq(&list.next(&a).unwrap(), &b));
+        assert!(AINode::ptr_eq(&list.prev(&b).unwrap(), &a));
+
+        // Pushing a node that's already in the list moves it
+        list.push_front(b.clone());
+        assert_eq!(format!("{:?}", list), "[[2, 2], [], [1]]");
+        assert_eq!(b.strong_count(), 2);
+
+        let other : AIList<[i32]> = AIList::new();
+        assert!(!other.remove(&a));
+        assert!(list.remove(&a));
+        assert!(!a.in_list());
+        assert_eq!(a.strong_count(), 1);
+        other.push_back(a.clone());
+        assert!(other.contains(&a) && !list.contains(&a));
+
+        let popped = list.pop_back().unwrap();
+        assert_eq!(popped.len(), 0);
+        assert!(AINode::ptr_eq(&list.pop_front().unwrap(), &b));
+        assert!(list.is_empty() && list.head().is_none() && list.tail().is_none());
+        assert_eq!(b.strong_count(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn push_in_other_list() {
+        let list1 : AIList<[i32]> = AIList::new();
+        let list2 : AIList<[i32]> = AIList::new();
+        let node : AINode<[i32]> = AINode::new([1]);
+        list1.push_back(node.clone());
+        list2.push_back(node);
+    }
+
+    #[test]
+    fn threads() {
+        struct Counted(usize, Arc<AtomicUsize>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.1.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let list : Arc<Mutex<AIList<Any + Send + Sync>>> = Arc::new(Mutex::new(AIList::new()));
+
+        let producers : Vec<_> = (0..4).map(|t| {
+            let list = list.clone();
+            let drops = drops.clone();
+            thread::spawn(move || {
+                for i in 0..100 {
+                    let value = Counted(t * 100 + i, drops.clone());
+                    let node : AINode<Any + Send + Sync> = AINode::new(value);
+                    let keep = node.clone();
+                    list.lock().unwrap().push_back(node);
+                    drop(keep);
+                }
+            })
+        }).collect();
+
+        let consumer = {
+            let list = list.clone();
+            thread::spawn(move || {
+                let mut seen = Vec::new();
+                while seen.len() < 200 {
+                    if let Some(node) = list.lock().unwrap().pop_front() {
+                        seen.push(node.downcast_ref::<Counted>().unwrap().0);
+                    }
+                }
+                seen
+            })
+        };
+
+        for p in producers {
+            p.join().unwrap();
+        }
+        let seen = consumer.join().unwrap();
+        assert_eq!(seen.len(), 200);
+        assert_eq!(drops.load(Ordering::SeqCst), 200);
+
+        // Move the whole list to another thread and drop the rest there
+        let list = Arc::try_unwrap(list).ok().unwrap().into_inner().unwrap();
+        assert_eq!(list.len(), 200);
+        let held = list.head().unwrap();
+        thread::spawn(move || drop(list)).join().unwrap();
+        assert_eq!(drops.load(Ordering::SeqCst), 399);
+        assert!(!held.in_list());
+        drop(held);
+        assert_eq!(drops.load(Ordering::SeqCst), 400);
+    }
+
+    #[test]
+    fn shared_counts() {
+        let node : AINode<Any + Send + Sync> = AINode::new(5usize);
+        let threads : Vec<_> = (0..8).map(|_| {
+            let node = node.clone();
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    let clone = node.clone();
+                    assert_eq!(*clone.downcast_ref::<usize>().unwrap(), 5);
+                }
+            })
+        }).collect();
+
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(node.strong_count(), 1);
+    }
+}
@@ -0,0 +1,781 @@
+//! An intrusive list of structs that hold their own links.
+//!
+//! An `IList` wraps every value in a node that holds the links. An `EList` instead goes through
+//! an `ilist::Links` that's a field of the value itself, so the value's own allocation is the
+//! only one. A struct marks its links field with `define_node!`:
+//!
+//! ```text
+//! struct Task {
+//!     links: ilist::Links,
+//!     id: usize
+//! }
+//!
+//! define_node!(Task, links);
+//! ```
+//!
+//! Values are handled through `ENode`s, which work like `INode`s: an `ENode` is a
+//! reference-counted handle to a boxed value, with the count kept in the value's links, and a
+//! list holds a reference to each value in it. As with `IList`, list operations only need a
+//! shared reference, so a value can be moved between lists through any of its handles. Only
+//! shared references to the values are handed out; use `Cell`s for state that changes.
+
+use core::marker::{self, PhantomData};
+use core::cell::Cell;
+use core::{fmt, mem, ops};
+use core::nonzero::NonZero;
+use core::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use alloc::boxed::{Box, into_raw};
+
+use raw::Raw;
+
+/**
+ * Implements `Linked` for a struct, using the given field as its links. The field must be an
+ * `ilist::Links`.
+ *
+ * ```text
+ * define_node!(Task, links);
+ * ```
+ */
+#[macro_export]
+macro_rules! define_node {
+    ($ty:ty, $field:ident) => {
+        unsafe impl $crate::elist::Linked for $ty {
+            fn link_offset() -> usize {
+                unsafe {
+                    let item = 0 as *const $ty;
+                    &(*item).$field as *const $crate::ilist::Links as usize
+                }
+            }
+        }
+    }
+}
+
+/**
+ * The links that a struct embeds to be put in an `EList`. They also hold the count of the
+ * `ENode`s and lists that refer to the struct. This is exported as `ilist::Links`.
+ */
+pub struct Links {
+    next: Cell<Raw<Links>>,
+    prev: Cell<Raw<Links>>,
+    // The id of the list this is in, or 0. A sentinel has the id of its own list.
+    owner: Cell<usize>,
+    // The number of `ENode`s and lists that refer to the struct. A linked struct is always
+    // referred to by its list, so only a sentinel is linked with a count of 0.
+    count: Cell<usize>
+}
+
+impl Links {
+    pub fn new() -> Links {
+        Links {
+            next: Cell::new(Raw::null()),
+            prev: Cell::new(Raw::null()),
+            owner: Cell::new(0),
+            count: Cell::new(0)
+        }
+    }
+
+    /**
+     * Returns whether the struct holding these links is in a list.
+     */
+    pub fn is_linked(&self) -> bool {
+        self.owner.get() != 0
+    }
+
+    fn is_sentinel(&self) -> bool {
+        self.count.get() == 0
+    }
+
+    fn to_raw(&self) -> Raw<Links> {
+        Raw::new(self as *const Links as *mut Links)
+    }
+
+    /**
+     * Takes these links out of their list, and returns whether they were in one. The list's
+     * reference isn't released here, so the struct can't be freed while it's being unlinked.
+     */
+    fn take_from_list(&self) -> bool {
+        if !self.is_linked() {
+            return false;
+        }
+
+        let prev = self.prev.get();
+        let next = self.next.get();
+        prev.as_ref().unwrap().next.set(next);
+        next.as_ref().unwrap().prev.set(prev);
+
+        self.next.set(Raw::null());
+        self.prev.set(Raw::null());
+        self.owner.set(0);
+        true
+    }
+
+    /**
+     * Links the given value in after these links, handing its reference over to the list. These
+     * must be in a list, or be a list's sentinel.
+     */
+    fn insert_after<T: Linked>(&self, val: ENode<T>) {
+        assert!(val.links().to_raw() != self.to_raw(), "A node can't be inserted next to itself");
+        val.remove_from_list();
+        let next = self.next.get();
+
+        val.links().prev.set(self.to_raw());
+        val.links().next.set(next);
+        val.links().owner.set(self.owner.get());
+
+        let raw_val = val.into_raw();
+        self.next.set(raw_val);
+        next.as_ref().unwrap().prev.set(raw_val);
+    }
+
+    /**
+     * Links the given value in before these links, handing its reference over to the list.
+     * These must be in a list, or be a list's sentinel.
+     */
+    fn insert_before<T: Linked>(&self, val: ENode<T>) {
+        assert!(val.links().to_raw() != self.to_raw(), "A node can't be inserted next to itself");
+        val.remove_from_list();
+        let prev = self.prev.get();
+
+        val.links().next.set(self.to_raw());
+        val.links().prev.set(prev);
+        val.links().owner.set(self.owner.get());
+
+        let raw_val = val.into_raw();
+        self.prev.set(raw_val);
+        prev.as_ref().unwrap().next.set(raw_val);
+    }
+}
+
+impl Default for Links {
+    fn default() -> Links {
+        Links::new()
+    }
+}
+
+impl fmt::Debug for Links {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Links {{ linked: {} }}", self.is_linked())
+    }
+}
+
+/**
+ * A struct that holds an `ilist::Links`, and so can be put in an `EList`. Implement this with
+ * `define_node!`.
+ *
+ * This trait is unsafe to implement because lists trust that `link_offset` is the offset of a
+ * `Links` field in the struct.
+ */
+pub unsafe trait Linked: Sized {
+    /**
+     * Returns the offset in bytes of the struct's links from the start of the struct.
+     */
+    fn link_offset() -> usize;
+}
+
+/**
+ * Returns the links embedded in the given value.
+ */
+fn link_of<T: Linked>(item: &T) -> &Links {
+    unsafe {
+        let ptr = item as *const T as *const u8;
+        &*(ptr.offset(T::link_offset() as isize) as *const Links)
+    }
+}
+
+/**
+ * Returns the value that embeds the given links. The links must be part of a `T`.
+ */
+unsafe fn item_of<T: Linked>(link: Raw<Links>) -> *mut T {
+    let ptr = link.ptr() as *mut u8;
+    ptr.offset(-(T::link_offset() as isize)) as *mut T
+}
+
+/**
+ * Returns a new handle to the value that embeds the given links, or `None` if they're a
+ * sentinel.
+ */
+fn node_at<T: Linked>(link: Raw<Links>) -> Option<ENode<T>> {
+    match link.as_ref() {
+        Some(l) if !l.is_sentinel() => Some(ENode::from_raw(link)),
+        _ => None
+    }
+}
+
+/**
+ * A reference-counted handle to a value that embeds its own links, for use in an `EList`. The
+ * value is boxed when the first handle is made, and is dropped once neither a handle nor a list
+ * refers to it.
+ */
+#[unsafe_no_drop_flag]
+pub struct ENode<T: Linked> {
+    __ptr: NonZero<*mut T>
+}
+
+impl<T: Linked> !marker::Send for ENode<T> {}
+impl<T: Linked> !marker::Sync for ENode<T> {}
+
+impl<T: Linked> ENode<T> {
+    pub fn new(value: T) -> ENode<T> {
+        let ptr = into_raw(box value);
+        unsafe {
+            link_of(&*ptr).count.set(1);
+            ENode { __ptr: NonZero::new(ptr) }
+        }
+    }
+
+    /**
+     * Returns whether the two handles refer to the same value.
+     */
+    pub fn ptr_eq(a: &ENode<T>, b: &ENode<T>) -> bool {
+        *a.__ptr == *b.__ptr
+    }
+
+    /**
+     * Returns the number of handles and lists referring to the value.
+     */
+    pub fn strong_count(&self) -> usize {
+        self.links().count.get()
+    }
+
+    /**
+     * Returns whether or not the value is in a list.
+     */
+    pub fn in_list(&self) -> bool {
+        self.links().is_linked()
+    }
+
+    /**
+     * Returns the id of the list the value is in, or `None` if it isn't in a list. See
+     * `EList::id`.
+     */
+    pub fn owner_id(&self) -> Option<usize> {
+        match self.links().owner.get() {
+            0 => None,
+            id => Some(id)
+        }
+    }
+
+    /**
+     * Removes the value from the list it's in, if any.
+     */
+    pub fn remove_from_list(&self) {
+        if self.links().take_from_list() {
+            // The list's reference is released here, once the value has been completely unlinked
+            unsafe {
+                drop(ENode::<T>::from_counted(self.links().to_raw()));
+            }
+        }
+    }
+
+    /**
+     * Inserts the given value after this one.
+     *
+     * Panics if this value isn't in a list.
+     */
+    pub fn insert_after(&self, val: ENode<T>) {
+        assert!(self.in_list(), "insert_after: node isn't in a list");
+        self.links().insert_after(val);
+    }
+
+    /**
+     * Inserts the given value before this one.
+     *
+     * Panics if this value isn't in a list.
+     */
+    pub fn insert_before(&self, val: ENode<T>) {
+        assert!(self.in_list(), "insert_before: node isn't in a list");
+        self.links().insert_before(val);
+    }
+
+    /**
+     * Returns the next value in the list, or `None` if this is the last one or isn't in a list.
+     */
+    pub fn next(&self) -> Option<ENode<T>> {
+        node_at(self.links().next.get())
+    }
+
+    /**
+     * Returns the previous value in the list, or `None` if this is the first one or isn't in a
+     * list.
+     */
+    pub fn prev(&self) -> Option<ENode<T>> {
+        node_at(self.links().prev.get())
+    }
+
+    /**
+     * Returns a reference to the value. This is the same as dereferencing the handle.
+     */
+    pub fn as_ref<'a>(&'a self) -> &'a T {
+        unsafe {
+            &**self.__ptr
+        }
+    }
+
+    fn links(&self) -> &Links {
+        link_of(self.as_ref())
+    }
+
+    /**
+     * Gives up this handle's reference, returning the value's links.
+     */
+    fn into_raw(self) -> Raw<Links> {
+        let raw = self.links().to_raw();
+        mem::forget(self);
+        raw
+    }
+
+    /**
+     * Makes a handle out of a reference that's already been counted, such as a list's.
+     */
+    unsafe fn from_counted(link: Raw<Links>) -> ENode<T> {
+        ENode { __ptr: NonZero::new(item_of::<T>(link)) }
+    }
+
+    fn from_raw(link: Raw<Links>) -> ENode<T> {
+        let l = link.as_ref().unwrap();
+        l.count.set(l.count.get() + 1);
+        unsafe { ENode::from_counted(link) }
+    }
+}
+
+impl<T: Linked> Drop for ENode<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let ptr = *self.__ptr;
+
+            if !ptr.is_null() && ptr as usize != mem::POST_DROP_USIZE {
+                let links = link_of(&*ptr);
+                let count = links.count.get() - 1;
+                links.count.set(count);
+
+                // A list holds a reference to everything in it, so the value isn't linked
+                if count == 0 {
+                    drop(Box::from_raw(ptr));
+                }
+            }
+        }
+    }
+}
+
+impl<T: Linked> Clone for ENode<T> {
+    fn clone(&self) -> ENode<T> {
+        ENode::from_raw(self.links().to_raw())
+    }
+}
+
+impl<T: Linked> ops::Deref for ENode<T> {
+    type Target = T;
+
+    fn deref<'a>(&'a self) -> &'a T {
+        self.as_ref()
+    }
+}
+
+impl<T: Linked + fmt::Debug> fmt::Debug for ENode<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_ref(), f)
+    }
+}
+
+/**
+ * `ENode`s are compared by identity, as `INode`s are.
+ */
+impl<T: Linked> PartialEq for ENode<T> {
+    fn eq(&self, other: &ENode<T>) -> bool {
+        ENode::ptr_eq(self, other)
+    }
+}
+
+impl<T: Linked> Eq for ENode<T> {}
+
+static NEXT_LIST_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/**
+ * A doubly-linked list of values that embed their own links. See the module documentation for
+ * how to define a type that can be put in one.
+ */
+pub struct EList<T: Linked> {
+    // Only a set of links, with no value around it
+    sentinel: Raw<Links>,
+    id: usize,
+    marker: PhantomData<ENode<T>>
+}
+
+impl<T: Linked> EList<T> {
+    pub fn new() -> EList<T> {
+        let sentinel = Raw::new(into_raw(box Links::new()));
+        // Ids are never reused, and 0 means a value isn't in a list
+        let id = NEXT_LIST_ID.fetch_add(1, Ordering::Relaxed) + 1;
+
+        {
+            // An empty list's sentinel links round to itself. It keeps a count of 0, which no
+            // linked value has.
+            let s = sentinel.as_ref().unwrap();
+            s.next.set(sentinel);
+            s.prev.set(sentinel);
+            s.owner.set(id);
+        }
+
+        EList {
+            sentinel: sentinel,
+            id: id,
+            marker: PhantomData
+        }
+    }
+
+    fn sentinel(&self) -> &Links {
+        self.sentinel.as_ref().unwrap()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sentinel().next.get() == self.sentinel
+    }
+
+    /**
+     * Returns an identifier for this list, which can be compared against `ENode::owner_id`.
+     */
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /**
+     * Returns whether the given value is in this list. This takes constant time.
+     */
+    pub fn contains(&self, node: &ENode<T>) -> bool {
+        node.links().owner.get() == self.id
+    }
+
+    /**
+     * Removes the given value from this list. Returns false, leaving the value where it is, if
+     * it isn't in this list.
+     */
+    pub fn remove(&self, node: &ENode<T>) -> bool {
+        if self.contains(node) {
+            node.remove_from_list();
+            true
+        } else {
+            false
+        }
+    }
+
+    /**
+     * Pushes the given value to the front of the list, removing it from any list it's in.
+     */
+    pub fn push_front(&self, val: ENode<T>) {
+        self.sentinel().insert_after(val);
+    }
+
+    /**
+     * Pushes the given value to the back of the list, removing it from any list it's in.
+     */
+    pub fn push_back(&self, val: ENode<T>) {
+        self.sentinel().insert_before(val);
+    }
+
+    /**
+     * Returns the value at the front of the list, if there is one.
+     */
+    pub fn head(&self) -> Option<ENode<T>> {
+        node_at(self.sentinel().next.get())
+    }
+
+    /**
+     * Returns the value at the back of the list, if there is one.
+     */
+    pub fn tail(&self) -> Option<ENode<T>> {
+        node_at(self.sentinel().prev.get())
+    }
+
+    /**
+     * Removes the value at the front of the list and returns it, if there is one. The list's
+     * reference is handed over to the returned `ENode`.
+     */
+    pub fn pop_front(&self) -> Option<ENode<T>> {
+        self.pop(self.sentinel().next.get())
+    }
+
+    /**
+     * Removes the value at the back of the list and returns it, if there is one. The list's
+     * reference is handed over to the returned `ENode`.
+     */
+    pub fn pop_back(&self) -> Option<ENode<T>> {
+        self.pop(self.sentinel().prev.get())
+    }
+
+    fn pop(&self, link: Raw<Links>) -> Option<ENode<T>> {
+        let l = link.as_ref().unwrap();
+        if l.is_sentinel() {
+            None
+        } else {
+            l.take_from_list();
+            unsafe { Some(ENode::from_counted(link)) }
+        }
+    }
+
+    /**
+     * Returns an iterator over handles to the values in the list. Each value's successor is
+     * looked up as the value is returned, so removing the value the iterator just returned
+     * doesn't end the walk.
+     */
+    pub fn iter(&self) -> Iter<T> {
+        Iter { next: self.head() }
+    }
+}
+
+impl<T: Linked> Drop for EList<T> {
+    fn drop(&mut self) {
+        while let Some(node) = self.pop_front() {
+            drop(node);
+        }
+
+        unsafe {
+            drop(Box::from_raw(self.sentinel.ptr()));
+        }
+    }
+}
+
+impl<T: Linked + fmt::Debug> fmt::Debug for EList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T: Linked> IntoIterator for &'a EList<T> {
+    type Item = ENode<T>;
+    type IntoIter = Iter<T>;
+
+    fn into_iter(self) -> Iter<T> {
+        self.iter()
+    }
+}
+
+pub struct Iter<T: Linked> {
+    next: Option<ENode<T>>
+}
+
+impl<T: Linked> Iterator for Iter<T> {
+    type Item = ENode<T>;
+
+    fn next(&mut self) -> Option<ENode<T>> {
+        match self.next.take() {
+            Some(node) => {
+                self.next = node.next();
+                Some(node)
+            }
+            None => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::prelude::v1::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use raw::Raw;
+    use ilist;
+    use super::*;
+    use super::{link_of, item_of};
+
+    #[derive(Debug)]
+    struct Task {
+        id: usize,
+        links: ilist::Links,
+        // How many more times the task needs to run before it's done
+        work: Cell<usize>,
+        drops: Rc<Cell<usize>>
+    }
+
+    define_node!(Task, links);
+
+    impl Task {
+        fn new(id: usize, work: usize, drops: &Rc<Cell<usize>>) -> ENode<Task> {
+            ENode::new(Task {
+                id: id,
+                links: ilist::Links::new(),
+                work: Cell::new(work),
+                drops: drops.clone()
+            })
+        }
+    }
+
+    impl Drop for Task {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    struct Scheduler {
+        ready: EList<Task>,
+        blocked: EList<Task>
+    }
+
+    impl Scheduler {
+        fn ids(list: &EList<Task>) -> Vec<usize> {
+            list.iter().map(|t| t.id).collect()
+        }
+
+        // Runs the task at the front, then moves it to the back, or drops it if it's finished
+        fn step(&self) -> Option<usize> {
+            let task = match self.ready.pop_front() {
+                Some(task) => task,
+                None => return None
+            };
+
+            let id = task.id;
+            task.work.set(task.work.get() - 1);
+            if task.work.get() > 0 {
+                self.ready.push_back(task);
+            }
+            Some(id)
+        }
+
+        fn block(&self, task: &ENode<Task>) {
+            assert!(self.ready.contains(task));
+            self.blocked.push_back(task.clone());
+        }
+
+        fn wake(&self, task: &ENode<Task>) {
+            assert!(self.blocked.remove(task));
+            self.ready.push_front(task.clone());
+        }
+    }
+
+    #[test]
+    fn offset() {
+        let drops = Rc::new(Cell::new(0));
+        let task = Task::new(7, 1, &drops);
+        let link = link_of(&*task) as *const ilist::Links;
+        assert_eq!(link, &task.links as *const ilist::Links);
+        unsafe {
+            let item = item_of::<Task>(Raw::new(link as *mut ilist::Links));
+            assert_eq!(item as *const Task, &*task as *const Task);
+        }
+    }
+
+    #[test]
+    fn scheduler() {
+        let drops = Rc::new(Cell::new(0));
+        let sched = Scheduler {
+            ready: EList::new(),
+            blocked: EList::new()
+        };
+
+        let tasks : Vec<ENode<Task>> = vec![
+            Task::new(0, 1, &drops),
+            Task::new(1, 2, &drops),
+            Task::new(2, 1, &drops),
+            Task::new(3, 3, &drops)
+        ];
+        let addr = &*tasks[3] as *const Task;
+
+        sched.ready.push_back(tasks[1].clone());
+        sched.ready.push_back(tasks[2].clone());
+        sched.ready.push_back(tasks[3].clone());
+        sched.ready.push_front(tasks[0].clone());
+        assert_eq!(Scheduler::ids(&sched.ready), [0, 1, 2, 3]);
+        assert!(sched.ready.head().unwrap() == tasks[0]);
+        assert!(sched.ready.tail().unwrap() == tasks[3]);
+        assert_eq!(tasks[1].strong_count(), 2);
+        assert_eq!(tasks[1].owner_id(), Some(sched.ready.id()));
+
+        // Moving a task between lists keeps the same allocation
+        sched.block(&tasks[3]);
+        assert_eq!(Scheduler::ids(&sched.ready), [0, 1, 2]);
+        assert_eq!(Scheduler::ids(&sched.blocked), [3]);
+        {
+            let blocked = sched.blocked.head().unwrap();
+            assert_eq!(&*blocked as *const Task, addr);
+            assert!(sched.blocked.contains(&blocked));
+            assert!(!sched.ready.contains(&blocked));
+            assert_eq!(tasks[3].strong_count(), 3);
+        }
+
+        // The lists keep the tasks alive once the handles are gone
+        drop(tasks);
+        assert_eq!(sched.step(), Some(0));
+        assert_eq!(sched.step(), Some(1));
+        assert_eq!(drops.get(), 1);
+        assert_eq!(Scheduler::ids(&sched.ready), [2, 1]);
+
+        let task = sched.blocked.head().unwrap();
+        sched.wake(&task);
+        drop(task);
+        assert!(sched.blocked.is_empty());
+        assert_eq!(Scheduler::ids(&sched.ready), [3, 2, 1]);
+
+        let mut order = Vec::new();
+        while let Some(id) = sched.step() {
+            order.push(id);
+        }
+        assert_eq!(order, [3, 2, 1, 3, 3]);
+        assert!(sched.ready.is_empty());
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn remove() {
+        let drops = Rc::new(Cell::new(0));
+        let list = EList::new();
+        let tasks : Vec<ENode<Task>> = (0..6).map(|i| Task::new(i, 1, &drops)).collect();
+        for task in &tasks {
+            list.push_back(task.clone());
+        }
+
+        // A removed task is unlinked, and can go into another list
+        let task = list.pop_back().unwrap();
+        assert!(task == tasks[5]);
+        assert!(!task.in_list());
+        assert!(!task.links.is_linked());
+        let other = EList::new();
+        other.push_back(task);
+        assert!(other.head().unwrap().links.is_linked());
+        assert!(!list.remove(&tasks[5]));
+
+        // Removing through a handle, and through the list
+        tasks[2].remove_from_list();
+        assert!(!list.contains(&tasks[2]));
+        assert!(!list.remove(&tasks[2]));
+        assert!(list.remove(&tasks[3]));
+        assert_eq!(Scheduler::ids(&list), [0, 1, 4]);
+        assert_eq!(tasks[2].strong_count(), 1);
+
+        // Inserting next to a task, and walking from it
+        tasks[1].insert_after(tasks[3].clone());
+        tasks[0].insert_before(tasks[2].clone());
+        assert_eq!(Scheduler::ids(&list), [2, 0, 1, 3, 4]);
+        assert!(tasks[1].next().unwrap() == tasks[3]);
+        assert!(tasks[1].prev().unwrap() == tasks[0]);
+        assert!(tasks[2].prev().is_none());
+        assert!(tasks[4].next().is_none());
+
+        // The iterator carries on past a task removed as it's returned
+        let mut seen = Vec::new();
+        for task in &list {
+            seen.push(task.id);
+            if task.id % 2 == 1 {
+                task.remove_from_list();
+            }
+        }
+        assert_eq!(seen, [2, 0, 1, 3, 4]);
+        assert_eq!(Scheduler::ids(&list), [2, 0, 4]);
+        assert_eq!(drops.get(), 0);
+
+        // Dropping a list only drops the tasks nothing else refers to
+        drop(tasks);
+        assert_eq!(drops.get(), 2);
+        drop(list);
+        assert_eq!(drops.get(), 5);
+        drop(other);
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_after_not_in_list() {
+        let drops = Rc::new(Cell::new(0));
+        let task = Task::new(0, 1, &drops);
+        task.insert_after(Task::new(1, 1, &drops));
+    }
+}
@@ -0,0 +1,57 @@
+//! Allocators for list nodes.
+
+use alloc::heap::{allocate, deallocate};
+
+/**
+ * An allocator that lists use for their nodes.
+ *
+ * An allocator is cloned into every element that is removed from a list, so that the element can
+ * free its node when it's dropped. Implement this for a cheap handle to the allocator, like
+ * `&'a MyPool` or a zero-sized type, rather than the allocator itself.
+ *
+ * This trait is unsafe to implement because lists trust that `allocate` returns either null or a
+ * pointer to `size` bytes aligned to `align`, and that `same_as` only returns `true` for handles
+ * that can free each other's memory.
+ */
+pub unsafe trait Allocator: Clone {
+    /**
+     * Allocates `size` bytes aligned to `align`, returning null if the allocation fails. `size`
+     * is never zero.
+     */
+    unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8;
+
+    /**
+     * Frees memory previously returned by `allocate` on this allocator, or a clone of it, with
+     * the same size and alignment.
+     */
+    unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize);
+
+    /**
+     * Returns whether `other` is a handle to the same allocator as this one. Lists check this
+     * before taking in a node that was allocated by another list.
+     */
+    fn same_as(&self, other: &Self) -> bool;
+}
+
+/**
+ * The global heap. This is the default allocator for lists.
+ */
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Global;
+
+unsafe impl Allocator for Global {
+    #[inline]
+    unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8 {
+        allocate(size, align)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize) {
+        deallocate(ptr, size, align)
+    }
+
+    #[inline]
+    fn same_as(&self, _other: &Global) -> bool {
+        true
+    }
+}
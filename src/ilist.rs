@@ -1,17 +1,30 @@
-use std::marker::{self, Unsize};
-use std::boxed::into_raw;
-use std::cell::Cell;
-use std::mem;
+use core::marker::{self, PhantomData, Unsize};
+use core::cell::{Cell, Ref, RefCell, RefMut};
+use core::{fmt, iter, mem, ops, ptr};
+use core::hash::{Hash, Hasher};
+use core::cmp::Ordering;
+use core::sync::atomic::{self, AtomicUsize, ATOMIC_USIZE_INIT};
+use collections::borrow::Borrow;
 
-use std::intrinsics::drop_in_place;
-use std::rt::heap::{allocate, deallocate};
+use core::intrinsics::drop_in_place;
+use alloc::boxed::{Box, into_raw};
+use alloc::heap::deallocate;
 
 use core::nonzero::NonZero;
 
-use raw::{self, Raw};
+use raw::Raw;
+use node_cache;
+
+pub use node_cache::LIMIT as NODE_CACHE_LIMIT;
+
+// The links for structs that embed their own, to be put in an `EList`
+pub use elist::Links;
 
 /**
- * A reference-counted node for use in an `IList`. An `INode` can only be in one IList at a time.
+ * A reference-counted node for use in an `IList`. A node has a set of links for each `Tag`, so
+ * it can be in one `IList<T, Primary>` and one `IList<T, Secondary>` at a time. Only the
+ * `Primary` links are part of the node; the `Secondary` ones are allocated the first time
+ * they're used.
  */
 #[unsafe_no_drop_flag]
 pub struct INode<T: ?Sized> {
@@ -21,32 +34,201 @@ pub struct INode<T: ?Sized> {
 impl<T: ?Sized> !marker::Send for INode<T> {}
 impl<T: ?Sized> !marker::Sync for INode<T> {}
 
+/**
+ * A weak reference to an `INode`. It doesn't keep the node's data alive, and has to be upgraded
+ * to an `INode` to get at it.
+ */
+#[unsafe_no_drop_flag]
+pub struct IWeak<T: ?Sized> {
+    __ptr: NonZero<*mut Node<T>>
+}
+
+impl<T: ?Sized> !marker::Send for IWeak<T> {}
+impl<T: ?Sized> !marker::Sync for IWeak<T> {}
+
+/**
+ * The error returned when inserting next to a node that isn't in a list. It holds the node that
+ * was being inserted.
+ */
+pub struct NotInList<T: ?Sized> {
+    node: INode<T>
+}
+
+impl<T: ?Sized> NotInList<T> {
+    /**
+     * Returns the node that couldn't be inserted.
+     */
+    pub fn into_inner(self) -> INode<T> {
+        self.node
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for NotInList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("NotInList { .. }")
+    }
+}
+
+impl<T: ?Sized> fmt::Display for NotInList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("node isn't in a list")
+    }
+}
+
+/**
+ * Selects one of the sets of links in a node, and so which kind of list an `IList` is. A node
+ * can be in one list of each kind at the same time, such as a list of every entity and a list of
+ * the entities that have changed.
+ *
+ * Nodes can have at most two sets of links, so this is only implemented by `Primary` and
+ * `Secondary`.
+ */
+pub trait Tag: tag::Sealed {}
+
+mod tag {
+    pub trait Sealed {
+        fn index() -> usize;
+    }
+}
+
+/**
+ * The links used by an `IList` by default, and by the list methods on `INode` itself.
+ */
+pub enum Primary {}
+
+/**
+ * The second set of links in a node. An `IList<T, Secondary>` can hold nodes that are also in an
+ * `IList<T>`. A node only gets these links the first time they're used, so nodes that are never
+ * put in a `Secondary` list don't pay for them.
+ */
+pub enum Secondary {}
+
+impl Tag for Primary {}
+impl tag::Sealed for Primary {
+    fn index() -> usize { 0 }
+}
+
+impl Tag for Secondary {}
+impl tag::Sealed for Secondary {
+    fn index() -> usize { 1 }
+}
+
+/**
+ * A set of links for a node. The links point at other `NodeLinks` rather than at whole nodes,
+ * so a list's sentinel can be a `NodeLinks` on its own, and never needs the length or vtable of
+ * a `T` that doesn't exist.
+ */
+struct NodeLinks<T: ?Sized> {
+    next: Cell<Raw<NodeLinks<T>>>,
+    prev: Cell<Raw<NodeLinks<T>>>,
+    // The owner of the list this node is in, or null. A sentinel has its own list's owner.
+    owner: Cell<Raw<Owner>>,
+    // The node these links are part of, or null for a sentinel
+    node: Cell<Raw<Node<T>>>
+}
+
 struct Node<T: ?Sized, U: ?Sized=T> {
     count: Cell<usize>,
-    next: Cell<Raw<Node<U>>>,
-    prev: Cell<Raw<Node<U>>>,
+    // The strong references share a single weak reference, which is released when the data is
+    // dropped. The node is freed once this reaches zero.
+    weak: Cell<usize>,
+    // The `Primary` links. Every list the node is in holds a reference to it.
+    links: NodeLinks<U>,
+    // The `Secondary` links, or null until they're first used
+    secondary: Cell<Raw<NodeLinks<U>>>,
     data: T
 }
 
 impl<T: ?Sized> INode<T> {
     pub fn new<U: Unsize<T>>(value: U) -> INode<T> {
         unsafe {
-            let node : Box<Node<U, T>> = box Node {
-                count: Cell::new(1),
-                next: Cell::new(Raw::null()),
-                prev: Cell::new(Raw::null()),
-                data: value
-            };
+            let ptr : *mut Node<U, T> = INode::allocate(value);
 
+            // Go through a box to unsize the pointer. It's never dropped, so it doesn't matter
+            // that the memory may not have come from the heap
+            let node : Box<Node<U, T>> = Box::from_raw(ptr);
             let node : Box<Node<T, T>> = node;
-            let ptr = into_raw(node);
+            INode::from_allocated(into_raw(node))
+        }
+    }
+
+    /**
+     * Creates a node holding a sized value. `new` can't be used for this, as it needs a value
+     * that unsizes to `T`.
+     */
+    pub fn new_sized(value: T) -> INode<T> where T: Sized {
+        unsafe {
+            let ptr : *mut Node<T> = INode::allocate(value);
+            INode::from_allocated(ptr)
+        }
+    }
+
+    /**
+     * Allocates a node holding `value`, with links for nodes holding a `T`. The links don't
+     * point back at the node until it's passed to `from_allocated`.
+     */
+    unsafe fn allocate<U>(value: U) -> *mut Node<U, T> {
+        let ptr = node_cache::allocate(mem::size_of::<Node<U, T>>(),
+                                       mem::min_align_of::<Node<U, T>>());
+        let ptr = ptr as *mut Node<U, T>;
+        ptr::write(ptr, Node {
+            count: Cell::new(1),
+            weak: Cell::new(1),
+            links: NodeLinks::new(),
+            secondary: Cell::new(Raw::null()),
+            data: value
+        });
+        ptr
+    }
+
+    unsafe fn from_allocated(ptr: *mut Node<T>) -> INode<T> {
+        (*ptr).links.node.set(Raw::new(ptr));
+
+        INode {
+            __ptr: NonZero::new(ptr)
+        }
+    }
+
+    /**
+     * Creates a node from a boxed value. The value is moved out of the box into the node, so
+     * this can be used with trait objects and slices whose concrete type isn't known.
+     */
+    pub fn new_boxed(val: Box<T>) -> INode<T> {
+        unsafe {
+            let val = into_raw(val);
+
+            let val_size = mem::size_of_val(&*val);
+            let val_align = mem::min_align_of_val(&*val);
+
+            // Build a node pointer with the same metadata (length or vtable) as the value, which
+            // gives the size and alignment of the whole node
+            let mut node : *mut Node<T> = mem::transmute_copy(&val);
+            let size = mem::size_of_val(&*node);
+            let align = mem::min_align_of_val(&*node);
+
+            let ptr = node_cache::allocate(size, align);
+            *(&mut node as *mut *mut Node<T> as *mut *mut u8) = ptr;
 
-            INode {
-                __ptr: NonZero::new(ptr)
+            ptr::write(&mut (*node).count, Cell::new(1));
+            ptr::write(&mut (*node).weak, Cell::new(1));
+            ptr::write(&mut (*node).links, NodeLinks::new());
+            ptr::write(&mut (*node).secondary, Cell::new(Raw::null()));
+            (*node).links.node.set(Raw::new(node));
+            ptr::copy_nonoverlapping(val as *const u8, &mut (*node).data as *mut T as *mut u8,
+                                     val_size);
+
+            // The value has been moved, so free the box without dropping it
+            if val_size != 0 {
+                deallocate(val as *mut u8, val_size, val_align);
             }
+
+            INode { __ptr: NonZero::new(node) }
         }
     }
 
+    /**
+     * Returns a reference to the node's data. This is the same as dereferencing the node.
+     */
     pub fn as_ref<'a>(&'a self) -> &'a T {
         unsafe {
             let node = &**self.__ptr;
@@ -54,11 +236,67 @@ impl<T: ?Sized> INode<T> {
         }
     }
 
+    /**
+     * Returns a mutable reference to the node's data, if this is the only handle to the node.
+     *
+     * A node that is in a list is shared with the list, so this returns `None` until the node
+     * has been removed from it.
+     */
+    pub fn get_mut<'a>(&'a mut self) -> Option<&'a mut T> {
+        if self.is_sole_owner() {
+            unsafe {
+                let node = &mut **self.__ptr;
+                Some(&mut node.data)
+            }
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Moves the data out of the node and frees it, if this is the only handle to the node.
+     * Otherwise the handle is given back unchanged.
+     *
+     * As with `get_mut`, a node that is in a list, or that has any `IWeak`s, is shared, so this
+     * fails until the node has been removed from the list and the weak references are gone.
+     */
+    pub fn try_unwrap(self) -> Result<T, INode<T>> where T: Sized {
+        if !self.is_sole_owner() {
+            return Err(self);
+        }
+
+        unsafe {
+            let ptr = *self.__ptr;
+            mem::forget(self);
+
+            // There are no weak references, so the node can be freed as soon as the data has
+            // been moved out
+            let val = ptr::read(&(*ptr).data);
+            free_node(ptr);
+            Ok(val)
+        }
+    }
+
+    /**
+     * Returns whether the two handles refer to the same node.
+     */
+    pub fn ptr_eq(a: &INode<T>, b: &INode<T>) -> bool {
+        *a.__ptr as *const () == *b.__ptr as *const ()
+    }
+
+    /**
+     * Returns the node's links of the kind given by `L`, which can be used to move the node
+     * around in a list of that kind. The list methods on `INode` itself use the `Primary` links.
+     */
+    pub fn link<'a, L: Tag>(&'a self) -> ILink<'a, T, L> {
+        ILink { node: self, tag: PhantomData }
+    }
+
     /**
      * Removes this `INode` from the list it is in, if it is a list.
      */
     pub fn remove_from_list(&self) {
-        self.node().remove_from_list();
+        self.link::<Primary>().remove_from_list()
     }
 
     /**
@@ -67,21 +305,15 @@ impl<T: ?Sized> INode<T> {
      * Panics if this node isn't in a list.
      */
     pub fn insert_after(&self, val: INode<T>) {
-        assert!(self.in_list());
-        val.remove_from_list();
-        let raw_self = Raw::new(*self.__ptr);
-
-        let next = self.node().next.get();
-
-        val.node().prev.set(raw_self);
-        val.node().next.set(next);
-
-        let raw_val = val.into_raw();
-        self.node().next.set(raw_val);
+        self.link::<Primary>().insert_after(val)
+    }
 
-        if let Some(next) = next.as_ref() {
-            next.prev.set(raw_val);
-        }
+    /**
+     * Inserts the given node after this one. If this node isn't in a list, the given node is
+     * handed back in the error.
+     */
+    pub fn try_insert_after(&self, val: INode<T>) -> Result<(), NotInList<T>> {
+        self.link::<Primary>().try_insert_after(val)
     }
 
     /**
@@ -90,66 +322,98 @@ impl<T: ?Sized> INode<T> {
      * Panics if this node isn't in a list.
      */
     pub fn insert_before(&self, val: INode<T>) {
-        assert!(self.in_list());
-        val.remove_from_list();
-        let raw_self = Raw::new(*self.__ptr);
-
-        let prev = self.node().prev.get();
-
-        val.node().next.set(raw_self);
-        val.node().prev.set(prev);
-
-        let raw_val = val.into_raw();
-        self.node().prev.set(raw_val);
+        self.link::<Primary>().insert_before(val)
+    }
 
-        if let Some(prev) = prev.as_ref() {
-            prev.next.set(raw_val);
-        }
+    /**
+     * Inserts the given node before this one. If this node isn't in a list, the given node is
+     * handed back in the error.
+     */
+    pub fn try_insert_before(&self, val: INode<T>) -> Result<(), NotInList<T>> {
+        self.link::<Primary>().try_insert_before(val)
     }
 
     /**
      * Returns the next node in the list, or None if there is no next node.
      */
     pub fn next(&self) -> Option<INode<T>> {
-        let raw_next = self.node().next.get();
-
-        if let Some(next) = raw_next.as_ref() {
-            if !next.is_sentinel() {
-                unsafe {
-                    let next = INode { __ptr: NonZero::new(raw_next.ptr) };
-                    next.inc_count();
-                    return Some(next);
-                }
-            }
-        }
-
-        None
+        self.link::<Primary>().next()
     }
 
     /**
      * Returns the previous node in the list, or None if there is no previous node.
      */
     pub fn prev(&self) -> Option<INode<T>> {
-        let raw_prev = self.node().prev.get();
+        self.link::<Primary>().prev()
+    }
 
-        if let Some(prev) = raw_prev.as_ref() {
-            if !prev.is_sentinel() {
-                unsafe {
-                    let prev = INode { __ptr: NonZero::new(raw_prev.ptr) };
-                    prev.inc_count();
-                    return Some(prev);
-                }
-            }
-        }
+    /**
+     * Returns whether this node comes before `other` in the list they're both in, or `None` if
+     * they aren't in the same list. A node doesn't come before itself.
+     *
+     * This walks forward from this node, so takes time proportional to the length of the list.
+     */
+    pub fn is_before(&self, other: &INode<T>) -> Option<bool> {
+        self.link::<Primary>().is_before(other)
+    }
 
-        None
+    /**
+     * Returns the number of links between this node and `other`, in either direction, or `None`
+     * if they aren't in the same list.
+     *
+     * This walks the list from this node, so takes time proportional to its length.
+     */
+    pub fn distance_to(&self, other: &INode<T>) -> Option<usize> {
+        self.link::<Primary>().distance_to(other)
     }
 
     /**
      * Returns whether or not this node is in a list.
      */
     pub fn in_list(&self) -> bool {
-        !self.node().next().is_null()
+        self.link::<Primary>().in_list()
+    }
+
+    /**
+     * Returns the id of the list this node is in, or `None` if it isn't in a list. See
+     * `IList::id`.
+     */
+    pub fn owner_id(&self) -> Option<usize> {
+        self.link::<Primary>().owner_id()
+    }
+
+    /**
+     * Returns the number of references keeping this node alive. This includes the references
+     * held by the lists the node is in, so a node that is in one list and has a single `INode`
+     * handle has a count of 2.
+     */
+    pub fn strong_count(&self) -> usize {
+        self.count()
+    }
+
+    /**
+     * Returns the number of `IWeak` references to this node.
+     */
+    pub fn weak_count(&self) -> usize {
+        self.node().weak.get() - 1
+    }
+
+    /**
+     * Returns whether this handle is the only reference to the node, meaning there are no other
+     * `INode`s or `IWeak`s for it and it isn't in any list.
+     */
+    pub fn is_sole_owner(&self) -> bool {
+        // A linked node always has the list's reference as well, but check both so a node that
+        // is half-way through being linked in isn't reported as unshared.
+        self.count() == 1 && self.weak_count() == 0 && self.node().lists() == 0
+    }
+
+    /**
+     * Creates a new weak reference to this node.
+     */
+    pub fn downgrade(&self) -> IWeak<T> {
+        self.node().inc_weak();
+        IWeak { __ptr: self.__ptr }
     }
 
     fn count(&self) -> usize {
@@ -170,310 +434,4272 @@ impl<T: ?Sized> INode<T> {
         self.node().dec_count();
     }
 
-    fn into_raw(self) -> Raw<Node<T>> {
-        let raw = Raw::new(*self.__ptr);
+    fn links<L: Tag>(&self) -> &NodeLinks<T> {
+        match L::index() {
+            0 => &self.node().links,
+            _ => self.node().secondary()
+        }
+    }
+
+    fn into_raw<L: Tag>(self) -> Raw<NodeLinks<T>> {
+        let raw = self.to_raw::<L>();
         mem::forget(self);
         raw
     }
 
-    fn to_raw(&self) -> Raw<Node<T>> {
-        Raw::new(*self.__ptr)
+    fn to_raw<L: Tag>(&self) -> Raw<NodeLinks<T>> {
+        self.links::<L>().to_raw()
+    }
+
+    /**
+     * Takes over a reference that has already been counted, such as the one held by a list.
+     */
+    unsafe fn from_counted(raw: Raw<NodeLinks<T>>) -> INode<T> {
+        INode { __ptr: NonZero::new((*raw.ptr()).node.get().ptr()) }
     }
 
-    fn from_raw(raw: Raw<Node<T>>) -> INode<T> {
+    fn from_raw(raw: Raw<NodeLinks<T>>) -> INode<T> {
         unsafe {
-            let node = INode { __ptr: NonZero::new(raw.ptr) };
+            let node = INode::from_counted(raw);
             node.inc_count();
             node
         }
     }
 }
 
-impl<T: ?Sized> Drop for INode<T> {
-    fn drop(&mut self) {
-        unsafe {
-            let ptr = *self.__ptr;
+/**
+ * One of a node's sets of links, as returned by `INode::link`. Its methods are the same as the
+ * list methods on `INode`, but work with the list of the kind given by `L`, leaving the node's
+ * place in any other list alone.
+ */
+pub struct ILink<'a, T: ?Sized + 'a, L: Tag> {
+    node: &'a INode<T>,
+    tag: PhantomData<L>
+}
 
-            let vp = ptr as *const ();
+impl<'a, T: ?Sized, L: Tag> ILink<'a, T, L> {
+    /**
+     * Removes the node from the list it is in, if it is in a list.
+     */
+    pub fn remove_from_list(&self) {
+        let prev = self.links().prev.get();
+        let owned = self.links().take_from_list();
 
-            if !vp.is_null() && vp as usize != mem::POST_DROP_USIZE {
-                self.dec_count();
-                if self.count() == 0 {
-                    drop_in_place(&mut (*ptr).data);
-                    deallocate(ptr as *mut u8,
-                               mem::size_of_val(&*ptr),
-                               mem::min_align_of_val(&*ptr));
-                }
-            }
+        if let Some(prev) = prev.as_ref() {
+            prev.debug_check();
         }
-    }
-}
 
-impl<T: ?Sized> Clone for INode<T> {
-    fn clone(&self) -> INode<T> {
-        self.inc_count();
-        INode { __ptr: self.__ptr }
+        // The list's reference is released here, once the node has been completely unlinked
+        drop(owned);
     }
-}
 
-impl<T: ?Sized> Node<T> {
-    fn is_sentinel(&self) -> bool {
-        self.count.get() == !0
+    /**
+     * Inserts the given node after this one.
+     *
+     * Panics if this node isn't in a list.
+     */
+    pub fn insert_after(&self, val: INode<T>) {
+        self.try_insert_after(val).ok().expect("insert_after: node isn't in a list");
     }
 
-    fn inc_count(&self) {
-        let count = self.count.get();
-        self.count.set(count + 1);
-    }
+    /**
+     * Inserts the given node after this one. If this node isn't in a list, the given node is
+     * handed back in the error.
+     */
+    pub fn try_insert_after(&self, val: INode<T>) -> Result<(), NotInList<T>> {
+        if !self.in_list() {
+            return Err(NotInList { node: val });
+        }
 
-    fn dec_count(&self) {
-        let count = self.count.get();
-        self.count.set(count - 1);
+        self.links().insert_after::<L>(val);
+        self.links().debug_check();
+        Ok(())
     }
 
-    fn remove_from_list(&self) {
-        let prev = self.prev.get();
-        let next = self.next.get();
-
-        self.prev.set(Raw::null());
-        self.next.set(Raw::null());
+    /**
+     * Inserts the given node before this one.
+     *
+     * Panics if this node isn't in a list.
+     */
+    pub fn insert_before(&self, val: INode<T>) {
+        self.try_insert_before(val).ok().expect("insert_before: node isn't in a list");
+    }
 
-        if let Some(prev) = prev.as_ref() {
-            // The next pointers for each node are the ones that keep the refcount
-            // up
-            self.dec_count();
-            prev.next.set(next);
+    /**
+     * Inserts the given node before this one. If this node isn't in a list, the given node is
+     * handed back in the error.
+     */
+    pub fn try_insert_before(&self, val: INode<T>) -> Result<(), NotInList<T>> {
+        if !self.in_list() {
+            return Err(NotInList { node: val });
         }
 
-        if let Some(next) = next.as_ref() {
-            next.prev.set(prev);
-        }
+        self.links().insert_before::<L>(val);
+        self.links().debug_check();
+        Ok(())
     }
 
-}
+    /**
+     * Returns the next node in the list, or None if there is no next node.
+     */
+    pub fn next(&self) -> Option<INode<T>> {
+        let raw_next = self.links().next.get();
 
-fn make_sentinel<T: ?Sized>() -> INode<T> {
-    unsafe {
-        let align = mem::min_align_of::<Node<(), T>>();
-        let size  = mem::size_of::<Node<(), T>>();
+        if let Some(next) = raw_next.as_ref() {
+            if !next.is_sentinel() {
+                return Some(INode::from_raw(raw_next));
+            }
+        }
 
-        let mut ptr = allocate(size, align);
+        None
+    }
 
-        let ptr = if raw::is_sized::<T>() {
-            let mut ptr : (*mut _, usize) = (ptr, 0);
+    /**
+     * Returns the previous node in the list, or None if there is no previous node.
+     */
+    pub fn prev(&self) -> Option<INode<T>> {
+        let raw_prev = self.links().prev.get();
 
-            let ptr : *mut *mut Node<T> = &mut ptr as *mut _ as *mut *mut Node<T>;
+        if let Some(prev) = raw_prev.as_ref() {
+            if !prev.is_sentinel() {
+                return Some(INode::from_raw(raw_prev));
+            }
+        }
 
-            *ptr
-        } else {
-            let ptr : *mut *mut Node<T> = &mut ptr as *mut _ as *mut *mut Node<T>;
-            *ptr
-        };
+        None
+    }
 
-        (*ptr).next.set(Raw::null());
-        (*ptr).prev.set(Raw::null());
-        (*ptr).count.set(!0);
+    /**
+     * Returns whether this node comes before `other` in the list they're both in, or `None` if
+     * they aren't in the same list. A node doesn't come before itself.
+     *
+     * This walks forward from this node, so takes time proportional to the length of the list.
+     */
+    pub fn is_before(&self, other: &INode<T>) -> Option<bool> {
+        if !self.same_list(other) {
+            return None;
+        }
 
-        INode { __ptr: NonZero::new(ptr) }
+        Some(!INode::ptr_eq(self.node, other) && self.steps_to(other, true).is_some())
     }
-}
 
-pub struct IList<T: ?Sized> {
-    sentinel: INode<T>
-}
+    /**
+     * Returns the number of links between this node and `other`, in either direction, or `None`
+     * if they aren't in the same list.
+     *
+     * This walks the list from this node, so takes time proportional to its length.
+     */
+    pub fn distance_to(&self, other: &INode<T>) -> Option<usize> {
+        if !self.same_list(other) {
+            return None;
+        }
 
-impl<T: ?Sized> IList<T> {
-    pub fn new() -> IList<T> {
-        let sentinel = make_sentinel::<T>();
-        IList { sentinel: sentinel }
+        self.steps_to(other, true).or_else(|| self.steps_to(other, false))
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.sentinel.node().next.get().is_null()
+    fn same_list(&self, other: &INode<T>) -> bool {
+        let owner = self.links().owner_id();
+        owner != 0 && owner == other.links::<L>().owner_id()
     }
 
     /**
-     * Pushes the given node to the front of the list.
+     * Walks from this node towards one end of the list, returning how many links it took to
+     * reach `other`, or `None` if the walk reached the sentinel first.
      */
-    pub fn push_front(&self, val: INode<T>) {
-        if self.is_empty() {
-            val.remove_from_list();
-            let raw_s = self.sentinel.to_raw();
-            val.node().next.set(raw_s);
-            val.node().prev.set(raw_s);
+    fn steps_to(&self, other: &INode<T>, forwards: bool) -> Option<usize> {
+        let target = other.to_raw::<L>();
+        let mut current = self.node.to_raw::<L>();
+        let mut steps = 0;
 
-            let raw_val = val.into_raw();
+        while let Some(node) = current.as_ref() {
+            if current == target {
+                return Some(steps);
+            }
+            if node.is_sentinel() {
+                break;
+            }
 
-            self.sentinel.node().next.set(raw_val);
-            self.sentinel.node().prev.set(raw_val);
-        } else {
-            self.sentinel.insert_after(val);
+            current = if forwards { node.next.get() } else { node.prev.get() };
+            steps += 1;
         }
+
+        None
     }
 
     /**
-     * Pushes the given node to the back of the list.
+     * Returns whether or not this node is in a list.
      */
-    pub fn push_back(&self, val: INode<T>) {
-        if self.is_empty() {
-            val.remove_from_list();
-            let raw_s = self.sentinel.to_raw();
-            val.node().next.set(raw_s);
-            val.node().prev.set(raw_s);
-
-            let raw_val = val.into_raw();
+    pub fn in_list(&self) -> bool {
+        self.links().is_linked()
+    }
 
-            self.sentinel.node().next.set(raw_val);
-            self.sentinel.node().prev.set(raw_val);
+    /**
+     * Returns the id of the list this node is in, or `None` if it isn't in a list. See
+     * `IList::id`.
+     */
+    pub fn owner_id(&self) -> Option<usize> {
+        let owner = self.links().owner_id();
+        if owner == 0 {
+            None
         } else {
-            self.sentinel.insert_before(val);
+            Some(owner)
         }
     }
 
+    fn links(&self) -> &'a NodeLinks<T> {
+        self.node.links::<L>()
+    }
+}
+
+/**
+ * Nodes whose data can be changed through any handle to them. These are plain nodes holding a
+ * `RefCell`, so the borrow flag is the `RefCell`'s own and lives in the node's data, not beside its
+ * counts. A list of these is an `IList<RefCell<T>>` rather than an `IList<T>`, though `T` can still
+ * be a trait object or slice.
+ */
+impl<T: ?Sized> INode<RefCell<T>> {
     /**
-     * Returns the head of the list, if there is one
+     * Creates a node whose data can be mutably borrowed through shared handles. This is shorthand
+     * for `INode::new(RefCell::new(value))`.
      */
-    pub fn head(&self) -> Option<INode<T>> {
-        if self.is_empty() {
-            None
-        } else {
-            let head = self.sentinel.node().next.get();
-            let head = INode::from_raw(head);
-            Some(head)
+    pub fn new_mut<U: Unsize<T>>(value: U) -> INode<RefCell<T>> {
+        INode::new(RefCell::new(value))
+    }
+
+    /**
+     * Immutably borrows the node's data. Any number of immutable borrows can be held at once.
+     *
+     * Panics if the data is currently mutably borrowed.
+     */
+    pub fn borrow<'a>(&'a self) -> Ref<'a, T> {
+        self.as_ref().borrow()
+    }
+
+    /**
+     * Mutably borrows the node's data, through any handle to the node.
+     *
+     * Panics if the data is currently borrowed.
+     */
+    pub fn borrow_mut<'a>(&'a self) -> RefMut<'a, T> {
+        self.as_ref().borrow_mut()
+    }
+}
+
+/**
+ * Nodes with sized data can have the data replaced through any handle. These go through the
+ * `RefCell`'s borrow flag like `borrow_mut` does, so the data must not be borrowed at the time,
+ * and they panic if it is.
+ */
+impl<T> INode<RefCell<T>> {
+    /**
+     * Replaces the node's data with the given value, returning the old value.
+     *
+     * Panics if the data is currently borrowed.
+     */
+    pub fn replace(&self, val: T) -> T {
+        mem::replace(&mut *self.borrow_mut(), val)
+    }
+
+    /**
+     * Sets the node's data to the given value, dropping the old value.
+     *
+     * Panics if the data is currently borrowed.
+     */
+    pub fn set(&self, val: T) {
+        *self.borrow_mut() = val;
+    }
+
+    /**
+     * Takes the node's data, leaving the default value in its place.
+     *
+     * Panics if the data is currently borrowed.
+     */
+    pub fn take(&self) -> T where T: Default {
+        self.replace(T::default())
+    }
+}
+
+impl<T: ?Sized> Drop for INode<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let ptr = *self.__ptr;
+
+            let vp = ptr as *const ();
+
+            if !vp.is_null() && vp as usize != mem::POST_DROP_USIZE {
+                self.dec_count();
+                if self.count() == 0 {
+                    drop_in_place(&mut (*ptr).data);
+
+                    // Release the weak reference held by the strong references
+                    (*ptr).dec_weak();
+                    if (*ptr).weak.get() == 0 {
+                        free_node(ptr);
+                    }
+                }
+            }
         }
     }
+}
+
+impl<T: ?Sized> ops::Deref for INode<T> {
+    type Target = T;
+
+    fn deref<'a>(&'a self) -> &'a T {
+        self.as_ref()
+    }
+}
+
+impl<T: ?Sized> AsRef<T> for INode<T> {
+    fn as_ref(&self) -> &T {
+        INode::as_ref(self)
+    }
+}
+
+impl<T: ?Sized> Borrow<T> for INode<T> {
+    fn borrow(&self) -> &T {
+        INode::as_ref(self)
+    }
+}
+
+impl<T: ?Sized> Clone for INode<T> {
+    fn clone(&self) -> INode<T> {
+        self.inc_count();
+        INode { __ptr: self.__ptr }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for INode<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_ref(), f)
+    }
+}
+
+/**
+ * `INode`s are compared by identity, not by value: two handles are equal if they refer to the
+ * same node, regardless of what the nodes contain.
+ */
+impl<T: ?Sized> PartialEq for INode<T> {
+    fn eq(&self, other: &INode<T>) -> bool {
+        INode::ptr_eq(self, other)
+    }
+}
+
+impl<T: ?Sized> Eq for INode<T> {}
+
+/**
+ * Hashes the address of the node, to match the identity semantics of `PartialEq`.
+ */
+impl<T: ?Sized> Hash for INode<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (*self.__ptr as *const () as usize).hash(state);
+    }
+}
 
+impl<T: ?Sized> IWeak<T> {
     /**
-     * Returns the tail of the list, if there is one
+     * Returns a strong reference to the node, or `None` if its data has already been dropped.
      */
-    pub fn tail(&self) -> Option<INode<T>> {
-        if self.is_empty() {
+    pub fn upgrade(&self) -> Option<INode<T>> {
+        let node = self.node();
+        if node.count.get() == 0 {
             None
         } else {
-            let tail = self.sentinel.node().prev.get();
-            let tail = INode::from_raw(tail);
-            Some(tail)
+            node.inc_count();
+            Some(INode { __ptr: self.__ptr })
         }
     }
 
-    pub fn iter(&self) -> Iter<T> {
-        Iter {
-            current: self.head()
+    fn node(&self) -> &Node<T> {
+        unsafe {
+            &**self.__ptr
         }
     }
 }
 
-impl<T:?Sized> Drop for IList<T> {
+impl<T: ?Sized> Drop for IWeak<T> {
     fn drop(&mut self) {
         unsafe {
-            let mut node = self.sentinel.node().next.get();
+            let ptr = *self.__ptr;
+
+            let vp = ptr as *const ();
+
+            if !vp.is_null() && vp as usize != mem::POST_DROP_USIZE {
+                (*ptr).dec_weak();
+                if (*ptr).weak.get() == 0 {
+                    free_node(ptr);
+                }
+            }
+        }
+    }
+}
 
-            while !node.is_null() {
+impl<T: ?Sized> Clone for IWeak<T> {
+    fn clone(&self) -> IWeak<T> {
+        self.node().inc_weak();
+        IWeak { __ptr: self.__ptr }
+    }
+}
 
-                let inode = INode::from_raw(node);
-                let next = inode.node().next.get();
+/**
+ * Frees the memory for a node whose data has already been dropped. The memory goes back to this
+ * thread's node cache, to be reused by the next node of the same size.
+ */
+unsafe fn free_node<T: ?Sized>(ptr: *mut Node<T>) {
+    let secondary = (*ptr).secondary.get();
+    if !secondary.is_null() {
+        node_cache::deallocate(secondary.ptr() as *mut u8,
+                               mem::size_of::<NodeLinks<T>>(),
+                               mem::min_align_of::<NodeLinks<T>>());
+    }
+    node_cache::deallocate(ptr as *mut u8,
+                           mem::size_of_val(&*ptr),
+                           mem::min_align_of_val(&*ptr));
+}
 
-                inode.remove_from_list();
+/**
+ * Releases the memory of freed nodes that this thread is holding on to.
+ *
+ * When a node is freed, its memory is kept by the thread that made it, and reused for the next
+ * node of the same size and alignment, so code that makes and drops many similar nodes doesn't
+ * have to go to the heap each time. At most `NODE_CACHE_LIMIT` blocks of each size are kept. This
+ * gives all of them back to the heap. List sentinels aren't cached. Without the `std` feature,
+ * nothing is cached and this does nothing.
+ */
+pub fn trim_node_cache() {
+    node_cache::trim();
+}
 
-                if let Some(n) = next.as_ref() {
-                    if n.is_sentinel() { break; }
-                }
+impl<T: ?Sized> Node<T> {
+    fn inc_count(&self) {
+        let count = self.count.get();
+        self.count.set(count + 1);
+    }
 
-                node = next;
-            }
+    fn dec_count(&self) {
+        let count = self.count.get();
+        self.count.set(count - 1);
+    }
 
-            let sentinel = self.sentinel.__ptr;
-            self.sentinel.__ptr = NonZero::new(Raw::null().ptr);
+    fn inc_weak(&self) {
+        let weak = self.weak.get();
+        self.weak.set(weak + 1);
+    }
 
-            let sentinel = *sentinel as *mut u8;
+    fn dec_weak(&self) {
+        let weak = self.weak.get();
+        self.weak.set(weak - 1);
+    }
 
-            let align = mem::min_align_of::<Node<(), T>>();
-            let size  = mem::size_of::<Node<(), T>>();
+    /**
+     * Returns the node's `Secondary` links, allocating them if they haven't been used before.
+     * They stay with the node until it's freed.
+     */
+    fn secondary(&self) -> &NodeLinks<T> {
+        let mut raw = self.secondary.get();
+        if raw.is_null() {
+            unsafe {
+                let ptr = node_cache::allocate(mem::size_of::<NodeLinks<T>>(),
+                                               mem::min_align_of::<NodeLinks<T>>());
+                let ptr = ptr as *mut NodeLinks<T>;
+                ptr::write(ptr, NodeLinks::new());
+                (*ptr).node.set(Raw::new(self as *const Node<T> as *mut Node<T>));
+                raw = Raw::new(ptr);
+            }
+            self.secondary.set(raw);
+        }
 
-            deallocate(sentinel, size, align);
+        unsafe {
+            &*raw.ptr()
         }
     }
-}
 
-pub struct Iter<T: ?Sized> {
-    current: Option<INode<T>>
+    /**
+     * Returns the number of lists the node is in. This doesn't allocate the `Secondary` links.
+     */
+    fn lists(&self) -> usize {
+        let secondary = self.secondary.get();
+        let in_secondary = secondary.as_ref().map_or(false, |links| links.is_linked());
+        self.links.is_linked() as usize + in_secondary as usize
+    }
 }
 
-impl<T: ?Sized> Iterator for Iter<T> {
-    type Item = INode<T>;
+impl<T: ?Sized> NodeLinks<T> {
+    fn new() -> NodeLinks<T> {
+        NodeLinks {
+            next: Cell::new(Raw::null()),
+            prev: Cell::new(Raw::null()),
+            owner: Cell::new(Raw::null()),
+            node: Cell::new(Raw::null())
+        }
+    }
 
-    fn next(&mut self) -> Option<INode<T>> {
-        let node = self.current.take();
+    fn is_sentinel(&self) -> bool {
+        self.node.get().is_null()
+    }
 
-        if let Some(ref n) = node {
-            self.current = n.next();
-        }
+    fn is_linked(&self) -> bool {
+        // Both links are set when a node is linked in and cleared when it's unlinked, so only a
+        // node with neither link is out of a list.
+        !self.next.get().is_null() || !self.prev.get().is_null()
+    }
 
-        node
+    fn to_raw(&self) -> Raw<NodeLinks<T>> {
+        Raw::new(self as *const NodeLinks<T> as *mut NodeLinks<T>)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use std::fmt::Display;
-    use super::*;
+    /**
+     * Returns the current owner of the list these links are in, or null if they aren't in one.
+     * If the owner they point at has been forwarded, they're pointed straight at the current
+     * one, so the forwarding is only followed once.
+     */
+    fn owner(&self) -> Raw<Owner> {
+        let owner = self.owner.get();
+        let current = Owner::resolve(owner);
+        if current != owner {
+            self.set_owner(current);
+        }
+        current
+    }
 
-    #[test]
-    fn smoketest() {
-        let list : IList<Display> = IList::new();
+    /**
+     * Returns the id of the list these links are in, or 0 if they aren't in one.
+     */
+    fn owner_id(&self) -> usize {
+        match self.owner().as_ref() {
+            Some(owner) => owner.id,
+            None => 0
+        }
+    }
 
-        let node1 = INode::new(1);
-        list.push_back(node1);
+    /**
+     * Panics if these links are in a chain that `IList::sort_by` has taken out of its list. The
+     * chain is linked through the nodes, so they can't be moved until the sort puts them back.
+     */
+    fn check_not_sorting(&self) {
+        if let Some(owner) = self.owner.get().as_ref() {
+            assert!(owner.id != 0, "IList: a node was moved while its list was being sorted");
+        }
+    }
 
-        let node2 = INode::new(2);
-        list.push_back(node2.clone());
+    fn set_owner(&self, owner: Raw<Owner>) {
+        // The new owner is retained first, in case it's the one being released
+        Owner::retain(owner);
+        Owner::release(self.owner.get());
+        self.owner.set(owner);
+    }
 
-        let node3 = INode::new(3);
+    /**
+     * Returns the data of the node these links are part of. Must not be called on a sentinel.
+     */
+    fn data(&self) -> &T {
+        let node = self.node.get();
+        debug_assert!(!node.is_null());
+        unsafe {
+            &(*node.ptr()).data
+        }
+    }
 
-        node2.insert_after(node3);
+    /**
+     * Walks the ring of links these are in, from here back round to here, and panics if they
+     * aren't consistent: every next link must lead to links whose prev link leads back, every
+     * node must lead to the list's owner and be counted at least once for each list it's in,
+     * and there must be exactly one sentinel.
+     *
+     * Each back link is checked before the walk moves on, so the only node the walk can reach
+     * twice is the one it started from, and it ends even if the links have been corrupted, as
+     * long as they still point at live links.
+     */
+    fn check_ring(&self) {
+        let start = self.to_raw();
+        // The owners are resolved without repointing the links, so checking doesn't change
+        // anything
+        let owner = Owner::resolve(self.owner.get());
+        let mut sentinels = 0;
+        let mut current = start;
 
-        let node4 = INode::new("I'm a string");
-        node2.insert_before(node4);
+        loop {
+            let next = {
+                let links = current.as_ref().unwrap();
+                let next = links.next.get();
+                assert!(!next.is_null(), "IList: a linked node has no next link");
+                assert!(next.as_ref().unwrap().prev.get() == current,
+                        "IList: a node's next link doesn't lead back to it");
+                assert!(!owner.is_null() && Owner::resolve(links.owner.get()) == owner,
+                        "IList: a node has the wrong list id");
 
-        let mut node = list.head().unwrap();
-        assert_eq!(node.as_ref().to_string(), "1");
+                if links.is_sentinel() {
+                    sentinels += 1;
+                } else {
+                    let node = links.node.get();
+                    let node = node.as_ref().unwrap();
+                    assert!(node.count.get() >= node.lists(),
+                            "IList: a node has fewer references than the lists it's in");
+                }
 
-        node = node.next().unwrap();
-        assert_eq!(node.as_ref().to_string(), "I'm a string");
+                next
+            };
 
-        node = node.next().unwrap();
-        assert_eq!(node.as_ref().to_string(), "2");
+            current = next;
+            if current == start { break; }
+        }
 
-        node = node.next().unwrap();
-        assert_eq!(node.as_ref().to_string(), "3");
+        assert!(sentinels == 1, "IList: the links don't have exactly one sentinel");
+    }
 
-        assert!(node.next().is_none());
+    /**
+     * Calls `check_ring` in debug builds, if these links are in a list.
+     */
+    fn debug_check(&self) {
+        if cfg!(debug_assertions) && self.is_linked() {
+            self.check_ring();
+        }
     }
 
-    #[test]
-    fn move_lists() {
-        let list1 : IList<Display> = IList::new();
+    /**
+     * Takes the node these links are part of out of its list, and returns the reference the
+     * list held to it, or `None` if it wasn't in a list.
+     *
+     * The count isn't touched here, so the node can't be freed while it's being unlinked. It's
+     * freed when the returned handle is dropped, if that was the last reference.
+     */
+    fn take_from_list(&self) -> Option<INode<T>> {
+        self.check_not_sorting();
+        let prev = self.prev.get();
+        let next = self.next.get();
 
-        let node1 = INode::new(1);
-        list1.push_back(node1);
+        self.prev.set(Raw::null());
+        self.next.set(Raw::null());
+        self.set_owner(Raw::null());
 
-        let node2 = INode::new(2);
-        list1.push_back(node2.clone());
+        if prev == next {
+            // Either this wasn't in a list, or it was the only node and both links point at the
+            // sentinel, in which case the list goes back to being empty
+            match prev.as_ref() {
+                Some(sentinel) => {
+                    sentinel.next.set(Raw::null());
+                    sentinel.prev.set(Raw::null());
+                }
+                None => return None
+            }
+        } else {
+            if let Some(prev) = prev.as_ref() {
+                prev.next.set(next);
+            }
 
-        let list2 : IList<Display> = IList::new();
+            if let Some(next) = next.as_ref() {
+                next.prev.set(prev);
+            }
 
-        let node3 = INode::new(3);
-        list2.push_back(node3);
-        list2.push_back(node2);
+            // The next pointers are the ones that hold the list's references, so a node that
+            // only had a next link didn't have one
+            if prev.is_null() {
+                return None;
+            }
+        }
 
-        let node = list1.head().unwrap();
-        assert_eq!(node.as_ref().to_string(), "1");
+        unsafe {
+            Some(INode::from_counted(self.to_raw()))
+        }
+    }
+
+    /**
+     * Links the given node in after this one, handing its reference over to the list. This
+     * must be in a list, or be a list's sentinel.
+     */
+    fn insert_after<L: Tag>(&self, val: INode<T>) {
+        self.check_not_sorting();
+        val.link::<L>().remove_from_list();
+        let next = self.next.get();
+
+        val.links::<L>().prev.set(self.to_raw());
+        val.links::<L>().next.set(next);
+        val.links::<L>().set_owner(self.owner());
+
+        let raw_val = val.into_raw::<L>();
+        self.next.set(raw_val);
+
+        if let Some(next) = next.as_ref() {
+            next.prev.set(raw_val);
+        }
+    }
+
+    /**
+     * Links the given node in before this one, handing its reference over to the list. This
+     * must be in a list, or be a list's sentinel.
+     */
+    fn insert_before<L: Tag>(&self, val: INode<T>) {
+        self.check_not_sorting();
+        val.link::<L>().remove_from_list();
+        let prev = self.prev.get();
+
+        val.links::<L>().next.set(self.to_raw());
+        val.links::<L>().prev.set(prev);
+        val.links::<L>().set_owner(self.owner());
+
+        let raw_val = val.into_raw::<L>();
+        self.prev.set(raw_val);
+
+        if let Some(prev) = prev.as_ref() {
+            prev.next.set(raw_val);
+        }
+    }
+}
+
+/**
+ * Merges two sorted runs of nodes linked through `next`. Nodes from `a` come first when they
+ * compare equal.
+ */
+fn merge_runs<T: ?Sized, F>(mut a: Raw<NodeLinks<T>>, mut b: Raw<NodeLinks<T>>,
+                            cmp: &mut F) -> Raw<NodeLinks<T>> where F: FnMut(&T, &T) -> Ordering {
+    let mut head = Raw::null();
+    let mut tail : Raw<NodeLinks<T>> = Raw::null();
+
+    loop {
+        let take_a = match (a.as_ref(), b.as_ref()) {
+            (Some(x), Some(y)) => cmp(x.data(), y.data()) != Ordering::Greater,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break
+        };
+
+        let node = if take_a {
+            let node = a;
+            a = node.as_ref().unwrap().next.get();
+            node
+        } else {
+            let node = b;
+            b = node.as_ref().unwrap().next.get();
+            node
+        };
+
+        match tail.as_ref() {
+            Some(tail_node) => tail_node.next.set(node),
+            None => head = node
+        }
+        tail = node;
+    }
+
+    head
+}
+
+/**
+ * The owner of a list, which every set of links in the list points at, and which carries the
+ * list's id.
+ *
+ * When all of one list's nodes are moved into another, the first list's owner is forwarded to
+ * the second's, and the first list gets a new one, so the moved nodes don't each have to be
+ * updated. An owner is freed once nothing points at it, either directly or through forwarding.
+ */
+struct Owner {
+    // 0 for the owner of the nodes `IList::sort_by` has taken out of their list
+    id: usize,
+    // The links pointing at this owner, plus the owners forwarded to it
+    count: Cell<usize>,
+    // The owner this one has been forwarded to, or null if it's still current
+    forward: Cell<Raw<Owner>>
+}
+
+impl Owner {
+    fn new(id: usize) -> Raw<Owner> {
+        unsafe {
+            let ptr = node_cache::allocate_uncached(mem::size_of::<Owner>(),
+                                                    mem::min_align_of::<Owner>());
+            let ptr = ptr as *mut Owner;
+            ptr::write(ptr, Owner {
+                id: id,
+                count: Cell::new(0),
+                forward: Cell::new(Raw::null())
+            });
+            Raw::new(ptr)
+        }
+    }
+
+    /**
+     * Follows any forwarding from `owner` to the owner that's still current.
+     */
+    fn resolve(mut owner: Raw<Owner>) -> Raw<Owner> {
+        loop {
+            let forward = match owner.as_ref() {
+                Some(o) => o.forward.get(),
+                None => return owner
+            };
+            if forward.is_null() {
+                return owner;
+            }
+            owner = forward;
+        }
+    }
+
+    /**
+     * Forwards `from`, which must be current, to `to`. Every set of links pointing at `from` is
+     * then in `to`'s list.
+     */
+    fn forward(from: Raw<Owner>, to: Raw<Owner>) {
+        let owner = from.as_ref().unwrap();
+        debug_assert!(owner.forward.get().is_null());
+        Owner::retain(to);
+        owner.forward.set(to);
+    }
+
+    fn retain(owner: Raw<Owner>) {
+        if let Some(owner) = owner.as_ref() {
+            owner.count.set(owner.count.get() + 1);
+        }
+    }
+
+    /**
+     * Releases a reference to `owner`. If that was the last one, the owner is freed, and its
+     * reference to the owner it was forwarded to is released in turn.
+     */
+    fn release(mut owner: Raw<Owner>) {
+        loop {
+            let forward = match owner.as_ref() {
+                Some(o) => {
+                    let count = o.count.get() - 1;
+                    o.count.set(count);
+                    if count > 0 {
+                        return;
+                    }
+                    o.forward.get()
+                }
+                None => return
+            };
+
+            unsafe {
+                node_cache::deallocate_uncached(owner.ptr() as *mut u8,
+                                                mem::size_of::<Owner>(),
+                                                mem::min_align_of::<Owner>());
+            }
+            owner = forward;
+        }
+    }
+}
+
+/**
+ * Allocates the sentinel for a new list. It's only a set of links, with no node or data around
+ * it, so nothing about it depends on what `T` is.
+ */
+fn make_sentinel<T: ?Sized>(id: usize) -> Raw<NodeLinks<T>> {
+    unsafe {
+        let ptr = node_cache::allocate_uncached(mem::size_of::<NodeLinks<T>>(),
+                                                mem::min_align_of::<NodeLinks<T>>());
+        let ptr = ptr as *mut NodeLinks<T>;
+        ptr::write(ptr, NodeLinks::new());
+        (*ptr).set_owner(Owner::new(id));
+        Raw::new(ptr)
+    }
+}
+
+/**
+ * Frees a sentinel from `make_sentinel`, once it's been taken off its owner.
+ */
+unsafe fn free_sentinel<T: ?Sized>(sentinel: Raw<NodeLinks<T>>) {
+    node_cache::deallocate_uncached(sentinel.ptr() as *mut u8,
+                                    mem::size_of::<NodeLinks<T>>(),
+                                    mem::min_align_of::<NodeLinks<T>>());
+}
+
+static NEXT_LIST_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+pub struct IList<T: ?Sized, L: Tag = Primary> {
+    // Null until a node is first linked in, so a list that's never used doesn't allocate
+    sentinel: Cell<Raw<NodeLinks<T>>>,
+    // Every node in the list points at an owner with this id, so membership can be checked
+    // without a walk
+    id: usize,
+    tag: PhantomData<L>
+}
+
+impl<T: ?Sized, L: Tag> IList<T, L> {
+    pub fn new() -> IList<T, L> {
+        IList {
+            sentinel: Cell::new(Raw::null()),
+            // Ids are never reused, so a node can't be mistaken for being in a list that has
+            // since been dropped, and 0 means a node isn't in a list
+            id: NEXT_LIST_ID.fetch_add(1, atomic::Ordering::Relaxed) + 1,
+            tag: PhantomData
+        }
+    }
+
+    /**
+     * Returns the list's sentinel, allocating it if the list hasn't needed one yet.
+     */
+    fn sentinel(&self) -> &NodeLinks<T> {
+        if self.sentinel.get().is_null() {
+            self.sentinel.set(make_sentinel(self.id));
+        }
+
+        unsafe {
+            &*self.sentinel.get().ptr()
+        }
+    }
+
+    /**
+     * Returns the head's links, or null if the list is empty. Unlike going through `sentinel`,
+     * this never allocates.
+     */
+    fn head_raw(&self) -> Raw<NodeLinks<T>> {
+        match self.sentinel.get().as_ref() {
+            Some(sentinel) => sentinel.next.get(),
+            None => Raw::null()
+        }
+    }
+
+    /**
+     * Creates a list from the nodes produced by the iterator, in order. Each node is removed from
+     * any list it's already in.
+     *
+     * This can't be a `FromIterator` impl, as it would overlap with the one that wraps values in
+     * new nodes.
+     */
+    pub fn from_nodes<I: IntoIterator<Item=INode<T>>>(iter: I) -> IList<T, L> {
+        let list = IList::new();
+        list.extend_nodes(iter);
+        list
+    }
+
+    /**
+     * Pushes the nodes produced by the iterator to the back of the list, in order. Each node is
+     * removed from any list it's already in.
+     */
+    pub fn extend_nodes<I: IntoIterator<Item=INode<T>>>(&self, iter: I) {
+        for node in iter {
+            self.push_back(node);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head_raw().is_null()
+    }
+
+    /**
+     * Checks that the list's links are consistent, and panics if they've been corrupted. This
+     * walks the whole list, checking that each node's next link leads to a node whose prev link
+     * leads back, that the walk comes back round to the sentinel without meeting another one,
+     * and that each node carries the list's id and has at least as many references as lists
+     * it's in.
+     *
+     * This is meant for debugging; it's used in debug builds to check the list after any
+     * operation that relinks nodes. A corrupted link can point anywhere, so it can only catch
+     * corruption that still leaves the links pointing at live nodes.
+     */
+    pub fn check_invariants(&self) {
+        if self.sentinel.get().is_null() {
+            return;
+        }
+
+        let sentinel = self.sentinel();
+        assert!(sentinel.owner_id() == self.id, "IList: the sentinel has the wrong list id");
+
+        if sentinel.next.get().is_null() || sentinel.prev.get().is_null() {
+            assert!(sentinel.next.get().is_null() && sentinel.prev.get().is_null(),
+                    "IList: an empty list's sentinel has a link");
+        } else {
+            sentinel.check_ring();
+        }
+    }
+
+    fn debug_check(&self) {
+        if cfg!(debug_assertions) {
+            self.check_invariants();
+        }
+    }
+
+    /**
+     * Returns an identifier for this list. Identifiers are never reused, even once a list has
+     * been dropped. It can be compared against `INode::owner_id`.
+     */
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /**
+     * Returns whether the given node is in this list. Every node points at the owner of the
+     * list it's in, so this takes constant time.
+     */
+    pub fn contains(&self, node: &INode<T>) -> bool {
+        node.links::<L>().owner_id() == self.id
+    }
+
+    /**
+     * Removes the given node from this list. Returns false, leaving the node where it is, if the
+     * node isn't in this list.
+     */
+    pub fn remove(&self, node: &INode<T>) -> bool {
+        if self.contains(node) {
+            node.link::<L>().remove_from_list();
+            true
+        } else {
+            false
+        }
+    }
+
+    /**
+     * Pushes the given node to the front of the list.
+     */
+    pub fn push_front(&self, val: INode<T>) {
+        if self.is_empty() {
+            val.link::<L>().remove_from_list();
+            let raw_s = self.sentinel().to_raw();
+            val.links::<L>().next.set(raw_s);
+            val.links::<L>().prev.set(raw_s);
+            val.links::<L>().set_owner(self.sentinel().owner());
+
+            let raw_val = val.into_raw::<L>();
+
+            self.sentinel().next.set(raw_val);
+            self.sentinel().prev.set(raw_val);
+        } else {
+            self.sentinel().insert_after::<L>(val);
+        }
+
+        self.debug_check();
+    }
+
+    /**
+     * Pushes the given node to the back of the list.
+     */
+    pub fn push_back(&self, val: INode<T>) {
+        if self.is_empty() {
+            val.link::<L>().remove_from_list();
+            let raw_s = self.sentinel().to_raw();
+            val.links::<L>().next.set(raw_s);
+            val.links::<L>().prev.set(raw_s);
+            val.links::<L>().set_owner(self.sentinel().owner());
+
+            let raw_val = val.into_raw::<L>();
+
+            self.sentinel().next.set(raw_val);
+            self.sentinel().prev.set(raw_val);
+        } else {
+            self.sentinel().insert_before::<L>(val);
+        }
+
+        self.debug_check();
+    }
+
+    /**
+     * Pushes an already-boxed value to the front of the list, in a new node. See
+     * `INode::new_boxed`.
+     */
+    pub fn push_front_boxed(&self, val: Box<T>) {
+        self.push_front(INode::new_boxed(val));
+    }
+
+    /**
+     * Pushes an already-boxed value to the back of the list, in a new node. See
+     * `INode::new_boxed`.
+     */
+    pub fn push_back_boxed(&self, val: Box<T>) {
+        self.push_back(INode::new_boxed(val));
+    }
+
+    /**
+     * Returns the head of the list, if there is one
+     */
+    pub fn head(&self) -> Option<INode<T>> {
+        if self.is_empty() {
+            None
+        } else {
+            let head = self.sentinel().next.get();
+            let head = INode::from_raw(head);
+            Some(head)
+        }
+    }
+
+    /**
+     * Returns the tail of the list, if there is one
+     */
+    pub fn tail(&self) -> Option<INode<T>> {
+        if self.is_empty() {
+            None
+        } else {
+            let tail = self.sentinel().prev.get();
+            let tail = INode::from_raw(tail);
+            Some(tail)
+        }
+    }
+
+    /**
+     * Returns a reference to the data in the head of the list, if there is one, without
+     * touching the node's reference count.
+     *
+     * This is unsafe for the same reason as `iter_data`: the node must not be removed from the
+     * list while the reference is alive.
+     */
+    pub unsafe fn peek_front<'a>(&'a self) -> Option<&'a T> {
+        if self.is_empty() {
+            None
+        } else {
+            let head = self.sentinel().next.get();
+            Some((*head.ptr()).data())
+        }
+    }
+
+    /**
+     * Returns a reference to the data in the tail of the list, if there is one, without
+     * touching the node's reference count.
+     *
+     * This is unsafe for the same reason as `iter_data`: the node must not be removed from the
+     * list while the reference is alive.
+     */
+    pub unsafe fn peek_back<'a>(&'a self) -> Option<&'a T> {
+        if self.is_empty() {
+            None
+        } else {
+            let tail = self.sentinel().prev.get();
+            Some((*tail.ptr()).data())
+        }
+    }
+
+    /**
+     * Removes the head of the list and returns it, if there is one. The list's reference to the
+     * node is handed over to the returned `INode`.
+     */
+    pub fn pop_front(&self) -> Option<INode<T>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let raw_head = self.sentinel().next.get();
+        let head = raw_head.as_ref().unwrap();
+        let next = head.next.get();
+
+        head.next.set(Raw::null());
+        head.prev.set(Raw::null());
+        head.set_owner(Raw::null());
+
+        if next.as_ref().unwrap().is_sentinel() {
+            // That was the only node, so the list is empty again
+            self.sentinel().next.set(Raw::null());
+            self.sentinel().prev.set(Raw::null());
+        } else {
+            self.sentinel().next.set(next);
+            next.as_ref().unwrap().prev.set(self.sentinel().to_raw());
+        }
+
+        unsafe {
+            Some(INode::from_counted(raw_head))
+        }
+    }
+
+    /**
+     * Removes the tail of the list and returns it, if there is one. The list's reference to the
+     * node is handed over to the returned `INode`.
+     */
+    pub fn pop_back(&self) -> Option<INode<T>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let raw_tail = self.sentinel().prev.get();
+        let tail = raw_tail.as_ref().unwrap();
+        let prev = tail.prev.get();
+
+        tail.next.set(Raw::null());
+        tail.prev.set(Raw::null());
+        tail.set_owner(Raw::null());
+
+        if prev.as_ref().unwrap().is_sentinel() {
+            // That was the only node, so the list is empty again
+            self.sentinel().next.set(Raw::null());
+            self.sentinel().prev.set(Raw::null());
+        } else {
+            self.sentinel().prev.set(prev);
+            prev.as_ref().unwrap().next.set(self.sentinel().to_raw());
+        }
+
+        unsafe {
+            Some(INode::from_counted(raw_tail))
+        }
+    }
+
+    /**
+     * Removes the head of the list and moves its data out, if there is a head. This is
+     * `pop_front` followed by `INode::try_unwrap`: the data can only be moved out if the list
+     * held the only reference to the node, so if there are any other handles to it, the node is
+     * still removed, but is returned in the `Err` instead.
+     */
+    pub fn pop_front_value(&self) -> Option<Result<T, INode<T>>> where T: Sized {
+        self.pop_front().map(INode::try_unwrap)
+    }
+
+    /**
+     * Removes the tail of the list and moves its data out, if there is a tail. As with
+     * `pop_front_value`, the node is returned in the `Err` instead if anything else refers to it.
+     */
+    pub fn pop_back_value(&self) -> Option<Result<T, INode<T>>> where T: Sized {
+        self.pop_back().map(INode::try_unwrap)
+    }
+
+    /**
+     * Moves all the nodes in `other` to the end of this list, leaving `other` empty.
+     *
+     * This takes constant time. The nodes are linked in without touching their reference
+     * counts, and rather than each moved node being updated, `other`'s owner is forwarded to
+     * this list's.
+     */
+    pub fn append(&self, other: &IList<T, L>) {
+        if other.is_empty() || self.sentinel.get() == other.sentinel.get() {
+            return;
+        }
+
+        let raw_s = self.sentinel().to_raw();
+        let tail = if self.is_empty() { raw_s } else { self.sentinel().prev.get() };
+        self.splice_between(tail, raw_s, other);
+    }
+
+    /**
+     * Moves all the nodes in `other` into this list, right after `at`, leaving `other` empty.
+     *
+     * As with `append`, this takes constant time.
+     *
+     * Panics if `at` isn't in this list.
+     */
+    pub fn splice_after(&self, at: &INode<T>, other: &IList<T, L>) {
+        assert!(self.contains(at));
+
+        if other.is_empty() || self.sentinel.get() == other.sentinel.get() {
+            return;
+        }
+
+        let next = at.links::<L>().next.get();
+        self.splice_between(at.to_raw::<L>(), next, other);
+    }
+
+    /**
+     * Moves all the nodes in `other` into this list, right before `at`, leaving `other` empty.
+     *
+     * As with `append`, this takes constant time.
+     *
+     * Panics if `at` isn't in this list.
+     */
+    pub fn splice_before(&self, at: &INode<T>, other: &IList<T, L>) {
+        assert!(self.contains(at));
+
+        if other.is_empty() || self.sentinel.get() == other.sentinel.get() {
+            return;
+        }
+
+        let prev = at.links::<L>().prev.get();
+        self.splice_between(prev, at.to_raw::<L>(), other);
+    }
+
+    /**
+     * Moves the nodes of `other`, which must be a different, non-empty list, in between `prev`
+     * and `next`. Those are either neighbours in this list, or both this list's sentinel when
+     * it's empty.
+     */
+    fn splice_between(&self, prev: Raw<NodeLinks<T>>, next: Raw<NodeLinks<T>>,
+                      other: &IList<T, L>) {
+        let other_sentinel = other.sentinel();
+
+        let first = other_sentinel.next.get();
+        let last = other_sentinel.prev.get();
+
+        // The moved nodes keep pointing at the other list's owner, which now leads to this
+        // list's, and the other list starts afresh
+        Owner::forward(other_sentinel.owner(), self.sentinel().owner());
+        other_sentinel.set_owner(Owner::new(other.id));
+
+        // The reference held by the other sentinel's next pointer moves to `prev`, and the one
+        // `prev` held for `next` moves to `last`
+        prev.as_ref().unwrap().next.set(first);
+        first.as_ref().unwrap().prev.set(prev);
+        last.as_ref().unwrap().next.set(next);
+        next.as_ref().unwrap().prev.set(last);
+
+        other_sentinel.next.set(Raw::null());
+        other_sentinel.prev.set(Raw::null());
+
+        self.debug_check();
+        other.debug_check();
+    }
+
+    /**
+     * Moves `node` and every node after it into a new list, which is returned.
+     *
+     * The links are updated in constant time, but unlike `append`, the moved nodes have to be
+     * pointed at the new list's owner one by one, which takes time proportional to the number of
+     * nodes moved.
+     *
+     * Panics if `node` isn't in this list.
+     */
+    pub fn split_at(&self, node: &INode<T>) -> IList<T, L> {
+        assert!(self.contains(node));
+
+        let rest : IList<T, L> = IList::new();
+        let raw_rest = rest.sentinel().to_raw();
+        let raw_s = self.sentinel().to_raw();
+        let sentinel = self.sentinel();
+
+        let first = node.to_raw::<L>();
+        let last = sentinel.prev.get();
+        let before = node.links::<L>().prev.get();
+
+        let rest_owner = rest.sentinel().owner();
+        let mut current = first;
+        loop {
+            let n = current.as_ref().unwrap();
+            n.set_owner(rest_owner);
+            if current == last { break; }
+            current = n.next.get();
+        }
+
+        // The reference held by `before`'s next pointer moves to the new sentinel
+        rest.sentinel().next.set(first);
+        rest.sentinel().prev.set(last);
+        node.links::<L>().prev.set(raw_rest);
+        last.as_ref().unwrap().next.set(raw_rest);
+
+        if before == raw_s {
+            sentinel.next.set(Raw::null());
+            sentinel.prev.set(Raw::null());
+        } else {
+            before.as_ref().unwrap().next.set(raw_s);
+            sentinel.prev.set(before);
+        }
+
+        self.debug_check();
+        rest.debug_check();
+        rest
+    }
+
+    /**
+     * Moves the given node to the front of this list. Does nothing if it's already there.
+     *
+     * Panics if `node` isn't in this list.
+     */
+    pub fn move_to_front(&self, node: &INode<T>) {
+        assert!(self.contains(node));
+
+        let raw_s = self.sentinel().to_raw();
+        let sentinel = self.sentinel();
+        let raw_node = node.to_raw::<L>();
+
+        if sentinel.next.get() == raw_node {
+            return;
+        }
+
+        // Each next pointer takes over the reference held by the one it replaces, so the counts
+        // don't change
+        self.unlink(node.links::<L>());
+
+        let head = sentinel.next.get();
+        node.links::<L>().prev.set(raw_s);
+        node.links::<L>().next.set(head);
+        head.as_ref().unwrap().prev.set(raw_node);
+        sentinel.next.set(raw_node);
+        self.debug_check();
+    }
+
+    /**
+     * Moves the given node to the back of this list. Does nothing if it's already there.
+     *
+     * Panics if `node` isn't in this list.
+     */
+    pub fn move_to_back(&self, node: &INode<T>) {
+        assert!(self.contains(node));
+
+        let raw_s = self.sentinel().to_raw();
+        let sentinel = self.sentinel();
+        let raw_node = node.to_raw::<L>();
+
+        if sentinel.prev.get() == raw_node {
+            return;
+        }
+
+        self.unlink(node.links::<L>());
+
+        let tail = sentinel.prev.get();
+        node.links::<L>().next.set(raw_s);
+        node.links::<L>().prev.set(tail);
+        tail.as_ref().unwrap().next.set(raw_node);
+        sentinel.prev.set(raw_node);
+        self.debug_check();
+    }
+
+    /**
+     * Joins up the neighbours of a node in this list, leaving the node's own links and the
+     * reference counts as they are.
+     */
+    fn unlink(&self, node: &NodeLinks<T>) {
+        let prev = node.prev.get();
+        let next = node.next.get();
+        prev.as_ref().unwrap().next.set(next);
+        next.as_ref().unwrap().prev.set(prev);
+    }
+
+    /**
+     * Removes every node whose data doesn't satisfy the predicate from the list. Nodes that were
+     * only kept alive by the list are freed.
+     */
+    pub fn retain<F: FnMut(&T) -> bool>(&self, mut f: F) {
+        self.retain_nodes(|node| f(node.as_ref()))
+    }
+
+    /**
+     * Removes every node that doesn't satisfy the predicate from the list. The predicate is
+     * given a handle to each node, which it may clone to keep the node alive.
+     *
+     * The predicate may remove the node it is given from the list, but shouldn't otherwise
+     * change the list.
+     */
+    pub fn retain_nodes<F: FnMut(&INode<T>) -> bool>(&self, mut f: F) {
+        let mut current = self.head();
+
+        while let Some(node) = current {
+            // Get the next node first, so the walk can carry on if this one is removed
+            current = node.link::<L>().next();
+
+            if !f(&node) {
+                node.link::<L>().remove_from_list();
+            }
+        }
+    }
+
+    /**
+     * Calls `f` with a handle to each node in the list, in order. This is meant for lists of
+     * observers, where a callback may need to change the list while it's being notified.
+     *
+     * The walk holds handles to the current node and the one after it, so neither is freed
+     * during the call. While `f` is running for a node, it may:
+     *
+     * * Remove that node. The walk carries on from the node that followed it.
+     * * Remove any other node, including the one after it. Removed nodes that haven't been
+     *   reached yet aren't visited.
+     * * Push nodes onto the back of the list, or insert them after the current node. These are
+     *   visited later in the same walk. Nodes pushed onto the front aren't.
+     *
+     * If `f` removes both the current node and the one after it, the walk carries on after the
+     * last node it visited that's still in the list, or from the head if there isn't one. It
+     * only stops early if that node has been removed as well. Moving, sorting or reversing nodes
+     * part-way through may cause them to be skipped or visited twice.
+     */
+    pub fn for_each_node<F: FnMut(&INode<T>)>(&self, mut f: F) {
+        let mut current = self.head();
+        // The last node that was still in the list after its call, to find the way back in if a
+        // call removes both its node and the next one
+        let mut last : Option<INode<T>> = None;
+
+        while let Some(node) = current {
+            let next = node.link::<L>().next();
+
+            f(&node);
+
+            current = if self.contains(&node) {
+                // Read the next node again, so any nodes inserted after this one are reached
+                let next = node.link::<L>().next();
+                last = Some(node);
+                next
+            } else if next.as_ref().map_or(false, |next| self.contains(next)) {
+                next
+            } else {
+                match last {
+                    Some(ref last) if self.contains(last) => last.link::<L>().next(),
+                    Some(_) => None,
+                    None => self.head()
+                }
+            };
+        }
+    }
+
+    /**
+     * Returns an iterator that takes each node out of the list as it's reached, handing over the
+     * list's reference to it. Any nodes that haven't been reached when the iterator is dropped
+     * are removed as well, leaving the list empty.
+     */
+    pub fn drain<'a>(&'a self) -> Drain<'a, T, L> {
+        Drain { list: self }
+    }
+
+    /**
+     * Returns the first node whose data satisfies the predicate, if there is one.
+     */
+    pub fn find<F: FnMut(&T) -> bool>(&self, f: F) -> Option<INode<T>> {
+        self.find_node(f).map(|(_, node)| node)
+    }
+
+    /**
+     * Returns the index of the first node whose data satisfies the predicate, if there is one.
+     */
+    pub fn position<F: FnMut(&T) -> bool>(&self, f: F) -> Option<usize> {
+        self.find_node(f).map(|(i, _)| i)
+    }
+
+    /**
+     * Returns the node at the given index, or `None` if the list is too short.
+     */
+    pub fn nth(&self, n: usize) -> Option<INode<T>> {
+        let mut current = self.head_raw();
+        let mut i = 0;
+
+        while let Some(node) = current.as_ref() {
+            if node.is_sentinel() {
+                break;
+            }
+            if i == n {
+                return Some(INode::from_raw(current));
+            }
+
+            current = node.next.get();
+            i += 1;
+        }
+
+        None
+    }
+
+    fn find_node<F: FnMut(&T) -> bool>(&self, mut f: F) -> Option<(usize, INode<T>)> {
+        let mut current = self.head_raw();
+        let mut i = 0;
+
+        while let Some(node) = current.as_ref() {
+            if node.is_sentinel() {
+                break;
+            }
+
+            // The predicate could remove the node from the list, so keep it alive while the
+            // predicate runs. If it is removed, its next pointer is cleared and the search ends.
+            let node = INode::from_raw(current);
+            if f(node.as_ref()) {
+                return Some((i, node));
+            }
+
+            current = node.links::<L>().next.get();
+            i += 1;
+        }
+
+        None
+    }
+
+    /**
+     * Reverses the order of the nodes in the list. This swaps the links of each node in a
+     * single pass, so handles to the nodes stay valid.
+     */
+    pub fn reverse(&self) {
+        if self.is_empty() {
+            return;
+        }
+
+        // Each node still has exactly one next pointer leading to it afterwards, so the
+        // references the list holds don't change
+        let raw_s = self.sentinel().to_raw();
+        let mut current = raw_s;
+        loop {
+            let links = current.as_ref().unwrap();
+            let next = links.next.get();
+            links.next.set(links.prev.get());
+            links.prev.set(next);
+
+            current = next;
+            if current == raw_s { break; }
+        }
+
+        self.debug_check();
+    }
+
+    /**
+     * Sorts the list. The sort is stable, and works by relinking the nodes, so handles to them
+     * stay valid.
+     */
+    pub fn sort(&self) where T: Ord {
+        self.sort_by(|a, b| a.cmp(b))
+    }
+
+    /**
+     * Sorts the list using the given comparison function. This is a stable, bottom-up merge sort
+     * that relinks the nodes rather than moving their data.
+     *
+     * The list is empty while the sort runs. The nodes being sorted aren't in any list, and
+     * moving one of them, or inserting next to one, from the comparison function panics. If the
+     * comparison function panics, the nodes are leaked.
+     */
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&self, mut cmp: F) {
+        if self.is_empty() {
+            return;
+        }
+
+        let sentinel = self.sentinel();
+        let raw_s = sentinel.to_raw();
+
+        if sentinel.next.get() == sentinel.prev.get() {
+            return;
+        }
+
+        // Take the nodes out as a chain linked through `next`. The next pointers still hold the
+        // list's references, and `head` holds the one the sentinel had.
+        let mut head = sentinel.next.get();
+        sentinel.prev.get().as_ref().unwrap().next.set(Raw::null());
+        sentinel.next.set(Raw::null());
+        sentinel.prev.set(Raw::null());
+
+        // The comparison function can reach the nodes, so before it's called they're all given
+        // an owner that no list has. That keeps them out of `contains`, and stops anything from
+        // relinking them while they're in the chain.
+        let sorting = Owner::new(0);
+        let mut current = head;
+        while let Some(node) = current.as_ref() {
+            node.prev.set(Raw::null());
+            node.set_owner(sorting);
+            current = node.next.get();
+        }
+
+        // `runs[i]` is either null or a sorted run of `2^i` nodes. Runs with higher indices
+        // contain earlier nodes, which is what keeps the sort stable.
+        let mut runs = [Raw::null(); 64];
+        while !head.is_null() {
+            let mut carry = head;
+            {
+                let node = carry.as_ref().unwrap();
+                head = node.next.get();
+                node.next.set(Raw::null());
+            }
+
+            let mut i = 0;
+            while !runs[i].is_null() {
+                carry = merge_runs(runs[i], carry, &mut cmp);
+                runs[i] = Raw::null();
+                i += 1;
+            }
+            runs[i] = carry;
+        }
+
+        let mut sorted = Raw::null();
+        for run in runs.iter() {
+            if !run.is_null() {
+                sorted = merge_runs(*run, sorted, &mut cmp);
+            }
+        }
+
+        // Put the nodes back, after anything the comparison function added to the list
+        let owner = sentinel.owner();
+        let mut tail = if self.is_empty() { raw_s } else { sentinel.prev.get() };
+        let mut current = sorted;
+        while let Some(node) = current.as_ref() {
+            let next = node.next.get();
+            node.set_owner(owner);
+            node.prev.set(tail);
+            tail.as_ref().unwrap().next.set(current);
+            tail = current;
+            current = next;
+        }
+
+        tail.as_ref().unwrap().next.set(raw_s);
+        sentinel.prev.set(tail);
+        self.debug_check();
+    }
+
+    /**
+     * Returns a cursor for this list that starts at the beginning of the list.
+     *
+     * See the documentation for `ICursor` for more details.
+     */
+    pub fn cursor<'a>(&'a self) -> ICursor<'a, T, L> {
+        ICursor {
+            list: self,
+            prev: None,
+            next: self.head()
+        }
+    }
+
+    pub fn iter(&self) -> Iter<T, L> {
+        Iter {
+            current: self.head(),
+            back: self.tail(),
+            tag: PhantomData
+        }
+    }
+
+    /**
+     * Returns an iterator over the nodes in the list, from the tail to the head.
+     */
+    pub fn iter_rev(&self) -> iter::Rev<Iter<T, L>> {
+        self.iter().rev()
+    }
+
+    /**
+     * Returns an iterator over references to the data in the list's nodes. Unlike `iter`, this
+     * doesn't touch the nodes' reference counts.
+     *
+     * This is unsafe because the list doesn't keep the nodes alive while the references are in
+     * use: nodes can be removed from the list through any handle to them, or through the list
+     * itself, and then freed. No node may be removed from the list while the iterator, or any
+     * reference it returned, is alive.
+     */
+    pub unsafe fn iter_data<'a>(&'a self) -> DataIter<'a, T, L> {
+        DataIter {
+            current: self.head_raw(),
+            phantom: PhantomData
+        }
+    }
+}
+
+impl<T: ?Sized, L: Tag> Drop for IList<T, L> {
+    fn drop(&mut self) {
+        let sentinel = self.sentinel.get();
+        if sentinel.is_null() {
+            return;
+        }
+
+        // Each node is taken off the front with both of its links cleared, and only then is the
+        // list's reference to it released. Nothing is left pointing at a node that gets freed,
+        // and as the head is looked up afresh each time, it doesn't matter if dropping a node's
+        // data removes other nodes from the list.
+        while let Some(node) = self.pop_front() {
+            drop(node);
+        }
+
+        unsafe {
+            sentinel.as_ref().unwrap().set_owner(Raw::null());
+            free_sentinel(sentinel);
+        }
+    }
+}
+
+pub struct Iter<T: ?Sized, L: Tag = Primary> {
+    current: Option<INode<T>>,
+    back: Option<INode<T>>,
+    tag: PhantomData<L>
+}
+
+impl<T: ?Sized, L: Tag> Iterator for Iter<T, L> {
+    type Item = INode<T>;
+
+    fn next(&mut self) -> Option<INode<T>> {
+        let node = self.current.take();
+
+        if let Some(ref n) = node {
+            // Once the two ends meet, there's nothing left
+            if self.back.as_ref().map_or(false, |b| INode::ptr_eq(b, n)) {
+                self.back = None;
+            } else {
+                self.current = n.link::<L>().next();
+            }
+        }
+
+        node
+    }
+}
+
+impl<T: ?Sized, L: Tag> DoubleEndedIterator for Iter<T, L> {
+    fn next_back(&mut self) -> Option<INode<T>> {
+        let node = self.back.take();
+
+        if let Some(ref n) = node {
+            if self.current.as_ref().map_or(false, |c| INode::ptr_eq(c, n)) {
+                self.current = None;
+            } else {
+                self.back = n.link::<L>().prev();
+            }
+        }
+
+        node
+    }
+}
+
+impl<'a, T: ?Sized, L: Tag> IntoIterator for &'a IList<T, L> {
+    type Item = INode<T>;
+    type IntoIter = Iter<T, L>;
+
+    fn into_iter(self) -> Iter<T, L> {
+        self.iter()
+    }
+}
+
+impl<T: ?Sized, L: Tag> IntoIterator for IList<T, L> {
+    type Item = INode<T>;
+    type IntoIter = IntoIter<T, L>;
+
+    /**
+     * Consumes the list, returning an iterator that takes each node out of the list as it's
+     * returned.
+     */
+    fn into_iter(self) -> IntoIter<T, L> {
+        IntoIter { list: self }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, L: Tag> fmt::Debug for IList<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // `iter` stops before the sentinel, and holds each node while it's being formatted
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/**
+ * Cloning a list makes a deep copy: every node is cloned into a new node, so the copy doesn't
+ * share any nodes with the original, and changes made through handles to the original's nodes
+ * don't show up in it. Use `IList::from_nodes` to make a list out of the same nodes instead,
+ * which moves them out of this one.
+ */
+impl<T: Clone, L: Tag> Clone for IList<T, L> {
+    fn clone(&self) -> IList<T, L> {
+        let list = IList::new();
+        for node in self.iter() {
+            list.push_back(INode::new_sized(node.as_ref().clone()));
+        }
+        list
+    }
+}
+
+/**
+ * Lists are equal if their nodes' data is, pairwise and in order. Unlike with `INode`s, this
+ * compares by value.
+ */
+impl<T: ?Sized + PartialEq, L: Tag> PartialEq for IList<T, L> {
+    fn eq(&self, other: &IList<T, L>) -> bool {
+        let mut a = self.iter();
+        let mut b = other.iter();
+
+        loop {
+            match (a.next(), b.next()) {
+                (Some(x), Some(y)) => if x.as_ref() != y.as_ref() { return false; },
+                (None, None) => return true,
+                _ => return false
+            }
+        }
+    }
+}
+
+impl<T: ?Sized + Eq, L: Tag> Eq for IList<T, L> {}
+
+impl<T: ?Sized, L: Tag, U: Unsize<T>> iter::FromIterator<U> for IList<T, L> {
+    fn from_iter<I>(iter: I) -> IList<T, L> where I: IntoIterator<Item=U> {
+        let mut list = IList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: ?Sized, L: Tag, U: Unsize<T>> Extend<U> for IList<T, L> {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item=U> {
+        for val in iter {
+            self.push_back(INode::new(val));
+        }
+    }
+}
+
+pub struct IntoIter<T: ?Sized, L: Tag = Primary> {
+    list: IList<T, L>
+}
+
+impl<T: ?Sized, L: Tag> Iterator for IntoIter<T, L> {
+    type Item = INode<T>;
+
+    fn next(&mut self) -> Option<INode<T>> {
+        self.list.pop_front()
+    }
+}
+
+impl<T: ?Sized, L: Tag> DoubleEndedIterator for IntoIter<T, L> {
+    fn next_back(&mut self) -> Option<INode<T>> {
+        self.list.pop_back()
+    }
+}
+
+/**
+ * A cursor into an `IList`.
+ *
+ * Like the cursor for `XorList`, an `ICursor` sits between two nodes of the list, or before the
+ * head or after the tail. It holds handles to the nodes on either side, so they stay alive
+ * however the list is changed in the meantime.
+ *
+ * The cursor's position is given by the node after it. If that node is removed from the list by
+ * something other than the cursor, the cursor moves to just after the node before it instead, or
+ * to the start of the list if that one has been removed too.
+ */
+pub struct ICursor<'a, T: ?Sized + 'a, L: Tag + 'a = Primary> {
+    list: &'a IList<T, L>,
+    prev: Option<INode<T>>,
+    next: Option<INode<T>>
+}
+
+impl<'a, T: ?Sized, L: Tag> ICursor<'a, T, L> {
+    /**
+     * Moves the cursor forwards one position and returns the node that was skipped over, or
+     * `None` if the cursor is at the end of the list.
+     */
+    pub fn next(&mut self) -> Option<INode<T>> {
+        self.fix();
+
+        let node = match self.next.take() {
+            Some(node) => node,
+            None => return None
+        };
+
+        self.next = node.link::<L>().next();
+        self.prev = Some(node.clone());
+        Some(node)
+    }
+
+    /**
+     * Moves the cursor backwards one position and returns the node that was skipped over, or
+     * `None` if the cursor is at the start of the list.
+     */
+    pub fn prev(&mut self) -> Option<INode<T>> {
+        self.fix();
+
+        let node = match self.prev.take() {
+            Some(node) => node,
+            None => return None
+        };
+
+        self.prev = node.link::<L>().prev();
+        self.next = Some(node.clone());
+        Some(node)
+    }
+
+    /**
+     * Returns a reference to the data in the node after the cursor.
+     */
+    pub fn peek<'b>(&'b mut self) -> Option<&'b T> {
+        self.fix();
+        self.next.as_ref().map(|node| node.as_ref())
+    }
+
+    /**
+     * Move the cursor to the beginning of the list.
+     */
+    pub fn seek_to_start(&mut self) {
+        self.prev = None;
+        self.next = self.list.head();
+    }
+
+    /**
+     * Move the cursor to the end of the list.
+     */
+    pub fn seek_to_end(&mut self) {
+        self.prev = self.list.tail();
+        self.next = None;
+    }
+
+    /**
+     * Inserts the given node at the cursor position, leaving the cursor after it. The node is
+     * removed from any list it's already in.
+     */
+    pub fn insert_before(&mut self, node: INode<T>) {
+        self.fix();
+        self.insert(node.clone());
+        self.prev = Some(node);
+    }
+
+    /**
+     * Inserts the given node at the cursor position, leaving the cursor before it. The node is
+     * removed from any list it's already in.
+     */
+    pub fn insert_after(&mut self, node: INode<T>) {
+        self.fix();
+        self.insert(node.clone());
+        self.next = Some(node);
+    }
+
+    /**
+     * Removes the node after the cursor from the list and returns it, or returns `None` if the
+     * cursor is at the end of the list. Afterwards the cursor sits where the node was.
+     */
+    pub fn remove(&mut self) -> Option<INode<T>> {
+        self.fix();
+
+        let node = match self.next.take() {
+            Some(node) => node,
+            None => return None
+        };
+
+        self.next = node.link::<L>().next();
+        node.link::<L>().remove_from_list();
+        Some(node)
+    }
+
+    fn insert(&self, node: INode<T>) {
+        match self.next {
+            Some(ref next) => next.link::<L>().insert_before(node),
+            None => self.list.push_back(node)
+        }
+    }
+
+    /**
+     * Moves the cursor back into the list if the node it's anchored to has been removed by
+     * something else, and makes sure `prev` is the node right before `next`.
+     */
+    fn fix(&mut self) {
+        let next_ok = self.next.as_ref().map_or(true, |n| self.list.contains(n));
+        if !next_ok {
+            let prev_ok = self.prev.as_ref().map_or(true, |p| self.list.contains(p));
+            if !prev_ok {
+                self.seek_to_start();
+                return;
+            }
+
+            self.next = match self.prev {
+                Some(ref prev) => prev.link::<L>().next(),
+                None => self.list.head()
+            };
+        }
+
+        self.prev = match self.next {
+            Some(ref next) => next.link::<L>().prev(),
+            None => self.list.tail()
+        };
+    }
+}
+
+pub struct Drain<'a, T: ?Sized + 'a, L: Tag + 'a = Primary> {
+    list: &'a IList<T, L>
+}
+
+impl<'a, T: ?Sized, L: Tag> Iterator for Drain<'a, T, L> {
+    type Item = INode<T>;
+
+    fn next(&mut self) -> Option<INode<T>> {
+        self.list.pop_front()
+    }
+}
+
+impl<'a, T: ?Sized, L: Tag> DoubleEndedIterator for Drain<'a, T, L> {
+    fn next_back(&mut self) -> Option<INode<T>> {
+        self.list.pop_back()
+    }
+}
+
+impl<'a, T: ?Sized, L: Tag> Drop for Drain<'a, T, L> {
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+pub struct DataIter<'a, T: ?Sized + 'a, L: Tag + 'a = Primary> {
+    current: Raw<NodeLinks<T>>,
+    phantom: PhantomData<&'a IList<T, L>>
+}
+
+impl<'a, T: ?Sized, L: Tag> Iterator for DataIter<'a, T, L> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let current = self.current;
+        let node = match current.as_ref() {
+            Some(node) if !node.is_sentinel() => node,
+            _ => return None
+        };
+
+        self.current = node.next.get();
+        unsafe {
+            Some(mem::transmute(node.data()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::prelude::v1::*;
+    use std::fmt::Display;
+    use raw::Raw;
+    use super::*;
+
+    fn strings<L: Tag>(list: &IList<Display, L>) -> Vec<String> {
+        list.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn smoketest() {
+        let list : IList<Display> = IList::new();
+
+        let node1 = INode::new(1);
+        list.push_back(node1);
+
+        let node2 : INode<Display> = INode::new(2);
+        list.push_back(node2.clone());
+
+        let node3 = INode::new(3);
+
+        node2.insert_after(node3);
+
+        let node4 = INode::new("I'm a string");
+        node2.insert_before(node4);
+
+        let mut node = list.head().unwrap();
+        assert_eq!(node.as_ref().to_string(), "1");
+
+        node = node.next().unwrap();
+        assert_eq!(node.as_ref().to_string(), "I'm a string");
+
+        node = node.next().unwrap();
+        assert_eq!(node.as_ref().to_string(), "2");
+
+        node = node.next().unwrap();
+        assert_eq!(node.as_ref().to_string(), "3");
+
+        assert!(node.next().is_none());
+    }
+
+    #[test]
+    fn move_lists() {
+        let list1 : IList<Display> = IList::new();
+
+        let node1 = INode::new(1);
+        list1.push_back(node1);
+
+        let node2 : INode<Display> = INode::new(2);
+        list1.push_back(node2.clone());
+
+        let list2 : IList<Display> = IList::new();
+
+        let node3 = INode::new(3);
+        list2.push_back(node3);
+        list2.push_back(node2);
+
+        let node = list1.head().unwrap();
+        assert_eq!(node.as_ref().to_string(), "1");
 
         let mut node = list2.head().unwrap();
         assert_eq!(node.as_ref().to_string(), "3");
 
-        node = node.next().unwrap();
-        assert_eq!(node.as_ref().to_string(), "2");
+        node = node.next().unwrap();
+        assert_eq!(node.as_ref().to_string(), "2");
+
+    }
+
+    #[test]
+    fn pop() {
+        let list : IList<Display> = IList::new();
+        assert!(list.pop_front().is_none());
+        assert!(list.pop_back().is_none());
+
+        let nodes : Vec<INode<Display>> = (0..4).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list.push_back(node.clone());
+            assert_eq!(node.count(), 2);
+        }
+
+        let node = list.pop_front().unwrap();
+        assert_eq!(node.as_ref().to_string(), "0");
+        assert_eq!(node.count(), 2);
+        assert!(!node.in_list());
+        drop(node);
+        assert_eq!(nodes[0].count(), 1);
+
+        let node = list.pop_back().unwrap();
+        assert_eq!(node.as_ref().to_string(), "3");
+        assert_eq!(node.count(), 2);
+        assert!(!node.in_list());
+        drop(node);
+
+        assert_eq!(list.head().unwrap().as_ref().to_string(), "1");
+        assert_eq!(list.tail().unwrap().as_ref().to_string(), "2");
+        assert!(list.head().unwrap().prev().is_none());
+        assert!(list.tail().unwrap().next().is_none());
+
+        // Down to a single node, where the head is the tail
+        assert_eq!(list.pop_back().unwrap().as_ref().to_string(), "2");
+        assert_eq!(list.pop_front().unwrap().as_ref().to_string(), "1");
+        assert!(list.is_empty());
+        assert!(list.pop_front().is_none());
+        assert!(list.pop_back().is_none());
+
+        for node in &nodes {
+            assert_eq!(node.count(), 1);
+        }
+
+        // The list still works afterwards
+        list.push_back(nodes[2].clone());
+        list.push_front(nodes[1].clone());
+        list.push_back(nodes[3].clone());
+        let vals : Vec<String> = list.iter().map(|n| n.as_ref().to_string()).collect();
+        assert_eq!(vals, ["1", "2", "3"]);
+        assert_eq!(list.pop_front().unwrap().as_ref().to_string(), "1");
+        assert_eq!(list.pop_front().unwrap().as_ref().to_string(), "2");
+        assert_eq!(list.pop_front().unwrap().as_ref().to_string(), "3");
+        assert!(list.pop_front().is_none());
+    }
+
+    #[test]
+    fn iter_data() {
+        let list : IList<Display> = IList::new();
+        unsafe {
+            assert!(list.iter_data().next().is_none());
+        }
+
+        let nodes : Vec<INode<Display>> = vec![INode::new(1), INode::new("two"), INode::new(3.5)];
+        for node in &nodes {
+            list.push_back(node.clone());
+        }
+
+        let counts : Vec<usize> = nodes.iter().map(|n| n.count()).collect();
+        let data : Vec<String> = unsafe {
+            list.iter_data().map(|x| x.to_string()).collect()
+        };
+        let after : Vec<usize> = nodes.iter().map(|n| n.count()).collect();
+        assert_eq!(counts, after);
+
+        let from_iter : Vec<String> = list.iter().map(|n| n.as_ref().to_string()).collect();
+        assert_eq!(data, from_iter);
+        assert_eq!(data, ["1", "two", "3.5"]);
+
+        // The iterator stops at the sentinel, and keeps returning None
+        unsafe {
+            let mut iter = list.iter_data();
+            assert_eq!(iter.by_ref().count(), 3);
+            assert!(iter.next().is_none());
+        }
+    }
+
+    #[test]
+    fn iter_rev() {
+        fn strings<I: Iterator<Item=INode<Display>>>(iter: I) -> Vec<String> {
+            iter.map(|n| n.as_ref().to_string()).collect()
+        }
+
+        for len in 0..5 {
+            let list : IList<Display> = IList::new();
+            for i in 0..len {
+                list.push_back(INode::new(i));
+            }
+
+            let forwards = strings(list.iter());
+            let mut backwards = strings(list.iter().rev());
+            assert_eq!(strings(list.iter_rev()), backwards);
+            backwards.reverse();
+            assert_eq!(forwards, backwards);
+            assert_eq!(forwards.len(), len);
+
+            // Taking from both ends meets in the middle without repeating a node
+            let mut iter = list.iter();
+            let mut front = vec![];
+            let mut back = vec![];
+            loop {
+                match iter.next() {
+                    Some(n) => front.push(n.as_ref().to_string()),
+                    None => break
+                }
+                match iter.next_back() {
+                    Some(n) => back.push(n.as_ref().to_string()),
+                    None => break
+                }
+            }
+            assert!(iter.next().is_none());
+            assert!(iter.next_back().is_none());
+            back.reverse();
+            front.extend(back);
+            assert_eq!(front, forwards);
+        }
+    }
+
+    #[test]
+    fn into_iter() {
+        let list : IList<Display> = IList::new();
+        for i in 0..3 {
+            list.push_back(INode::new(i));
+        }
+
+        let mut vals = vec![];
+        for node in &list {
+            vals.push(node.as_ref().to_string());
+        }
+        assert_eq!(vals, ["0", "1", "2"]);
+
+        let nodes : Vec<INode<Display>> = list.into_iter().collect();
+        assert_eq!(nodes.len(), 3);
+        for (i, node) in nodes.iter().enumerate() {
+            assert_eq!(node.as_ref().to_string(), i.to_string());
+            assert!(!node.in_list());
+            assert_eq!(node.count(), 1);
+        }
+
+        // The nodes can go into another list
+        let other : IList<Display> = IList::new();
+        for node in nodes.into_iter().rev() {
+            other.push_back(node);
+        }
+        let vals : Vec<String> = other.into_iter().map(|n| n.as_ref().to_string()).collect();
+        assert_eq!(vals, ["2", "1", "0"]);
+    }
+
+    #[test]
+    fn into_iter_drop() {
+        use std::any::Any;
+        use std::rc::Rc;
+        use std::cell::Cell;
+
+        struct DropCounter(Rc<Cell<usize>>);
+
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let count = Rc::new(Cell::new(0));
+        let list : IList<Any> = IList::new();
+        for _ in 0..5 {
+            list.push_back(INode::new(DropCounter(count.clone())));
+        }
+
+        let mut iter = list.into_iter();
+        let first = iter.next().unwrap();
+        drop(iter.next_back());
+        assert_eq!(count.get(), 1);
+
+        drop(iter);
+        assert_eq!(count.get(), 4);
+        drop(first);
+        assert_eq!(count.get(), 5);
+    }
+
+    #[test]
+    fn deref() {
+        use std::borrow::Borrow;
+
+        fn show<T: Display + ?Sized>(x: &T) -> String {
+            x.to_string()
+        }
+
+        fn show_ref<T: AsRef<Display>>(x: T) -> String {
+            x.as_ref().to_string()
+        }
+
+        let list : IList<Display> = IList::new();
+        list.push_back(INode::new(1));
+        list.push_back(INode::new("two"));
+
+        let node = list.head().unwrap();
+        assert_eq!(node.to_string(), "1");
+        assert_eq!(show(&*node), "1");
+        let display : &Display = &*node;
+        assert_eq!(format!("{}", display), "1");
+
+        let node = node.next().unwrap();
+        assert_eq!(show_ref(node.clone()), "two");
+        let borrowed : &Display = node.borrow();
+        assert_eq!(borrowed.to_string(), "two");
+        assert_eq!(node.as_ref().to_string(), "two");
+
+        let vals : Vec<String> = list.iter().map(|n| n.to_string()).collect();
+        assert_eq!(vals, ["1", "two"]);
+
+        let nums : IList<[i32]> = IList::new();
+        nums.push_back(INode::new([1, 2, 3]));
+        let head = nums.head().unwrap();
+        assert_eq!(head.len(), 3);
+        assert_eq!(head.iter().fold(0, |a, b| a + b), 6);
+    }
+
+    #[test]
+    fn pop_value() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        #[derive(Debug)]
+        struct DropCounter(usize, Rc<Cell<usize>>);
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let list : IList<DropCounter> = IList::new();
+        assert!(list.pop_front_value().is_none());
+        assert!(list.pop_back_value().is_none());
+
+        for i in 0..4 {
+            list.push_back(INode::new_sized(DropCounter(i, drops.clone())));
+        }
+
+        // The list holds the only reference, so the data is moved out and the node freed
+        let val = list.pop_front_value().unwrap().unwrap();
+        assert_eq!(val.0, 0);
+        assert_eq!(drops.get(), 0);
+        drop(val);
+        assert_eq!(drops.get(), 1);
+
+        let val = list.pop_back_value().unwrap().unwrap();
+        assert_eq!(val.0, 3);
+        drop(val);
+        assert_eq!(drops.get(), 2);
+
+        // With another handle, the node is removed but handed back
+        let head = list.head().unwrap();
+        let node = list.pop_front_value().unwrap().unwrap_err();
+        assert!(INode::ptr_eq(&node, &head));
+        assert!(!node.in_list());
+        assert_eq!(node.strong_count(), 2);
+        assert_eq!(drops.get(), 2);
+
+        // Once it's the only handle, it can be unwrapped
+        let node = node.try_unwrap().unwrap_err();
+        drop(head);
+        let val = node.try_unwrap().unwrap();
+        assert_eq!(val.0, 1);
+        assert_eq!(drops.get(), 2);
+        drop(val);
+        assert_eq!(drops.get(), 3);
+
+        // Weak references and lists count as sharing too
+        let node = list.tail().unwrap();
+        let node = node.try_unwrap().unwrap_err();
+        node.remove_from_list();
+        let weak = node.downgrade();
+        let node = node.try_unwrap().unwrap_err();
+        drop(weak);
+        assert_eq!(node.try_unwrap().unwrap().0, 2);
+        assert_eq!(drops.get(), 4);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn get_mut() {
+        let list : IList<[i32]> = IList::new();
+        list.push_back(INode::new([0]));
+
+        let mut node : INode<[i32]> = INode::new([1, 2, 3]);
+        node.get_mut().unwrap()[0] = 10;
+        assert_eq!(&*node, [10, 2, 3]);
+
+        let other = node.clone();
+        assert!(node.get_mut().is_none());
+        drop(other);
+
+        list.push_back(node.clone());
+        assert!(node.get_mut().is_none());
+
+        node.remove_from_list();
+        assert!(!node.in_list());
+        assert_eq!(node.count(), 1);
+        node.get_mut().unwrap()[2] = 30;
+        assert_eq!(&*node, [10, 2, 30]);
+
+        assert_eq!(list.head().unwrap()[0], 0);
+    }
+
+    #[test]
+    fn strong_count() {
+        let list : IList<Display> = IList::new();
+
+        let node : INode<Display> = INode::new(1);
+        assert_eq!(node.strong_count(), 1);
+        assert!(!node.in_list());
+        assert!(node.is_sole_owner());
+
+        let clone = node.clone();
+        assert_eq!(node.strong_count(), 2);
+        assert_eq!(clone.strong_count(), 2);
+        assert!(!node.is_sole_owner());
+
+        list.push_back(clone);
+        assert_eq!(node.strong_count(), 2);
+        assert!(node.in_list());
+        assert!(!node.is_sole_owner());
+
+        let head = list.head().unwrap();
+        assert_eq!(node.strong_count(), 3);
+        drop(head);
+        assert_eq!(node.strong_count(), 2);
+
+        let other : INode<Display> = INode::new(2);
+        list.push_back(other.clone());
+        assert_eq!(other.strong_count(), 2);
+
+        node.remove_from_list();
+        assert!(!node.in_list());
+        assert_eq!(node.strong_count(), 1);
+        assert!(node.is_sole_owner());
+
+        // A node with only a prev link is still reported as being in a list
+        other.links::<Primary>().next.set(Raw::null());
+        assert!(other.in_list());
+        other.links::<Primary>().next.set(list.sentinel().to_raw());
+
+        drop(list);
+        assert!(!other.in_list());
+        assert_eq!(other.strong_count(), 1);
+    }
+
+    #[test]
+    fn weak() {
+        use std::cell::{Cell, RefCell};
+        use std::any::Any;
+        use std::rc::Rc;
+
+        struct DropCounter(Rc<Cell<usize>>);
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let list : IList<Display> = IList::new();
+        list.push_back(INode::new(0));
+        let node : INode<Display> = INode::new(1);
+        let weak = node.downgrade();
+        assert_eq!(node.weak_count(), 1);
+        assert!(!node.is_sole_owner());
+
+        list.push_back(node);
+        let upgraded = weak.upgrade().unwrap();
+        assert_eq!(upgraded.to_string(), "1");
+        assert_eq!(upgraded.strong_count(), 2);
+
+        // Still alive after removal while there's a strong reference
+        upgraded.remove_from_list();
+        assert!(list.head().unwrap().next().is_none());
+        drop(upgraded);
+        assert!(weak.upgrade().is_none());
+        assert!(weak.clone().upgrade().is_none());
+
+        // The list alone keeps a node alive
+        let weak = {
+            let node : INode<Display> = INode::new(2);
+            let weak = node.downgrade();
+            list.push_back(node);
+            weak
+        };
+        assert_eq!(weak.upgrade().unwrap().to_string(), "2");
+        drop(list);
+        assert!(weak.upgrade().is_none());
+
+        // The data is dropped with the last strong reference, not the last weak one
+        let count = Rc::new(Cell::new(0));
+        let node : INode<Any> = INode::new(DropCounter(count.clone()));
+        let weak1 = node.downgrade();
+        let weak2 = weak1.clone();
+        assert_eq!(node.weak_count(), 2);
+        drop(node);
+        assert_eq!(count.get(), 1);
+        assert!(weak1.upgrade().is_none());
+        drop(weak1);
+        drop(weak2);
+        assert_eq!(count.get(), 1);
+
+        // A node whose data holds the only weak reference to it
+        struct Cyclic {
+            me: RefCell<Option<IWeak<Any>>>,
+            _counter: DropCounter
+        }
+
+        let node : INode<Any> = INode::new(Cyclic {
+            me: RefCell::new(None),
+            _counter: DropCounter(count.clone())
+        });
+        *node.downcast_ref::<Cyclic>().unwrap().me.borrow_mut() = Some(node.downgrade());
+        assert_eq!(node.weak_count(), 1);
+        drop(node);
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn identity() {
+        use std::collections::HashSet;
+        use std::hash::{Hash, Hasher, SipHasher};
+
+        fn hash<T: Hash>(x: &T) -> u64 {
+            let mut state = SipHasher::new();
+            x.hash(&mut state);
+            state.finish()
+        }
+
+        let a : INode<Display> = INode::new(1);
+        let b = a.clone();
+        let c : INode<Display> = INode::new(1);
+
+        assert!(INode::ptr_eq(&a, &b));
+        assert!(!INode::ptr_eq(&a, &c));
+        assert!(a == b);
+        assert!(a != c);
+        assert_eq!(hash(&a), hash(&b));
+
+        let list : IList<Display> = IList::new();
+        list.push_back(a.clone());
+        list.push_back(c.clone());
+        assert!(list.head().unwrap() == a);
+        assert!(list.tail().unwrap() == c);
+
+        let mut set = HashSet::new();
+        assert!(set.insert(a));
+        assert!(!set.insert(b));
+        assert!(set.insert(c.clone()));
+        assert!(!set.insert(list.tail().unwrap()));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&c));
+    }
+
+    #[test]
+    fn contains_remove() {
+        let list1 : IList<Display> = IList::new();
+        let list2 : IList<Display> = IList::new();
+
+        let nodes : Vec<INode<Display>> = (0..3).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list1.push_back(node.clone());
+        }
+        let other : INode<Display> = INode::new(10);
+        list2.push_back(other.clone());
+        let loose : INode<Display> = INode::new(20);
+
+        for node in &nodes {
+            assert!(list1.contains(node));
+            assert!(!list2.contains(node));
+        }
+        assert!(list2.contains(&other));
+        assert!(!list1.contains(&other));
+        assert!(!list1.contains(&loose));
+
+        // Nodes in another list, or no list, are left alone
+        assert!(!list1.remove(&other));
+        assert!(list2.contains(&other));
+        assert_eq!(other.strong_count(), 2);
+        assert!(!list1.remove(&loose));
+        assert!(!loose.in_list());
+
+        assert!(list1.remove(&nodes[1]));
+        assert!(!nodes[1].in_list());
+        assert!(!list1.contains(&nodes[1]));
+        assert_eq!(nodes[1].strong_count(), 1);
+        assert!(!list1.remove(&nodes[1]));
+        assert!(list1.head().unwrap().next().unwrap() == nodes[2]);
+
+        // Removing the only node empties the list
+        assert!(list2.remove(&other));
+        assert!(list2.is_empty());
+        assert!(list2.head().is_none());
+        list2.push_back(other.clone());
+        assert!(list2.contains(&other));
+
+        assert!(list1.remove(&nodes[0]));
+        assert!(list1.remove(&nodes[2]));
+        assert!(list1.is_empty());
+        assert!(!list1.contains(&nodes[0]));
+    }
+
+    #[test]
+    fn owner() {
+        let list1 : IList<Display> = IList::new();
+        let list2 : IList<Display> = IList::new();
+        assert!(list1.id() != list2.id());
+
+        let a : INode<Display> = INode::new(1);
+        let b : INode<Display> = INode::new(2);
+        let c : INode<Display> = INode::new(3);
+        assert_eq!(a.owner_id(), None);
+
+        list1.push_back(a.clone());
+        a.insert_after(b.clone());
+        a.insert_before(c.clone());
+        for node in &[&a, &b, &c] {
+            assert_eq!(node.owner_id(), Some(list1.id()));
+        }
+
+        // Moving a node to another list changes its owner
+        list2.push_front(b.clone());
+        assert_eq!(b.owner_id(), Some(list2.id()));
+        assert!(list2.contains(&b));
+        assert!(!list1.contains(&b));
+
+        let popped = list1.pop_front().unwrap();
+        assert!(popped == c);
+        assert_eq!(c.owner_id(), None);
+
+        a.remove_from_list();
+        assert_eq!(a.owner_id(), None);
+        assert!(list1.is_empty());
+
+        list1.push_back(a.clone());
+        list1.push_back(c.clone());
+        drop(list1);
+        assert_eq!(a.owner_id(), None);
+        assert_eq!(c.owner_id(), None);
+        assert!(!a.in_list());
+
+        // A list created after the drop doesn't claim the old nodes
+        let list3 : IList<Display> = IList::new();
+        assert!(!list3.contains(&a));
+        assert_eq!(b.owner_id(), Some(list2.id()));
+    }
+
+    #[test]
+    fn id_stamps() {
+        let loose : INode<Display> = INode::new(0);
+        let node : INode<Display> = INode::new(1);
+
+        // Ids aren't reused, even once the list that had one is gone
+        let mut ids = vec![];
+        for _ in 0..3 {
+            let list : IList<Display> = IList::new();
+            list.push_back(node.clone());
+            assert!(list.contains(&node));
+            assert!(!list.contains(&loose));
+            ids.push(list.id());
+        }
+        assert!(ids[0] != 0 && ids[0] < ids[1] && ids[1] < ids[2]);
+
+        // The node was left behind by each list as it was dropped
+        assert_eq!(node.owner_id(), None);
+        let fresh : IList<Display> = IList::new();
+        assert!(!fresh.contains(&node));
+        assert!(!fresh.contains(&loose));
+        assert!(!fresh.remove(&node));
+
+        // Moving a node between lists changes its owner
+        let a : IList<Display> = IList::new();
+        let b : IList<Display, Secondary> = IList::new();
+        let c : IList<Display> = IList::new();
+        a.push_back(node.clone());
+        b.push_back(node.clone());
+        assert!(a.contains(&node) && b.contains(&node));
+        assert!(a.id() != b.id());
+        c.push_back(loose.clone());
+        c.splice_before(&loose, &a);
+        assert!(!a.contains(&node));
+        assert!(c.contains(&node));
+        assert!(b.contains(&node));
+        assert_eq!(node.owner_id(), Some(c.id()));
+        assert_eq!(node.link::<Secondary>().owner_id(), Some(b.id()));
+
+        // A list can't be used to remove nodes it doesn't have
+        assert!(!a.remove(&node));
+        assert!(c.contains(&node));
+    }
+
+    #[test]
+    #[should_panic]
+    fn move_to_front_other_list() {
+        let a : IList<i32> = IList::new();
+        let other : IList<i32> = IList::new();
+        let node = INode::new_sized(1);
+        other.push_back(node.clone());
+        a.move_to_front(&node);
+    }
+
+    #[test]
+    fn append_forwards_owner() {
+        let nodes : Vec<INode<usize>> = (0..6).map(INode::new_sized).collect();
+        let a : IList<usize> = IList::from_nodes(nodes[..3].iter().cloned());
+        let b : IList<usize> = IList::from_nodes(nodes[3..].iter().cloned());
+        let c : IList<usize> = IList::new();
+
+        a.append(&b);
+        c.append(&a);
+
+        // The moved nodes weren't touched, and reach `c`'s owner through the ones they had
+        let current = c.sentinel().owner();
+        for node in &nodes {
+            assert!(node.links::<Primary>().owner.get() != current);
+        }
+
+        // Looking up their owner points them straight at it
+        for node in &nodes {
+            assert!(c.contains(node));
+            assert!(!a.contains(node) && !b.contains(node));
+            assert!(node.links::<Primary>().owner.get() == current);
+        }
+
+        // The emptied lists have owners of their own again
+        a.push_back(nodes[0].clone());
+        b.push_back(nodes[3].clone());
+        assert_eq!(nodes[0].owner_id(), Some(a.id()));
+        assert_eq!(nodes[3].owner_id(), Some(b.id()));
+        assert!(c.contains(&nodes[1]) && !c.contains(&nodes[0]));
+        c.check_invariants();
+
+        // Splicing a list into the middle forwards its owner the same way
+        let d : IList<usize> = IList::from_nodes(nodes[3..5].iter().cloned());
+        c.splice_after(&nodes[1], &d);
+        let current = c.sentinel().owner();
+        assert!(nodes[4].links::<Primary>().owner.get() != current);
+        assert!(c.contains(&nodes[4]) && !d.contains(&nodes[4]));
+        assert!(nodes[4].links::<Primary>().owner.get() == current);
+        assert_eq!(c.iter().map(|n| *n).collect::<Vec<_>>(), [1, 3, 4, 2, 5]);
+    }
+
+    #[test]
+    fn append() {
+        let list1 : IList<Display> = IList::new();
+        let list2 : IList<Display> = IList::new();
+
+        // Both empty
+        list1.append(&list2);
+        assert!(list1.is_empty() && list2.is_empty());
+
+        // Into an empty list
+        let a : INode<Display> = INode::new(1);
+        list2.push_back(a.clone());
+        list1.append(&list2);
+        assert!(list2.is_empty());
+        assert_eq!(strings(&list1), ["1"]);
+        assert_eq!(a.owner_id(), Some(list1.id()));
+        assert_eq!(a.strong_count(), 2);
+
+        // From an empty list
+        list1.append(&list2);
+        assert_eq!(strings(&list1), ["1"]);
+
+        // Both single-element
+        let b : INode<Display> = INode::new(2);
+        list2.push_back(b.clone());
+        list1.append(&list2);
+        assert!(list2.is_empty());
+        assert!(list2.head().is_none());
+        assert_eq!(strings(&list1), ["1", "2"]);
+        assert!(a.next().unwrap() == b);
+        assert!(b.prev().unwrap() == a);
+        assert!(b.next().is_none());
+
+        // Several nodes
+        let nodes : Vec<INode<Display>> = (3..6).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list2.push_back(node.clone());
+        }
+        list1.append(&list2);
+        assert_eq!(strings(&list1), ["1", "2", "3", "4", "5"]);
+        let rev : Vec<String> = list1.iter_rev().map(|n| n.to_string()).collect();
+        assert_eq!(rev, ["5", "4", "3", "2", "1"]);
+        assert!(nodes[0].prev().unwrap() == b);
+        assert!(list1.tail().unwrap() == nodes[2]);
+        for node in &nodes {
+            assert!(list1.contains(node));
+            assert_eq!(node.strong_count(), 2);
+        }
+
+        // The source is still usable
+        list2.push_back(INode::new(6));
+        assert_eq!(strings(&list2), ["6"]);
+
+        list1.append(&list1);
+        assert_eq!(strings(&list1), ["1", "2", "3", "4", "5"]);
+
+        drop(list1);
+        assert_eq!(a.strong_count(), 1);
+        assert!(!nodes[1].in_list());
+    }
+
+    #[test]
+    fn splice() {
+        fn check<F>(at: usize, splice: F, expected: &[&str])
+                where F: Fn(&IList<Display>, &INode<Display>, &IList<Display>) {
+            let list : IList<Display> = IList::new();
+            let nodes : Vec<INode<Display>> = (0..3).map(|i| INode::new(i)).collect();
+            for node in &nodes {
+                list.push_back(node.clone());
+            }
+
+            let other : IList<Display> = IList::new();
+            let moved : Vec<INode<Display>> = vec![INode::new("a"), INode::new("b")];
+            for node in &moved {
+                other.push_back(node.clone());
+            }
+
+            splice(&list, &nodes[at], &other);
+            assert_eq!(strings(&list), expected);
+            assert!(other.is_empty());
+            assert!(other.head().is_none() && other.tail().is_none());
+
+            let backwards : Vec<String> = list.iter_rev().map(|n| n.to_string()).collect();
+            let mut reversed : Vec<String> = expected.iter().map(|s| s.to_string()).collect();
+            reversed.reverse();
+            assert_eq!(backwards, reversed);
+
+            for node in &moved {
+                assert!(list.contains(node));
+                assert_eq!(node.strong_count(), 2);
+            }
+
+            // The other list can still be used
+            other.push_back(INode::new("c"));
+            assert_eq!(strings(&other), ["c"]);
+        }
+
+        check(0, |l, at, o| l.splice_after(at, o), &["0", "a", "b", "1", "2"]);
+        check(1, |l, at, o| l.splice_after(at, o), &["0", "1", "a", "b", "2"]);
+        check(2, |l, at, o| l.splice_after(at, o), &["0", "1", "2", "a", "b"]);
+        check(0, |l, at, o| l.splice_before(at, o), &["a", "b", "0", "1", "2"]);
+        check(1, |l, at, o| l.splice_before(at, o), &["0", "a", "b", "1", "2"]);
+        check(2, |l, at, o| l.splice_before(at, o), &["0", "1", "a", "b", "2"]);
+
+        // Splicing in an empty list does nothing
+        let list : IList<Display> = IList::new();
+        let node : INode<Display> = INode::new(0);
+        list.push_back(node.clone());
+        list.splice_after(&node, &IList::new());
+        assert_eq!(strings(&list), ["0"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn splice_not_in_list() {
+        let list : IList<Display> = IList::new();
+        let other : IList<Display> = IList::new();
+        other.push_back(INode::new(1));
+        list.splice_after(&INode::new(0), &other);
+    }
+
+    #[test]
+    fn split_at() {
+        fn check(split: usize, front: &[&str], back: &[&str]) {
+            let list : IList<Display> = IList::new();
+            let nodes : Vec<INode<Display>> = (0..4).map(|i| INode::new(i)).collect();
+            for node in &nodes {
+                list.push_back(node.clone());
+            }
+
+            let rest = list.split_at(&nodes[split]);
+            assert_eq!(strings(&list), front);
+            assert_eq!(strings(&rest), back);
+
+            let rev : Vec<String> = rest.iter_rev().map(|n| n.to_string()).collect();
+            let mut expected : Vec<&str> = back.to_vec();
+            expected.reverse();
+            assert_eq!(rev, expected);
+
+            assert!(nodes[split].prev().is_none());
+            assert!(rest.contains(&nodes[split]));
+            assert!(!list.contains(&nodes[split]));
+            if split > 0 {
+                assert!(nodes[split - 1].next().is_none());
+                assert!(list.tail().unwrap() == nodes[split - 1]);
+            } else {
+                assert!(list.is_empty());
+            }
+
+            // Both lists are still usable
+            list.push_back(INode::new("a"));
+            rest.push_back(INode::new("b"));
+            assert_eq!(strings(&list).last().unwrap(), "a");
+            assert_eq!(strings(&rest).last().unwrap(), "b");
+
+            drop(list);
+            drop(rest);
+            for node in &nodes {
+                assert_eq!(node.strong_count(), 1);
+                assert!(!node.in_list());
+            }
+        }
+
+        check(0, &[], &["0", "1", "2", "3"]);
+        check(3, &["0", "1", "2"], &["3"]);
+        check(2, &["0", "1"], &["2", "3"]);
+    }
+
+    #[test]
+    fn move_to_ends() {
+        let cache : IList<Display> = IList::new();
+        let nodes : Vec<INode<Display>> = (0..5).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            cache.push_front(node.clone());
+        }
+        assert_eq!(strings(&cache), ["4", "3", "2", "1", "0"]);
+
+        // Accessing an entry moves it to the front, so the tail is the next to be evicted
+        for &i in &[0, 2, 0, 4, 1] {
+            cache.move_to_front(&nodes[i]);
+            assert!(cache.head().unwrap() == nodes[i]);
+        }
+        assert_eq!(strings(&cache), ["1", "4", "0", "2", "3"]);
+        assert!(cache.tail().unwrap() == nodes[3]);
+
+        let evicted = cache.pop_back().unwrap();
+        assert!(evicted == nodes[3]);
+        assert!(cache.tail().unwrap() == nodes[2]);
+
+        cache.move_to_front(&nodes[1]);
+        assert_eq!(strings(&cache), ["1", "4", "0", "2"]);
+
+        cache.move_to_back(&nodes[4]);
+        cache.move_to_back(&nodes[4]);
+        cache.move_to_back(&nodes[1]);
+        assert_eq!(strings(&cache), ["0", "2", "4", "1"]);
+        let rev : Vec<String> = cache.iter_rev().map(|n| n.to_string()).collect();
+        assert_eq!(rev, ["1", "4", "2", "0"]);
+        assert!(nodes[1].prev().unwrap() == nodes[4]);
+        assert!(nodes[0].prev().is_none());
+
+        // Nothing was moved by cloning handles
+        for node in &nodes {
+            assert_eq!(node.strong_count(), 2);
+        }
+        drop(evicted);
+        assert_eq!(nodes[3].strong_count(), 1);
+
+        let single : IList<Display> = IList::new();
+        single.push_back(nodes[3].clone());
+        single.move_to_front(&nodes[3]);
+        single.move_to_back(&nodes[3]);
+        assert_eq!(strings(&single), ["3"]);
+    }
+
+    #[test]
+    fn ordering() {
+        let list : IList<Display> = IList::new();
+        let nodes : Vec<INode<Display>> = (0..4).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list.push_back(node.clone());
+        }
+
+        assert_eq!(nodes[0].is_before(&nodes[3]), Some(true));
+        assert_eq!(nodes[1].is_before(&nodes[2]), Some(true));
+        assert_eq!(nodes[3].is_before(&nodes[0]), Some(false));
+        assert_eq!(nodes[2].is_before(&nodes[2]), Some(false));
+
+        assert_eq!(nodes[0].distance_to(&nodes[3]), Some(3));
+        assert_eq!(nodes[3].distance_to(&nodes[1]), Some(2));
+        assert_eq!(nodes[2].distance_to(&nodes[2]), Some(0));
+
+        let other : IList<Display> = IList::new();
+        let foreign : INode<Display> = INode::new(10);
+        other.push_back(foreign.clone());
+        let loose : INode<Display> = INode::new(20);
+        let loose2 : INode<Display> = INode::new(30);
+
+        assert_eq!(nodes[0].is_before(&foreign), None);
+        assert_eq!(foreign.distance_to(&nodes[0]), None);
+        assert_eq!(nodes[0].is_before(&loose), None);
+        assert_eq!(loose.is_before(&loose2), None);
+        assert_eq!(loose.distance_to(&loose), None);
+
+        list.move_to_front(&nodes[3]);
+        assert_eq!(nodes[3].is_before(&nodes[0]), Some(true));
+        assert_eq!(nodes[2].distance_to(&nodes[3]), Some(3));
+    }
+
+    #[test]
+    fn retain() {
+        use std::any::Any;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct Counted(usize, Rc<Cell<usize>>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        fn value(x: &Any) -> usize {
+            x.downcast_ref::<Counted>().unwrap().0
+        }
+
+        fn values(list: &IList<Any>) -> Vec<usize> {
+            list.iter().map(|n| value(&*n)).collect()
+        }
+
+        let count = Rc::new(Cell::new(0));
+        let list : IList<Any> = IList::new();
+        for i in 0..6 {
+            list.push_back(INode::new(Counted(i, count.clone())));
+        }
+        let kept : INode<Any> = list.tail().unwrap();
+
+        list.retain(|x| value(x) % 2 == 0);
+        assert_eq!(values(&list), [0, 2, 4]);
+        assert_eq!(count.get(), 2);
+        assert!(!kept.in_list());
+
+        // A removed node with an external handle survives
+        let mut survivors = Vec::new();
+        list.retain_nodes(|node| {
+            if value(&**node) == 2 {
+                survivors.push(node.clone());
+                false
+            } else {
+                true
+            }
+        });
+        assert_eq!(values(&list), [0, 4]);
+        assert_eq!(count.get(), 2);
+        assert_eq!(survivors.len(), 1);
+        assert!(!survivors[0].in_list());
+        drop(survivors);
+        assert_eq!(count.get(), 3);
+
+        // The predicate can remove the node itself
+        list.retain_nodes(|node| {
+            node.remove_from_list();
+            true
+        });
+        assert!(list.is_empty());
+        assert_eq!(count.get(), 5);
+
+        assert_eq!(value(&*kept), 5);
+        drop(kept);
+        assert_eq!(count.get(), 6);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn node_cache() {
+        use std::any::Any;
+        use std::cell::Cell;
+        use std::rc::Rc;
+        use node_cache::{heap_calls, cached};
+
+        struct Particle {
+            dropped: Rc<Cell<usize>>,
+            _pos: [f64; 3]
+        }
+        impl Drop for Particle {
+            fn drop(&mut self) {
+                self.dropped.set(self.dropped.get() + 1);
+            }
+        }
+
+        fn particle(made: &Cell<usize>, dropped: &Rc<Cell<usize>>) -> INode<Any> {
+            made.set(made.get() + 1);
+            INode::new(Particle { dropped: dropped.clone(), _pos: [0.0; 3] })
+        }
+
+        fn frame(list: &IList<Any>, n: usize, made: &Cell<usize>, dropped: &Rc<Cell<usize>>) {
+            for _ in 0..n {
+                list.push_back(particle(made, dropped));
+            }
+            while let Some(_) = list.pop_front() {}
+        }
+
+        // `heap_calls` counts every call the list makes to the heap, for its sentinel and owner
+        // as well as its nodes
+        trim_node_cache();
+        assert_eq!(cached(), 0);
+
+        let made = Cell::new(0);
+        let dropped = Rc::new(Cell::new(0));
+        let list : IList<Any> = IList::new();
+
+        // The first frame gets its nodes from the heap, and keeps them when they're freed. The
+        // list makes its sentinel and owner, which aren't cached, and keeps them.
+        let (allocs, frees) = heap_calls();
+        frame(&list, 100, &made, &dropped);
+        assert_eq!(heap_calls(), (allocs + 102, frees));
+        assert_eq!(cached(), 100);
+
+        // After that, nothing goes to the heap at all, and every particle is still made and
+        // dropped once
+        for _ in 0..10 {
+            frame(&list, 100, &made, &dropped);
+        }
+        assert_eq!(heap_calls(), (allocs + 102, frees));
+        assert_eq!(made.get(), 1100);
+        assert_eq!(dropped.get(), 1100);
+
+        // A node held by a weak reference isn't returned until the weak reference is dropped
+        let node = particle(&made, &dropped);
+        let weak = node.downgrade();
+        drop(node);
+        assert_eq!(dropped.get(), 1101);
+        assert_eq!(cached(), 99);
+        drop(weak);
+        assert_eq!(cached(), 100);
+
+        // Blocks freed past the limit go back to the heap
+        frame(&list, NODE_CACHE_LIMIT + 10, &made, &dropped);
+        assert_eq!(heap_calls(), (allocs + 2 + NODE_CACHE_LIMIT + 10, frees + 10));
+        assert_eq!(cached(), NODE_CACHE_LIMIT);
+        assert_eq!(made.get(), dropped.get());
+
+        // Dropping the list frees its sentinel and owner, but the node blocks stay cached
+        drop(list);
+        assert_eq!(heap_calls(), (allocs + 2 + NODE_CACHE_LIMIT + 10, frees + 12));
+        assert_eq!(cached(), NODE_CACHE_LIMIT);
+        trim_node_cache();
+        assert_eq!(cached(), 0);
+        assert_eq!(heap_calls(),
+                   (allocs + 2 + NODE_CACHE_LIMIT + 10, frees + 12 + NODE_CACHE_LIMIT));
+    }
+
+    #[test]
+    fn for_each_node() {
+        fn make_list(n: usize) -> IList<usize> {
+            IList::from_nodes((0..n).map(INode::new_sized))
+        }
+
+        fn values(list: &IList<usize>) -> Vec<usize> {
+            list.iter().map(|n| *n).collect()
+        }
+
+        // An observer removing itself
+        let list = make_list(5);
+        let mut seen = Vec::new();
+        list.for_each_node(|node| {
+            seen.push(**node);
+            if **node % 2 == 1 {
+                node.remove_from_list();
+            }
+        });
+        assert_eq!(seen, [0, 1, 2, 3, 4]);
+        assert_eq!(values(&list), [0, 2, 4]);
+
+        // An observer removing the one after it, which is never reached
+        let list = make_list(5);
+        let mut seen = Vec::new();
+        list.for_each_node(|node| {
+            seen.push(**node);
+            if **node == 1 {
+                node.next().unwrap().remove_from_list();
+            }
+        });
+        assert_eq!(seen, [0, 1, 3, 4]);
+        assert_eq!(values(&list), [0, 1, 3, 4]);
+
+        // An observer removing the one before it
+        let list = make_list(5);
+        let mut seen = Vec::new();
+        list.for_each_node(|node| {
+            seen.push(**node);
+            if **node == 2 {
+                node.prev().unwrap().remove_from_list();
+            }
+        });
+        assert_eq!(seen, [0, 1, 2, 3, 4]);
+        assert_eq!(values(&list), [0, 2, 3, 4]);
+
+        // An observer removing itself and the one after it, which carries on after the last
+        // observer still in the list
+        let list = make_list(5);
+        let mut seen = Vec::new();
+        list.for_each_node(|node| {
+            seen.push(**node);
+            if **node == 1 {
+                node.next().unwrap().remove_from_list();
+                node.remove_from_list();
+            }
+        });
+        assert_eq!(seen, [0, 1, 3, 4]);
+        assert_eq!(values(&list), [0, 3, 4]);
+
+        // The same with the first observer, which carries on from the head
+        let list = make_list(4);
+        let mut seen = Vec::new();
+        list.for_each_node(|node| {
+            seen.push(**node);
+            if **node == 0 {
+                node.next().unwrap().remove_from_list();
+                node.remove_from_list();
+            }
+        });
+        assert_eq!(seen, [0, 2, 3]);
+        assert_eq!(values(&list), [2, 3]);
+
+        // New observers added during notification are notified too
+        let list = make_list(3);
+        let mut seen = Vec::new();
+        list.for_each_node(|node| {
+            seen.push(**node);
+            if **node == 0 {
+                list.push_back(INode::new_sized(10));
+                node.insert_after(INode::new_sized(20));
+            }
+        });
+        assert_eq!(seen, [0, 20, 1, 2, 10]);
+        assert_eq!(values(&list), [0, 20, 1, 2, 10]);
+
+        // Removing the last observer, and the list emptying out
+        let list = make_list(3);
+        let mut seen = Vec::new();
+        list.for_each_node(|node| {
+            seen.push(**node);
+            node.remove_from_list();
+        });
+        assert_eq!(seen, [0, 1, 2]);
+        assert!(list.is_empty());
+        list.check_invariants();
+    }
+
+    #[test]
+    fn drain() {
+        let list : IList<Display> = IList::new();
+        let nodes : Vec<INode<Display>> = (0..4).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list.push_back(node.clone());
+        }
+
+        let drained : Vec<INode<Display>> = list.drain().collect();
+        assert!(list.is_empty());
+        assert_eq!(drained.len(), 4);
+        for (node, drained) in nodes.iter().zip(drained.iter()) {
+            assert!(node == drained);
+            assert!(!drained.in_list());
+            assert!(drained.next().is_none() && drained.prev().is_none());
+            assert_eq!(drained.strong_count(), 2);
+        }
+
+        // The list can be reused, here to put the nodes back in reverse
+        for node in drained.into_iter().rev() {
+            list.push_back(node);
+        }
+        assert_eq!(strings(&list), ["3", "2", "1", "0"]);
+        assert_eq!(nodes[0].strong_count(), 2);
+
+        // Dropping the iterator part way through removes the rest
+        {
+            let mut drain = list.drain();
+            assert!(drain.next().unwrap() == nodes[3]);
+            assert!(drain.next_back().unwrap() == nodes[0]);
+        }
+        assert!(list.is_empty());
+        for node in &nodes {
+            assert_eq!(node.strong_count(), 1);
+            assert!(!node.in_list());
+        }
+
+        list.push_back(nodes[1].clone());
+        assert_eq!(strings(&list), ["1"]);
+    }
+
+    #[test]
+    fn find() {
+        use std::cell::Cell;
+
+        let list : IList<Display> = IList::new();
+        assert!(list.find(|_| true).is_none());
+        assert_eq!(list.position(|_| true), None);
+        assert!(list.nth(0).is_none());
+
+        let nodes : Vec<INode<Display>> = (0..5).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list.push_back(node.clone());
+        }
+
+        for &(s, i) in &[("0", 0), ("2", 2), ("4", 4)] {
+            let found = list.find(|x| x.to_string() == s).unwrap();
+            assert!(found == nodes[i]);
+            assert_eq!(found.strong_count(), 3);
+            assert_eq!(list.position(|x| x.to_string() == s), Some(i));
+            assert!(list.nth(i).unwrap() == nodes[i]);
+        }
+
+        assert!(list.find(|x| x.to_string() == "5").is_none());
+        assert_eq!(list.position(|x| x.to_string() == "5"), None);
+        assert!(list.nth(5).is_none());
+
+        let mut probes = 0;
+        assert_eq!(list.position(|_| { probes += 1; probes == 3 }), Some(2));
+        for node in &nodes {
+            assert_eq!(node.strong_count(), 2);
+        }
+
+        // A predicate that removes nodes ends the search early
+        assert!(list.find(|_| { list.pop_front(); false }).is_none());
+        assert_eq!(nodes[0].strong_count(), 1);
+
+        // The node being probed stays alive even if the predicate drops the list's reference
+        // to it
+        struct Dropped<'a>(&'a Cell<usize>);
+        impl<'a> Drop for Dropped<'a> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let list : IList<Dropped> = IList::new();
+        list.push_back(INode::new_sized(Dropped(&drops)));
+        let found = list.find(|_| {
+            drop(list.pop_front());
+            assert_eq!(drops.get(), 0);
+            false
+        });
+        assert!(found.is_none());
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn from_iter() {
+        let mut list : IList<Display> = (0..3).collect();
+        assert_eq!(strings(&list), ["0", "1", "2"]);
+        list.extend(vec!["a", "b"]);
+        assert_eq!(strings(&list), ["0", "1", "2", "a", "b"]);
+
+        let nums : IList<[i32]> = vec![[1, 2], [3, 4]].into_iter().collect();
+        assert_eq!(&*nums.tail().unwrap(), [3, 4]);
+
+        // Collecting nodes takes them out of the list they were in
+        let nodes : Vec<INode<Display>> = list.iter().collect();
+        let other = IList::from_nodes(nodes.iter().rev().take(2).cloned());
+        assert_eq!(strings(&other), ["b", "a"]);
+        assert_eq!(strings(&list), ["0", "1", "2"]);
+        assert!(other.contains(&nodes[4]));
+
+        other.extend_nodes(list.iter().skip(1));
+        assert_eq!(strings(&other), ["b", "a", "1", "2"]);
+        assert_eq!(strings(&list), ["0"]);
+        assert_eq!(nodes[2].strong_count(), 2);
+    }
+
+    #[test]
+    fn clone_eq() {
+        use std::cell::RefCell;
+
+        let list : IList<RefCell<String>> = IList::new();
+        let nodes : Vec<INode<RefCell<String>>> = (0..3).map(|i| {
+            INode::new_sized(RefCell::new(i.to_string()))
+        }).collect();
+        for node in &nodes {
+            list.push_back(node.clone());
+        }
+
+        let copy = list.clone();
+        assert!(copy == list);
+        assert!(copy.id() != list.id());
+        assert_eq!(copy.iter().count(), 3);
+
+        // The copy has its own nodes
+        for (a, b) in list.iter().zip(copy.iter()) {
+            assert!(!INode::ptr_eq(&a, &b));
+            assert!(!list.contains(&b));
+            assert!(copy.contains(&b));
+            assert_eq!(b.strong_count(), 2);
+        }
+
+        nodes[1].set(String::from("changed"));
+        assert!(copy != list);
+        let vals : Vec<String> = copy.iter().map(|n| n.borrow().clone()).collect();
+        assert_eq!(vals, ["0", "1", "2"]);
+
+        // Lists built in different ways compare by their data
+        let a : IList<i32> = IList::from_nodes((0..3).map(INode::new_sized));
+        let b : IList<i32> = IList::new();
+        b.push_front(INode::new_sized(2));
+        b.push_front(INode::new_sized(0));
+        b.head().unwrap().insert_after(INode::new_sized(1));
+        assert!(a == b);
+        assert!(a.clone() == b);
+
+        b.pop_back();
+        assert!(a != b);
+        assert!(b != a);
+        assert!(IList::<i32>::new() == IList::new());
+        assert!(IList::<i32>::new().clone().is_empty());
+
+        let dyns : IList<[i32]> = IList::new();
+        dyns.push_back(INode::new([1, 2]));
+        let others : IList<[i32]> = IList::new();
+        others.push_back(INode::new_boxed(vec![1, 2].into_boxed_slice()));
+        assert!(dyns == others);
+    }
+
+    #[test]
+    fn debug() {
+        use std::fmt::Debug;
+
+        let list : IList<Debug> = IList::new();
+        assert_eq!(format!("{:?}", list), "[]");
+
+        list.push_back(INode::new(1));
+        list.push_back(INode::new("two"));
+        list.push_back(INode::new(Some(3.5)));
+        list.push_back(INode::new((4, '5')));
+        assert_eq!(format!("{:?}", list), "[1, \"two\", Some(3.5), (4, '5')]");
+
+        let head = list.head().unwrap();
+        assert_eq!(format!("{:?}", head), "1");
+        assert_eq!(format!("{:?}", head.next()), "Some(\"two\")");
+
+        let nested : IList<[i32]> = IList::new();
+        nested.push_back(INode::new([1, 2]));
+        assert_eq!(format!("{:?}", nested), "[[1, 2]]");
+    }
+
+    #[test]
+    fn peek() {
+        let list : IList<Display> = IList::new();
+        unsafe {
+            assert!(list.peek_front().is_none());
+            assert!(list.peek_back().is_none());
+        }
+
+        let nodes : Vec<INode<Display>> = (0..3).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list.push_back(node.clone());
+        }
+
+        unsafe {
+            let front = list.peek_front().map(|x| x.to_string());
+            let back = list.peek_back().map(|x| x.to_string());
+            assert_eq!(nodes[0].strong_count(), 2);
+            assert_eq!(nodes[2].strong_count(), 2);
+            assert_eq!(front, list.head().map(|n| n.as_ref().to_string()));
+            assert_eq!(back, list.tail().map(|n| n.as_ref().to_string()));
+            assert_eq!(front.unwrap(), "0");
+            assert_eq!(back.unwrap(), "2");
+        }
+
+        list.pop_front();
+        list.pop_back();
+        unsafe {
+            assert_eq!(list.peek_front().unwrap().to_string(), "1");
+            assert_eq!(list.peek_back().unwrap().to_string(), "1");
+        }
+        assert_eq!(nodes[1].strong_count(), 2);
+    }
+
+    #[test]
+    fn try_insert() {
+        let list : IList<Display> = IList::new();
+        let a : INode<Display> = INode::new(1);
+        let b : INode<Display> = INode::new(2);
+
+        let err = a.try_insert_after(b.clone()).unwrap_err();
+        assert_eq!(format!("{:?}", err), "NotInList { .. }");
+        assert_eq!(err.to_string(), "node isn't in a list");
+        let b2 = err.into_inner();
+        assert!(b2 == b);
+        assert!(!b2.in_list());
+        assert_eq!(b.strong_count(), 2);
+        drop(b2);
+
+        let err = a.try_insert_before(b.clone()).unwrap_err();
+        drop(err);
+        assert_eq!(b.strong_count(), 1);
+
+        // A node taken out of its list can't be inserted next to
+        list.push_back(a.clone());
+        a.remove_from_list();
+        assert!(a.try_insert_before(b.clone()).is_err());
+
+        list.push_back(a.clone());
+        a.try_insert_after(b.clone()).unwrap();
+        let c : INode<Display> = INode::new(3);
+        b.try_insert_before(c.clone()).unwrap();
+        let vals : Vec<String> = list.iter().map(|n| n.to_string()).collect();
+        assert_eq!(vals, ["1", "3", "2"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_after_not_in_list() {
+        let a : INode<Display> = INode::new(1);
+        a.insert_after(INode::new(2));
+    }
+
+    #[test]
+    fn push_boxed() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct Counted(&'static str, Rc<Cell<usize>>);
+        impl Display for Counted {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        let count = Rc::new(Cell::new(0));
+        let list : IList<Display> = IList::new();
+        let boxes : Vec<Box<Display>> = vec![box 1, box "two", box 3.5f64, box 'c'];
+        for b in boxes.into_iter() {
+            list.push_back_boxed(b);
+        }
+        list.push_front_boxed(box 0u8);
+        list.push_back_boxed(box Counted("counted", count.clone()));
+        list.push_back(INode::new(6));
+
+        let vals : Vec<String> = list.iter().map(|n| n.to_string()).collect();
+        assert_eq!(vals, ["0", "1", "two", "3.5", "c", "counted", "6"]);
+        assert_eq!(count.get(), 0);
+
+        let node = list.tail().unwrap().prev().unwrap();
+        let weak = node.downgrade();
+        drop(list);
+        assert_eq!(count.get(), 0);
+        drop(node);
+        assert_eq!(count.get(), 1);
+        assert!(weak.upgrade().is_none());
+
+        let list : IList<[u8]> = IList::new();
+        let buffer : Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+        list.push_back_boxed(buffer.clone().into_boxed_slice());
+        list.push_front_boxed(vec![].into_boxed_slice());
+        list.push_back_boxed(vec![1, 2, 3].into_boxed_slice());
+
+        let mut iter = list.iter();
+        assert_eq!(&*iter.next().unwrap(), &[][..]);
+        assert_eq!(&*iter.next().unwrap(), &buffer[..]);
+        assert_eq!(&*iter.next().unwrap(), &[1, 2, 3][..]);
+        assert!(iter.next().is_none());
+
+        let mut node : INode<[u64]> = INode::new_boxed(vec![1, 2, 3].into_boxed_slice());
+        node.get_mut().unwrap()[1] = 20;
+        assert_eq!(&*node, &[1, 20, 3][..]);
+    }
+
+    #[test]
+    fn borrow_mut() {
+        use std::any::Any;
+        use std::cell::RefCell;
+
+        let list : IList<RefCell<[i32]>> = IList::new();
+        let node : INode<RefCell<[i32]>> = INode::new_mut([1, 2, 3]);
+        list.push_back(node.clone());
+
+        // Shared borrows can be nested
+        {
+            let a = node.borrow();
+            let b = node.borrow();
+            let head = list.head().unwrap();
+            let c = head.borrow();
+            assert_eq!(a[0] + b[1] + c[2], 6);
+        }
+
+        // Changes show up through the other handles
+        node.borrow_mut()[1] = 20;
+        assert_eq!(&*list.head().unwrap().borrow(), [1, 20, 3]);
+        for n in &list {
+            n.borrow_mut()[0] += 10;
+        }
+        assert_eq!(&*node.borrow(), [11, 20, 3]);
+
+        // Trait objects work too
+        let any : IList<RefCell<Any>> = IList::new();
+        any.push_back(INode::new_mut(String::from("a")));
+        any.head().unwrap().borrow_mut().downcast_mut::<String>().unwrap().push_str("b");
+        assert_eq!(any.head().unwrap().borrow().downcast_ref::<String>().unwrap(), "ab");
+    }
+
+    #[test]
+    #[should_panic]
+    fn borrow_mut_while_borrowed() {
+        use std::cell::RefCell;
+
+        let node : INode<RefCell<[i32]>> = INode::new_mut([1]);
+        let clone = node.clone();
+        let _a = node.borrow();
+        let _b = clone.borrow_mut();
+    }
+
+    #[test]
+    #[should_panic]
+    fn borrow_mut_twice() {
+        use std::cell::RefCell;
+
+        let node : INode<RefCell<[i32]>> = INode::new_mut([1]);
+        let _a = node.borrow_mut();
+        let _b = node.borrow_mut();
+    }
+
+    #[test]
+    fn reverse() {
+        fn strings_rev(list: &IList<Display>) -> Vec<String> {
+            list.iter_rev().map(|n| n.to_string()).collect()
+        }
+
+        let list : IList<Display> = IList::new();
+        list.reverse();
+        assert!(list.is_empty());
+
+        let node : INode<Display> = INode::new(0);
+        list.push_back(node.clone());
+        list.reverse();
+        assert_eq!(strings(&list), ["0"]);
+        assert!(node.next().is_none() && node.prev().is_none());
+
+        let nodes : Vec<INode<Display>> = (1..5).map(|i| INode::new(i)).collect();
+        for n in &nodes {
+            list.push_back(n.clone());
+        }
+
+        list.reverse();
+        assert_eq!(strings(&list), ["4", "3", "2", "1", "0"]);
+        assert_eq!(strings_rev(&list), ["0", "1", "2", "3", "4"]);
+        assert_eq!(nodes[1].next().unwrap().to_string(), "1");
+        assert_eq!(nodes[1].prev().unwrap().to_string(), "3");
+        assert!(nodes[3].prev().is_none());
+        assert!(node.next().is_none());
+        for n in &nodes {
+            assert_eq!(n.strong_count(), 2);
+        }
+
+        list.reverse();
+        assert_eq!(strings(&list), ["0", "1", "2", "3", "4"]);
+        assert_eq!(strings_rev(&list), ["4", "3", "2", "1", "0"]);
+
+        // Pushing and popping still work at the right ends
+        list.reverse();
+        list.push_back(INode::new("end"));
+        assert_eq!(list.pop_front().unwrap().to_string(), "4");
+        assert_eq!(strings(&list), ["3", "2", "1", "0", "end"]);
+    }
+
+    #[test]
+    fn sort() {
+        fn shuffle<T>(values: &mut [T], mut seed: u32) {
+            for i in (1..values.len()).rev() {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let j = (seed >> 16) as usize % (i + 1);
+                values.swap(i, j);
+            }
+        }
+
+        fn keys(list: &IList<[i32]>) -> Vec<i32> {
+            list.iter().map(|n| n[0]).collect()
+        }
+
+        let list : IList<[i32]> = IList::new();
+        list.sort();
+        assert!(list.is_empty());
+
+        for len in 1..40 {
+            let mut values : Vec<i32> = (0..len).collect();
+            shuffle(&mut values, len as u32);
+
+            let list : IList<[i32]> = IList::new();
+            let nodes : Vec<INode<[i32]>> = values.iter().map(|&v| INode::new([v])).collect();
+            for node in &nodes {
+                list.push_back(node.clone());
+            }
+
+            list.sort();
+            let expected : Vec<i32> = (0..len).collect();
+            assert_eq!(keys(&list), expected);
+
+            let rev : Vec<i32> = list.iter_rev().map(|n| n[0]).collect();
+            let mut expected_rev = expected.clone();
+            expected_rev.reverse();
+            assert_eq!(rev, expected_rev);
+
+            // Existing handles point at the same data, now with sorted neighbours
+            for node in &nodes {
+                let v = node[0];
+                assert_eq!(node.prev().map(|n| n[0]), if v > 0 { Some(v - 1) } else { None });
+                assert_eq!(node.next().map(|n| n[0]), if v < len - 1 { Some(v + 1) } else { None });
+                assert_eq!(node.strong_count(), 2);
+                assert!(list.contains(node));
+            }
+        }
+
+        // The sort is stable
+        let list : IList<[i32]> = IList::new();
+        for (i, &k) in [2, 1, 2, 1, 0].iter().enumerate() {
+            list.push_back(INode::new([k, i as i32]));
+        }
+        list.sort_by(|a, b| a[0].cmp(&b[0]));
+        let pairs : Vec<(i32, i32)> = list.iter().map(|n| (n[0], n[1])).collect();
+        assert_eq!(pairs, [(0, 4), (1, 1), (1, 3), (2, 0), (2, 2)]);
+
+        list.sort_by(|a, b| b[1].cmp(&a[1]));
+        assert_eq!(list.head().unwrap()[1], 4);
+        assert_eq!(list.tail().unwrap()[1], 0);
+    }
+
+    #[test]
+    fn sort_hides_nodes() {
+        let list : IList<[i32]> = IList::new();
+        let nodes : Vec<INode<[i32]>> = (0..4).map(|i| INode::new([3 - i])).collect();
+        list.extend_nodes(nodes.iter().cloned());
+
+        // The nodes being sorted aren't in the list, or any other, until the sort is done
+        list.sort_by(|a, b| {
+            for node in &nodes {
+                assert!(!list.contains(node));
+                assert_eq!(node.owner_id(), None);
+            }
+            a[0].cmp(&b[0])
+        });
+
+        for node in &nodes {
+            assert!(list.contains(node));
+        }
+        assert_eq!(list.iter().map(|n| n[0]).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        list.check_invariants();
+    }
+
+    #[test]
+    #[should_panic]
+    fn sort_remove_from_comparison() {
+        let list : IList<[i32]> = IList::new();
+        let nodes : Vec<INode<[i32]>> = (0..4).map(|i| INode::new([3 - i])).collect();
+        list.extend_nodes(nodes.iter().cloned());
+
+        list.sort_by(|a, b| {
+            nodes[3].remove_from_list();
+            a[0].cmp(&b[0])
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn sort_insert_from_comparison() {
+        let list : IList<[i32]> = IList::new();
+        let nodes : Vec<INode<[i32]>> = (0..4).map(|i| INode::new([3 - i])).collect();
+        list.extend_nodes(nodes.iter().cloned());
+
+        list.sort_by(|a, b| {
+            nodes[0].insert_before(INode::new([4]));
+            a[0].cmp(&b[0])
+        });
+    }
+
+    #[test]
+    fn cursor_basic() {
+        let list : IList<Display> = IList::new();
+
+        list.push_back(INode::new(0));
+        list.push_back(INode::new(1));
+        list.push_back(INode::new(2));
+        list.push_back(INode::new(3));
+        list.push_back(INode::new(4));
+        list.push_back(INode::new(5));
+
+        {
+            let mut cursor = list.cursor();
+            cursor.remove();
+
+            cursor.next();
+            cursor.next();
+
+            cursor.insert_before(INode::new(6));
+
+            cursor.remove();
+
+            cursor.insert_after(INode::new(7));
+        }
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "1");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "2");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "6");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "7");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "4");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "5");
+    }
+
+    #[test]
+    fn cursor_moves() {
+        let list : IList<Display> = IList::new();
+        let nodes : Vec<INode<Display>> = (0..5).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list.push_back(node.clone());
+        }
+
+        let mut cursor = list.cursor();
+        assert!(cursor.prev().is_none());
+        assert_eq!(cursor.peek().unwrap().to_string(), "0");
+        assert!(cursor.next().unwrap() == nodes[0]);
+        assert!(cursor.next().unwrap() == nodes[1]);
+        assert!(cursor.prev().unwrap() == nodes[1]);
+
+        cursor.seek_to_end();
+        assert!(cursor.peek().is_none());
+        assert!(cursor.next().is_none());
+        assert!(cursor.remove().is_none());
+        cursor.insert_before(INode::new("end"));
+        assert!(cursor.prev().unwrap().to_string() == "end");
+        cursor.seek_to_start();
+        cursor.insert_after(INode::new("start"));
+        assert_eq!(strings(&list), ["start", "0", "1", "2", "3", "4", "end"]);
+
+        // Removing the node after the cursor through another handle moves the cursor on to the
+        // one that followed it
+        cursor.next();
+        cursor.next();
+        assert_eq!(cursor.peek().unwrap().to_string(), "1");
+        nodes[1].remove_from_list();
+        assert_eq!(cursor.peek().unwrap().to_string(), "2");
+
+        // A node inserted right before the cursor is skipped back over
+        nodes[2].insert_before(INode::new("new"));
+        assert_eq!(cursor.prev().unwrap().to_string(), "new");
+        assert_eq!(cursor.next().unwrap().to_string(), "new");
+
+        // If both neighbours go, the cursor goes back to the start
+        nodes[2].remove_from_list();
+        list.remove(&list.find(|x| x.to_string() == "new").unwrap());
+        assert_eq!(cursor.peek().unwrap().to_string(), "start");
+
+        let removed = cursor.remove().unwrap();
+        assert!(!removed.in_list());
+        assert_eq!(removed.strong_count(), 1);
+        assert_eq!(strings(&list), ["0", "3", "4", "end"]);
+    }
+
+    #[test]
+    fn replace() {
+        use std::cell::RefCell;
+
+        let list : IList<RefCell<String>> = IList::new();
+        let nodes : Vec<INode<RefCell<String>>> = (0..3).map(|i| {
+            INode::new_sized(RefCell::new(i.to_string()))
+        }).collect();
+        for node in &nodes {
+            list.push_back(node.clone());
+        }
+
+        let old = nodes[1].replace(String::from("one"));
+        assert_eq!(old, "1");
+        assert_eq!(*list.head().unwrap().next().unwrap().borrow(), "one");
+
+        list.tail().unwrap().set(String::from("two"));
+        assert_eq!(*nodes[2].borrow(), "two");
+
+        assert_eq!(nodes[0].take(), "0");
+        let vals : Vec<String> = list.iter().map(|n| n.borrow().clone()).collect();
+        assert_eq!(vals, ["", "one", "two"]);
+
+        let node : INode<String> = INode::new_sized(String::from("sized"));
+        assert_eq!(&*node, "sized");
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_while_borrowed() {
+        use std::cell::RefCell;
+
+        let node : INode<RefCell<i32>> = INode::new_sized(RefCell::new(1));
+        let clone = node.clone();
+        let _borrowed = clone.borrow();
+        node.set(2);
+    }
+
+    #[test]
+    fn sentinel() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct Shown(usize, Rc<Cell<usize>>);
+        impl Display for Shown {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+        impl Drop for Shown {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let list : IList<Display> = IList::new();
+
+        list.push_back(INode::new(Shown(1, drops.clone())));
+        list.push_front(INode::new("zero"));
+        list.push_back(INode::new(2.5));
+
+        // The sentinel is only links, with no node around it
+        assert!(list.sentinel().is_sentinel());
+        assert!(list.sentinel().node.get().is_null());
+        assert_eq!(list.sentinel().owner_id(), list.id());
+
+        let head = list.head().unwrap();
+        let tail = list.tail().unwrap();
+        assert!(head.links::<Primary>().prev.get() == list.sentinel().to_raw());
+        assert!(tail.links::<Primary>().next.get() == list.sentinel().to_raw());
+        assert!(!head.links::<Primary>().is_sentinel());
+
+        let strings : Vec<String> = list.iter().map(|n| n.to_string()).collect();
+        assert_eq!(strings, ["zero", "1", "2.5"]);
+        let strings : Vec<String> = list.iter_rev().map(|n| n.to_string()).collect();
+        assert_eq!(strings, ["2.5", "1", "zero"]);
+        unsafe {
+            let strings : Vec<String> = list.iter_data().map(|x| x.to_string()).collect();
+            assert_eq!(strings, ["zero", "1", "2.5"]);
+        }
+
+        drop(head);
+        drop(tail);
+        drop(list);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn lazy_sentinel() {
+        use node_cache::heap_calls;
+
+        // The nodes are made up front, so every heap call after this is for a sentinel or owner
+        let loose : INode<Display> = INode::new(0);
+        let one : INode<Display> = INode::new(1);
+        let two : INode<Display> = INode::new(2);
+
+        // Nothing that only looks at an empty list allocates its sentinel
+        let (allocs, frees) = heap_calls();
+        let list : IList<Display> = IList::new();
+        assert!(list.is_empty());
+        assert!(list.head().is_none());
+        assert!(list.tail().is_none());
+        assert!(list.pop_front().is_none());
+        assert!(list.pop_back().is_none());
+        assert!(list.nth(0).is_none());
+        assert!(list.find(|_| true).is_none());
+        assert!(!list.contains(&loose));
+        assert!(!list.remove(&loose));
+        assert_eq!(list.iter().count(), 0);
+        unsafe {
+            assert!(list.peek_front().is_none());
+            assert_eq!(list.iter_data().count(), 0);
+        }
+        list.sort_by(|a, b| a.to_string().cmp(&b.to_string()));
+        list.retain(|_| false);
+        list.append(&IList::new());
+        assert!(list.sentinel.get().is_null());
+        drop(list);
+        assert_eq!(heap_calls(), (allocs, frees));
+
+        // Pushing to a list without a sentinel behaves as it would with one. The list makes its
+        // sentinel and owner.
+        let list : IList<Display> = IList::new();
+        list.push_front(one);
+        assert!(!list.sentinel.get().is_null());
+        assert_eq!(heap_calls().0, allocs + 2);
+        list.push_back(two);
+        list.push_front(loose.clone());
+        assert_eq!(strings(&list), ["0", "1", "2"]);
+        assert!(list.contains(&loose));
+        assert_eq!(loose.owner_id(), Some(list.id()));
+
+        let other : IList<Display> = IList::new();
+        other.append(&list);
+        assert_eq!(strings(&other), ["0", "1", "2"]);
+        assert!(list.is_empty());
+        assert_eq!(loose.owner_id(), Some(other.id()));
+
+        let rest = other.split_at(&loose);
+        assert!(other.is_empty());
+        assert_eq!(strings(&rest), ["0", "1", "2"]);
+
+        // An unused list still has an id of its own
+        let unused : IList<Display> = IList::new();
+        let id = unused.id();
+        assert!(unused.sentinel.get().is_null());
+        assert_eq!(unused.id(), id);
+        assert!(id != rest.id());
+
+        // Only the lists that had nodes pushed or moved into them made a sentinel and owner, and
+        // `append` gave `list` a fresh owner
+        assert_eq!(heap_calls().0, allocs + 7);
+    }
+
+    #[test]
+    fn drop_list_with_handles() {
+        use std::any::Any;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let list : IList<Display> = IList::new();
+        let nodes : Vec<INode<Display>> = (0..5).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list.push_back(node.clone());
+        }
+
+        let first = nodes[0].clone();
+        let middle = nodes[2].clone();
+        let last = nodes[4].clone();
+        drop(nodes);
+        drop(list);
+
+        for node in &[first, middle, last] {
+            assert!(!node.in_list());
+            assert!(node.owner_id().is_none());
+            assert!(node.next().is_none());
+            assert!(node.prev().is_none());
+            assert_eq!(node.strong_count(), 1);
+            assert!(node.links::<Primary>().next.get().is_null());
+            assert!(node.links::<Primary>().prev.get().is_null());
+        }
+
+        // A node whose data removes the node after it when dropped, freeing that node before the
+        // list gets to it
+        struct Unlinker(Option<INode<Any>>, Rc<Cell<usize>>);
+        impl Drop for Unlinker {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+                if let Some(node) = self.0.take() {
+                    node.remove_from_list();
+                }
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let list : IList<Any> = IList::new();
+        let third : INode<Any> = INode::new(Unlinker(None, drops.clone()));
+        let second : INode<Any> = INode::new(Unlinker(None, drops.clone()));
+        let first : INode<Any> = INode::new(Unlinker(Some(second.clone()), drops.clone()));
+        list.push_back(first);
+        list.push_back(second);
+        list.push_back(third.clone());
+
+        drop(list);
+        assert_eq!(drops.get(), 2);
+        assert!(!third.in_list());
+        assert!(third.prev().is_none());
+        drop(third);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn remove_releases_list_reference() {
+        use std::any::Any;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct DropCounter(Rc<Cell<usize>>);
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let list : IList<Any> = IList::new();
+
+        // Only the list refers to the node, so removing it through a temporary handle frees it
+        list.push_back(INode::new(DropCounter(drops.clone())));
+        list.head().unwrap().remove_from_list();
+        assert_eq!(drops.get(), 1);
+        assert!(list.is_empty());
+
+        // The same goes for a node in the middle of the list
+        list.push_back(INode::new(0));
+        list.push_back(INode::new(DropCounter(drops.clone())));
+        list.push_back(INode::new(2));
+        list.nth(1).unwrap().remove_from_list();
+        assert_eq!(drops.get(), 2);
+        assert_eq!(list.iter().count(), 2);
+        assert_eq!(list.head().unwrap().next().unwrap().strong_count(), 2);
+
+        // With an outside handle, removing the node only releases the list's reference
+        let node : INode<Any> = INode::new(DropCounter(drops.clone()));
+        list.push_back(node.clone());
+        assert_eq!(node.strong_count(), 2);
+        node.remove_from_list();
+        assert_eq!(node.strong_count(), 1);
+        assert_eq!(drops.get(), 2);
+
+        // Removing it again does nothing
+        node.remove_from_list();
+        assert_eq!(node.strong_count(), 1);
+        assert!(!node.in_list());
+        assert_eq!(list.iter().count(), 2);
+
+        drop(node);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn two_lists() {
+        use std::mem;
+
+        // A node carries its `Primary` links, and only a pointer to the `Secondary` ones
+        assert_eq!(mem::size_of::<Node<u64>>(),
+                   mem::size_of::<NodeLinks<u64>>() + 4 * mem::size_of::<usize>());
+
+        let all : IList<Display> = IList::new();
+        let dirty : IList<Display, Secondary> = IList::new();
+
+        let nodes : Vec<INode<Display>> = (0..4).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            all.push_back(node.clone());
+        }
+        assert!(nodes.iter().all(|n| n.node().secondary.get().is_null()));
+
+        dirty.push_back(nodes[3].clone());
+        dirty.push_back(nodes[1].clone());
+        assert!(!nodes[3].node().secondary.get().is_null());
+        assert!(nodes[0].node().secondary.get().is_null());
+
+        assert_eq!(strings(&all), ["0", "1", "2", "3"]);
+        assert_eq!(strings(&dirty), ["3", "1"]);
+
+        // Each list holds its own reference
+        assert_eq!(nodes[1].strong_count(), 3);
+        assert!(nodes[1].in_list());
+        assert!(nodes[1].link::<Secondary>().in_list());
+        assert!(!nodes[0].link::<Secondary>().in_list());
+        assert!(all.contains(&nodes[1]) && dirty.contains(&nodes[1]));
+        assert!(!dirty.contains(&nodes[0]));
+        assert_eq!(nodes[1].owner_id(), Some(all.id()));
+        assert_eq!(nodes[1].link::<Secondary>().owner_id(), Some(dirty.id()));
+
+        // The links of each kind only see their own list
+        assert!(nodes[3].next().is_none());
+        assert_eq!(nodes[3].link::<Secondary>().next().unwrap().to_string(), "1");
+        assert_eq!(nodes[1].prev().unwrap().to_string(), "0");
+        assert_eq!(nodes[1].link::<Secondary>().prev().unwrap().to_string(), "3");
+        assert_eq!(nodes[3].link::<Secondary>().distance_to(&nodes[1]), Some(1));
+        assert_eq!(nodes[3].distance_to(&nodes[1]), Some(2));
+
+        // Removing a node from one list leaves it in the other
+        nodes[1].link::<Secondary>().remove_from_list();
+        assert_eq!(strings(&dirty), ["3"]);
+        assert_eq!(strings(&all), ["0", "1", "2", "3"]);
+        assert!(nodes[1].in_list());
+        assert_eq!(nodes[1].strong_count(), 2);
+
+        nodes[2].remove_from_list();
+        assert_eq!(strings(&all), ["0", "1", "3"]);
+        assert_eq!(strings(&dirty), ["3"]);
+
+        // Inserting through the second links puts the node in the second list
+        nodes[3].link::<Secondary>().insert_before(nodes[0].clone());
+        assert_eq!(strings(&dirty), ["0", "3"]);
+        assert_eq!(strings(&all), ["0", "1", "3"]);
+        assert!(nodes[2].link::<Secondary>().try_insert_after(INode::new(5)).is_err());
+
+        // A node in either list isn't unshared, and dropping one list leaves the other alone
+        all.remove(&nodes[3]);
+        assert!(!nodes[3].is_sole_owner());
+        drop(dirty);
+        assert!(!nodes[3].link::<Secondary>().in_list());
+        assert_eq!(nodes[3].strong_count(), 1);
+        assert!(nodes[3].is_sole_owner());
+        assert_eq!(strings(&all), ["0", "1"]);
+        assert!(nodes[0].in_list());
+    }
+
+    #[test]
+    fn check_invariants() {
+        use std::thread;
+
+        fn corrupted(f: fn(&[INode<Display>])) -> bool {
+            thread::spawn(move || {
+                let list : IList<Display> = IList::new();
+                let nodes : Vec<INode<Display>> = (0..4).map(|i| INode::new(i)).collect();
+                for node in &nodes {
+                    list.push_back(node.clone());
+                }
+                f(&nodes);
+                list.check_invariants();
+            }).join().is_err()
+        }
+
+        fn unchanged(_: &[INode<Display>]) {}
+
+        // Popping the nodes off as the list is dropped puts this link right again
+        fn bad_prev(nodes: &[INode<Display>]) {
+            nodes[2].links::<Primary>().prev.set(Raw::null());
+        }
+
+        fn bad_id(nodes: &[INode<Display>]) {
+            let other : IList<Display> = IList::new();
+            nodes[1].links::<Primary>().set_owner(other.sentinel().owner());
+        }
+
+        let list : IList<Display> = IList::new();
+        list.check_invariants();
+        let nodes : Vec<INode<Display>> = (0..4).map(|i| INode::new(i)).collect();
+        for node in &nodes {
+            list.push_front(node.clone());
+            list.check_invariants();
+        }
+        list.reverse();
+        list.move_to_front(&nodes[2]);
+        nodes[1].remove_from_list();
+        list.check_invariants();
+        while list.pop_back().is_some() {
+            list.check_invariants();
+        }
 
+        assert!(!corrupted(unchanged));
+        assert!(corrupted(bad_prev));
+        assert!(corrupted(bad_id));
     }
 }
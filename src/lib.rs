@@ -1,14 +1,39 @@
 #![feature(box_syntax, core, alloc, unsafe_no_drop_flag)]
 #![feature(optin_builtin_traits, filling_drop)]
+#![feature(no_std, collections, fused, const_fn)]
+#![cfg_attr(feature = "std", feature(thread_local_state))]
+#![no_std]
 
-extern crate core;
+//! Linked lists for dynamically-sized types.
+//!
+//! The crate only needs an allocator, so it is `#![no_std]` and builds on `core`, `alloc` and
+//! `collections`. The default `std` feature links `std` for anything that needs it; build with
+//! `default-features = false` to use the crate without it.
+
+#[cfg(any(test, feature = "std"))]
+#[macro_use]
+extern crate std;
+
+extern crate alloc;
+extern crate collections;
 
 mod raw;
+mod node_cache;
+pub mod heap;
 pub mod xorlist;
 pub mod ilist;
+pub mod ailist;
+#[macro_use]
+pub mod elist;
 
 #[doc(inline)]
 pub use xorlist::XorList;
 
 #[doc(inline)]
 pub use ilist::IList;
+
+#[doc(inline)]
+pub use ailist::AIList;
+
+#[doc(inline)]
+pub use elist::EList;
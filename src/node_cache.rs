@@ -0,0 +1,262 @@
+//! A per-thread cache of freed `INode` memory.
+//!
+//! Nodes can't leave the thread they were made on, so each thread keeps its own lists of freed
+//! node blocks, one for each size and alignment, and new nodes are taken from these before going
+//! to the heap. Without the `std` feature there are no thread-locals to keep the lists in, and
+//! nodes always come straight from the heap.
+//!
+//! Everything `ilist` takes from the heap goes through here, including list sentinels and owners,
+//! which aren't cached. The heap is reached through `HEAP`, which the tests swap for an allocator
+//! that counts the calls made to it.
+
+use heap::Allocator;
+
+#[cfg(not(test))]
+use heap::Global;
+
+#[cfg(not(test))]
+const HEAP: Global = Global;
+
+#[cfg(test)]
+const HEAP: counting::Counting = counting::Counting;
+
+/**
+ * The most free blocks of any one size and alignment that a thread keeps. Blocks freed past this
+ * go back to the heap.
+ */
+pub const LIMIT: usize = 256;
+
+#[cfg(feature = "std")]
+pub use self::imp::{allocate, deallocate, trim};
+
+#[cfg(not(feature = "std"))]
+pub use self::heap_only::{allocate, deallocate, trim};
+
+#[cfg(feature = "std")]
+mod imp {
+    use std::prelude::v1::*;
+    use std::cell::RefCell;
+    use std::thread::LocalKeyState;
+    use heap::Allocator;
+
+    use super::{LIMIT, HEAP};
+
+    // The free blocks of one size and alignment, linked through their first word. Every node
+    // starts with its counts, so a block is always big enough, and aligned enough, to hold the
+    // pointer.
+    struct Bin {
+        size: usize,
+        align: usize,
+        head: *mut u8,
+        len: usize
+    }
+
+    struct Cache {
+        bins: Vec<Bin>
+    }
+
+    thread_local!(static CACHE: RefCell<Cache> = RefCell::new(Cache {
+        bins: Vec::new()
+    }));
+
+    impl Cache {
+        fn bin(&mut self, size: usize, align: usize) -> &mut Bin {
+            let idx = match self.bins.iter().position(|b| b.size == size && b.align == align) {
+                Some(idx) => idx,
+                None => {
+                    self.bins.push(Bin {
+                        size: size,
+                        align: align,
+                        head: 0 as *mut u8,
+                        len: 0
+                    });
+                    self.bins.len() - 1
+                }
+            };
+
+            &mut self.bins[idx]
+        }
+
+        unsafe fn allocate(&mut self, size: usize, align: usize) -> *mut u8 {
+            {
+                let bin = self.bin(size, align);
+                let ptr = bin.head;
+                if !ptr.is_null() {
+                    bin.head = *(ptr as *mut *mut u8);
+                    bin.len -= 1;
+                    return ptr;
+                }
+            }
+
+            HEAP.allocate(size, align)
+        }
+
+        unsafe fn deallocate(&mut self, ptr: *mut u8, size: usize, align: usize) {
+            {
+                let bin = self.bin(size, align);
+                if bin.len < LIMIT {
+                    *(ptr as *mut *mut u8) = bin.head;
+                    bin.head = ptr;
+                    bin.len += 1;
+                    return;
+                }
+            }
+
+            HEAP.deallocate(ptr, size, align);
+        }
+
+        fn trim(&mut self) {
+            for bin in self.bins.iter_mut() {
+                while !bin.head.is_null() {
+                    unsafe {
+                        let ptr = bin.head;
+                        bin.head = *(ptr as *mut *mut u8);
+                        HEAP.deallocate(ptr, bin.size, bin.align);
+                    }
+                }
+                bin.len = 0;
+            }
+        }
+    }
+
+    impl Drop for Cache {
+        fn drop(&mut self) {
+            self.trim();
+        }
+    }
+
+    /**
+     * Returns whether this thread's cache can be used. Nodes that are dropped by other
+     * thread-locals' destructors may outlive it.
+     */
+    fn available() -> bool {
+        match CACHE.state() {
+            LocalKeyState::Destroyed => false,
+            _ => true
+        }
+    }
+
+    /**
+     * Allocates a node block, reusing a cached one if there is one. Panics if the heap is out of
+     * memory.
+     */
+    pub unsafe fn allocate(size: usize, align: usize) -> *mut u8 {
+        let ptr = if available() {
+            CACHE.with(|c| c.borrow_mut().allocate(size, align))
+        } else {
+            HEAP.allocate(size, align)
+        };
+
+        assert!(!ptr.is_null(), "Out of memory");
+        ptr
+    }
+
+    /**
+     * Frees a node block, keeping it for reuse if there's room.
+     */
+    pub unsafe fn deallocate(ptr: *mut u8, size: usize, align: usize) {
+        if available() {
+            CACHE.with(|c| c.borrow_mut().deallocate(ptr, size, align));
+        } else {
+            HEAP.deallocate(ptr, size, align);
+        }
+    }
+
+    /**
+     * Frees every block in this thread's cache.
+     */
+    pub fn trim() {
+        if available() {
+            CACHE.with(|c| c.borrow_mut().trim());
+        }
+    }
+
+    /**
+     * Returns how many free blocks this thread's cache holds.
+     */
+    #[cfg(test)]
+    pub fn cached() -> usize {
+        CACHE.with(|c| c.borrow().bins.iter().map(|b| b.len).fold(0, |a, b| a + b))
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod heap_only {
+    pub use super::{allocate_uncached as allocate, deallocate_uncached as deallocate};
+
+    pub fn trim() {}
+}
+
+/**
+ * Allocates a block straight from the heap, for the things that aren't worth caching, such as
+ * list sentinels. Panics if the heap is out of memory.
+ */
+pub unsafe fn allocate_uncached(size: usize, align: usize) -> *mut u8 {
+    let ptr = HEAP.allocate(size, align);
+    assert!(!ptr.is_null(), "Out of memory");
+    ptr
+}
+
+/**
+ * Frees a block from `allocate_uncached`.
+ */
+pub unsafe fn deallocate_uncached(ptr: *mut u8, size: usize, align: usize) {
+    HEAP.deallocate(ptr, size, align)
+}
+
+#[cfg(test)]
+mod counting {
+    use std::cell::Cell;
+    use std::thread::LocalKeyState;
+    use heap::{Allocator, Global};
+
+    // The calls made to the heap on this thread, as (allocations, frees)
+    thread_local!(static CALLS: Cell<(usize, usize)> = Cell::new((0, 0)));
+
+    /**
+     * The global heap, counting every call this thread makes to it.
+     */
+    #[derive(Copy, Clone)]
+    pub struct Counting;
+
+    fn count(allocs: usize, frees: usize) {
+        // The cache gives its blocks back as the thread exits, which may be after the counts
+        // are gone
+        if let LocalKeyState::Destroyed = CALLS.state() {
+            return;
+        }
+        CALLS.with(|c| {
+            let (a, f) = c.get();
+            c.set((a + allocs, f + frees));
+        });
+    }
+
+    unsafe impl Allocator for Counting {
+        unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8 {
+            count(1, 0);
+            Global.allocate(size, align)
+        }
+
+        unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize) {
+            count(0, 1);
+            Global.deallocate(ptr, size, align)
+        }
+
+        fn same_as(&self, _other: &Counting) -> bool {
+            true
+        }
+    }
+
+    /**
+     * Returns how many times this thread has allocated from and freed to the heap.
+     */
+    pub fn heap_calls() -> (usize, usize) {
+        CALLS.with(|c| c.get())
+    }
+}
+
+#[cfg(test)]
+pub use self::counting::heap_calls;
+
+#[cfg(all(test, feature = "std"))]
+pub use self::imp::cached;
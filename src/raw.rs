@@ -1,74 +1,85 @@
-use std::{mem, cmp};
+use core::{mem, cmp};
+use core::nonzero::NonZero;
 
 pub struct Raw<T: ?Sized> {
-    pub ptr: *mut T
+    // `None` is the null pointer. Unlike a null `*mut T`, it can be built in a `const fn` even
+    // when `T` is unsized, and it's still a single (possibly fat) pointer in size.
+    ptr: Option<NonZero<*mut T>>
 }
 impl<T: ?Sized> Copy for Raw<T> { }
 impl<T: ?Sized> Clone for Raw<T> { fn clone(&self) -> Raw<T> { *self } }
 
 impl<T: ?Sized> Raw<T> {
     pub fn new(ptr: *mut T) -> Raw<T> {
-        Raw {
-            ptr: ptr
+        let p = ptr as *const ();
+        if p.is_null() {
+            Raw::null()
+        } else {
+            unsafe {
+                Raw { ptr: Some(NonZero::new(ptr)) }
+            }
         }
     }
 
-    pub fn null() -> Raw<T> {
-        unsafe {
-            mem::zeroed()
+    pub const fn null() -> Raw<T> {
+        Raw {
+            ptr: None
         }
     }
 
-    pub fn as_ref<'a>(&'a self) -> Option<&'a T> {
-        if self.is_null() {
-            None
-        } else {
-            unsafe {
-                Some(mem::transmute(self.ptr))
-            }
+    /**
+     * Returns the pointer. A null `Raw` gives a null pointer with zeroed metadata.
+     */
+    pub fn ptr(&self) -> *mut T {
+        match self.ptr {
+            Some(ptr) => *ptr,
+            None => unsafe { mem::zeroed() }
         }
     }
 
-    pub fn as_mut<'a>(&'a mut self) -> Option<&'a mut T> {
-        if self.is_null() {
-            None
-        } else {
-            unsafe {
-                Some(mem::transmute(self.ptr))
-            }
+    pub fn as_ref<'a>(&'a self) -> Option<&'a T> {
+        match self.ptr {
+            Some(ptr) => unsafe { Some(&**ptr) },
+            None => None
         }
     }
 
-    pub fn take(&mut self) -> Option<Box<T>> {
-        if self.is_null() {
-            None
-        } else {
-            unsafe {
-                let p = self.ptr;
-                self.ptr = mem::zeroed();
-                Some(Box::from_raw(p))
-            }
+    pub fn as_mut<'a>(&'a mut self) -> Option<&'a mut T> {
+        match self.ptr {
+            Some(ptr) => unsafe { Some(&mut **ptr) },
+            None => None
         }
     }
 
     pub fn is_null(&self) -> bool {
-        let p = self.ptr as *const ();
-        p.is_null()
+        self.ptr.is_none()
     }
 
     pub fn xor(&self, other: &Raw<T>) -> Raw<T> {
+        // A null `Raw` has no metadata stored, so treat it as all zeroes, which leaves the other
+        // side unchanged
+        if other.is_null() {
+            return *self;
+        }
+        if self.is_null() {
+            return *other;
+        }
+
         unsafe {
+            let a = self.ptr();
+            let b = other.ptr();
+
             if is_sized::<T>() {
-                let a = self.ptr as *const () as usize;
-                let b = other.ptr as *const () as usize;
+                let a = a as *const () as usize;
+                let b = b as *const () as usize;
 
                 let res = a ^ b;
                 let res : *const *mut T = &res as *const _ as *const *mut T;
 
                 Raw::new(*res)
             } else {
-                let a : *const (usize, usize) = self as *const _ as *const (usize, usize);
-                let b : *const (usize, usize) = other as *const _ as *const (usize, usize);
+                let a : *const (usize, usize) = &a as *const _ as *const (usize, usize);
+                let b : *const (usize, usize) = &b as *const _ as *const (usize, usize);
 
                 let res = ((*a).0 ^ (*b).0, (*a).1 ^ (*b).1);
                 let res : *const *mut T = &res as *const _ as *const *mut T;
@@ -86,8 +97,8 @@ pub fn is_sized<T: ?Sized>() -> bool {
 impl<T:?Sized> cmp::PartialEq for Raw<T> {
 
     fn eq(&self, other: &Raw<T>) -> bool {
-        let p1 = self.ptr as *const ();
-        let p2 = other.ptr as *const ();
+        let p1 = self.ptr() as *const ();
+        let p2 = other.ptr() as *const ();
 
         p1 == p2
     }
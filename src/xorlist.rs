@@ -1,8 +1,18 @@
-use std::marker::{PhantomData, Unsize};
-use std::{iter, ops, mem};
-use std::boxed::into_raw;
-use std::cell::Cell;
-
+use core::marker::{PhantomData, Unsize};
+use core::{fmt, iter, ops, mem, ptr};
+use core::any::Any;
+use core::hash::{Hash, Hasher};
+use core::cmp::{self, Ordering};
+use core::cell::Cell;
+use core::sync::atomic::{self, AtomicUsize, ATOMIC_USIZE_INIT};
+use core::intrinsics::drop_in_place;
+use alloc::boxed::{Box, into_raw};
+use alloc::heap::{allocate, deallocate};
+use collections::borrow::Borrow;
+use collections::string::String;
+use collections::vec::Vec;
+
+use heap::{Allocator, Global};
 use raw::Raw;
 
 struct Node<T: ?Sized, U:?Sized=T> {
@@ -11,13 +21,211 @@ struct Node<T: ?Sized, U:?Sized=T> {
 }
 
 impl<T: ?Sized> Node<T> {
-    fn new<U: Unsize<T>>(val: U) -> Box<Node<T>> {
-        let node : Box<Node<U, T>> = box Node {
-            link: Raw::null(),
-            data: val
-        };
+    fn new<U: Unsize<T>, A: Allocator>(val: U, alloc: &A) -> Raw<Node<T>> {
+        unsafe {
+            let size = mem::size_of::<Node<U, T>>();
+            let align = mem::min_align_of::<Node<U, T>>();
 
-        return node;
+            let ptr = alloc.allocate(size, align);
+            assert!(!ptr.is_null(), "Out of memory");
+
+            let node = ptr as *mut Node<U, T>;
+            ptr::write(node, Node {
+                link: Raw::null(),
+                data: val
+            });
+
+            return Raw::new(node);
+        }
+    }
+}
+
+impl<T: ?Sized> Node<T> {
+    /**
+     * Moves a boxed value into a new node, freeing the original allocation.
+     */
+    fn from_box<A: Allocator>(val: Box<T>, alloc: &A) -> Raw<Node<T>> {
+        unsafe {
+            let val = into_raw(val);
+
+            let val_size = mem::size_of_val(&*val);
+            let val_align = mem::min_align_of_val(&*val);
+
+            // The data comes after the link, at an offset aligned for the value
+            let link_size = mem::size_of::<Raw<Node<T>>>();
+            let link_align = mem::min_align_of::<Raw<Node<T>>>();
+            let offset = round_up(link_size, val_align);
+            let align = cmp::max(link_align, val_align);
+            let size = round_up(offset + val_size, align);
+
+            let ptr = alloc.allocate(size, align);
+            assert!(!ptr.is_null(), "Out of memory");
+
+            // Build a node pointer with the same metadata (length or vtable) as the value
+            let mut node : *mut Node<T> = mem::transmute_copy(&val);
+            *(&mut node as *mut *mut Node<T> as *mut *mut u8) = ptr;
+
+            debug_assert_eq!(mem::size_of_val(&*node), size);
+            debug_assert_eq!(&(*node).data as *const T as *const u8 as usize - ptr as usize,
+                             offset);
+
+            ptr::write(&mut (*node).link, Raw::null());
+            ptr::copy_nonoverlapping(val as *const u8, &mut (*node).data as *mut T as *mut u8,
+                                     val_size);
+
+            // The value has been moved, so free the box without dropping it
+            if val_size != 0 {
+                deallocate(val as *mut u8, val_size, val_align);
+            }
+
+            Raw::new(node)
+        }
+    }
+
+    /**
+     * Drops the value in the node and frees it. The node must not be linked into a list. The
+     * node is freed even if dropping the value panics.
+     */
+    unsafe fn free<A: Allocator>(node: Raw<Node<T>>, alloc: &A) {
+        struct Dealloc<'a, T: ?Sized + 'a, A: Allocator + 'a>(Raw<Node<T>>, &'a A);
+
+        impl<'a, T: ?Sized, A: Allocator> Drop for Dealloc<'a, T, A> {
+            fn drop(&mut self) {
+                unsafe { Node::dealloc(self.0, self.1); }
+            }
+        }
+
+        let _guard = Dealloc(node, alloc);
+        drop_in_place(&mut (*node.ptr()).data);
+    }
+
+    /**
+     * Frees the node without dropping its value, which must already have been moved out.
+     */
+    unsafe fn dealloc<A: Allocator>(node: Raw<Node<T>>, alloc: &A) {
+        let size = mem::size_of_val(&*node.ptr());
+        let align = mem::min_align_of_val(&*node.ptr());
+        alloc.deallocate(node.ptr() as *mut u8, size, align);
+    }
+}
+
+fn round_up(n: usize, align: usize) -> usize {
+    (n + align - 1) & !(align - 1)
+}
+
+/**
+ * A free list of node allocations kept by a list for reuse. Freed nodes are chained together
+ * through their first word, so the pool itself never allocates. Since nodes for dynamically-sized
+ * elements can have different layouts, the pool only holds nodes with one layout at a time: the
+ * layout of the first node put into it while it's empty. Nodes with other layouts are freed.
+ *
+ * The pool is only used through `&mut` access to the list it belongs to, even though it uses
+ * `Cell`s, so it doesn't affect the list being `Sync`.
+ */
+struct Pool {
+    free: Cell<*mut u8>,
+    len: Cell<usize>,
+    capacity: usize,
+    size: Cell<usize>,
+    align: Cell<usize>
+}
+
+impl Pool {
+    const fn new(capacity: usize) -> Pool {
+        Pool {
+            free: Cell::new(ptr::null_mut()),
+            len: Cell::new(0),
+            capacity: capacity,
+            size: Cell::new(0),
+            align: Cell::new(0)
+        }
+    }
+
+    /**
+     * Frees all the nodes in the pool.
+     */
+    fn clear<A: Allocator>(&self, alloc: &A) {
+        let mut ptr = self.free.get();
+        while !ptr.is_null() {
+            unsafe {
+                let next = *(ptr as *mut *mut u8);
+                alloc.deallocate(ptr, self.size.get(), self.align.get());
+                ptr = next;
+            }
+        }
+
+        self.free.set(ptr::null_mut());
+        self.len.set(0);
+    }
+}
+
+/**
+ * The allocator a list uses for its own nodes, which takes nodes from the list's pool before
+ * going to the real allocator.
+ */
+struct Recycler<'a, A: Allocator + 'a> {
+    pool: &'a Pool,
+    alloc: &'a A
+}
+
+impl<'a, A: Allocator> Clone for Recycler<'a, A> {
+    fn clone(&self) -> Recycler<'a, A> {
+        Recycler {
+            pool: self.pool,
+            alloc: self.alloc
+        }
+    }
+}
+
+unsafe impl<'a, A: Allocator> Allocator for Recycler<'a, A> {
+    unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8 {
+        let pool = self.pool;
+        let ptr = pool.free.get();
+        if !ptr.is_null() && size == pool.size.get() && align == pool.align.get() {
+            pool.free.set(*(ptr as *mut *mut u8));
+            pool.len.set(pool.len.get() - 1);
+            ptr
+        } else {
+            self.alloc.allocate(size, align)
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize) {
+        let pool = self.pool;
+        if pool.len.get() == 0 && pool.capacity > 0 {
+            pool.size.set(size);
+            pool.align.set(align);
+        }
+
+        if pool.len.get() < pool.capacity && size == pool.size.get() && align == pool.align.get() {
+            // Nodes always start with a link, so there's room for the pointer
+            *(ptr as *mut *mut u8) = pool.free.get();
+            pool.free.set(ptr);
+            pool.len.set(pool.len.get() + 1);
+        } else {
+            self.alloc.deallocate(ptr, size, align);
+        }
+    }
+
+    fn same_as(&self, other: &Recycler<'a, A>) -> bool {
+        self.alloc.same_as(other.alloc)
+    }
+}
+
+impl<T> Node<T> {
+    fn new_sized<A: Allocator>(val: T, alloc: &A) -> Raw<Node<T>> {
+        unsafe {
+            let ptr = alloc.allocate(mem::size_of::<Node<T>>(), mem::min_align_of::<Node<T>>());
+            assert!(!ptr.is_null(), "Out of memory");
+
+            let node = ptr as *mut Node<T>;
+            ptr::write(node, Node {
+                link: Raw::null(),
+                data: val
+            });
+
+            return Raw::new(node);
+        }
     }
 }
 
@@ -31,21 +239,150 @@ impl<T: ?Sized> Node<T> {
  * This list is capable of holding dynamically-sized types. Each node is a seperate allocation
  * containing the data and a field. Due to XOR compression, each node has only a single pointer of
  * overhead, for a dynamically-sized type, this is two-words.
+ *
+ * Nodes are allocated with `A`, which is the global heap by default. See `new_in`.
+ *
+ * A list is `Send` and `Sync` when its elements are, like a `Vec`. A list of elements that can't
+ * be sent to another thread can't be sent either:
+ *
+ * ```compile_fail
+ * # use std::rc::Rc;
+ * # use dynalist::XorList;
+ * fn assert_send<T: Send>() {}
+ * assert_send::<XorList<Rc<i32>>>();
+ * ```
+ *
+ * And a list of elements that can't be shared between threads can't be shared either:
+ *
+ * ```compile_fail
+ * # use std::cell::Cell;
+ * # use dynalist::XorList;
+ * fn assert_sync<T: Sync>() {}
+ * assert_sync::<XorList<Cell<i32>>>();
+ * ```
  */
-pub struct XorList<T: ?Sized> {
+pub struct XorList<T: ?Sized, A: Allocator = Global> {
     head: Raw<Node<T>>,
-    tail: Raw<Node<T>>
+    tail: Raw<Node<T>>,
+    len: usize,
+    alloc: A,
+    pool: Pool,
+    // Identifies the list for saved cursor positions, and changes whenever nodes leave the list.
+    // The id is 0 until it's first needed, so that `new` can be a `const fn`.
+    id: AtomicUsize,
+    gen: usize
 }
 
+static NEXT_LIST_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+// The list uniquely owns all of its nodes, and the raw pointers between them are never shared
+// with anything outside the list (every access goes through `&self` or `&mut self`). So sending
+// or sharing the list is the same as sending or sharing the `T`s it owns, like `Vec<T>`.
+unsafe impl<T: ?Sized + Send, A: Allocator + Send> Send for XorList<T, A> {}
+unsafe impl<T: ?Sized + Sync, A: Allocator + Sync> Sync for XorList<T, A> {}
+
 impl<T: ?Sized> XorList<T> {
     /**
-     * Constructs a new empty list
+     * Constructs a new empty list. This doesn't allocate, and can be used to initialise a
+     * `static`.
+     */
+    pub const fn new() -> XorList<T> {
+        XorList {
+            head: Raw::null(),
+            tail: Raw::null(),
+            len: 0,
+            alloc: Global,
+            pool: Pool::new(0),
+            id: AtomicUsize::new(0),
+            gen: 0
+        }
+    }
+
+    /**
+     * Creates a list from an iterator of boxed elements. This is the boxed counterpart to
+     * `collect`, which can't be used here since `Box<T>` values would be unsized into the list
+     * rather than unboxed.
+     */
+    pub fn from_boxes<I>(iter: I) -> XorList<T> where I: IntoIterator<Item=Box<T>> {
+        let mut list = XorList::new();
+        list.extend_boxed(iter);
+        return list;
+    }
+
+    /**
+     * Constructs a new empty list that keeps up to `capacity` freed nodes for reuse. See
+     * `with_recycling_in`.
+     */
+    pub fn with_recycling(capacity: usize) -> XorList<T> {
+        XorList::with_recycling_in(capacity, Global)
+    }
+}
+
+impl<T: ?Sized, A: Allocator> XorList<T, A> {
+    /**
+     * Constructs a new empty list that allocates its nodes with `alloc`.
+     *
+     * Elements and nodes can only be moved between lists with the same allocator, since the list
+     * they end up in has to be able to free them. Methods that take elements or nodes from
+     * another list, like `append` and `push_back_elem`, panic if its allocator isn't the same as
+     * this one, as told by `Allocator::same_as`.
+     */
+    pub fn new_in(alloc: A) -> XorList<T, A> {
+        XorList::with_recycling_in(0, alloc)
+    }
+
+    /**
+     * Constructs a new empty list that allocates its nodes with `alloc`, and keeps up to
+     * `capacity` of the nodes it frees so they can be reused by later pushes rather than going
+     * back to the allocator.
+     *
+     * Nodes are recycled when the list frees them itself: by `pop_front_value`, `pop_back_value`,
+     * `clear`, `retain`, `truncate` and so on. Elements that are removed as `Elem`s take their
+     * nodes with them. Only nodes of one size and alignment are kept at a time, which for lists of
+     * dynamically-sized elements is the layout of the first node freed into an empty pool.
      */
-    pub fn new() -> XorList<T> {
+    pub fn with_recycling_in(capacity: usize, alloc: A) -> XorList<T, A> {
         XorList {
             head: Raw::null(),
-            tail: Raw::null()
+            tail: Raw::null(),
+            len: 0,
+            alloc: alloc,
+            pool: Pool::new(capacity),
+            id: AtomicUsize::new(0),
+            gen: 0
+        }
+    }
+
+    /**
+     * Returns the list's id, giving it one if it doesn't have one yet.
+     */
+    fn id(&self) -> usize {
+        let id = self.id.load(atomic::Ordering::Relaxed);
+        if id != 0 {
+            return id;
         }
+
+        // Another thread may be doing the same through a shared reference, so only the first id
+        // stored sticks
+        let new_id = NEXT_LIST_ID.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+        match self.id.compare_and_swap(0, new_id, atomic::Ordering::Relaxed) {
+            0 => new_id,
+            id => id
+        }
+    }
+
+    /**
+     * Frees any nodes the list is holding on to for reuse.
+     */
+    pub fn shrink_pool(&mut self) {
+        self.pool.clear(&self.alloc);
+    }
+
+    /**
+     * Returns a reference to the allocator used for the list's nodes.
+     */
+    pub fn allocator(&self) -> &A {
+        &self.alloc
     }
 
     /**
@@ -53,86 +390,175 @@ impl<T: ?Sized> XorList<T> {
      * list. In general, this means that if `T` is a trait, `U` must implement that trait.
      */
     pub fn push_back<U: Unsize<T>>(&mut self, val: U) {
-        let mut node = Node::new(val);
+        let node = Node::new(val, &self.node_alloc());
+        self.push_back_node(node);
+    }
 
-        if self.head.is_null() {
-            let node_ptr = Raw::new(into_raw(node));
-            self.head = node_ptr;
-        } else if self.tail.is_null() {
-            node.link = self.head;
-            let node_ptr = Raw::new(into_raw(node));
-            self.tail = node_ptr;
-            let head = self.head.as_mut().expect("There should be a head!");
-            head.link = self.tail;
-        } else {
-            node.link = self.tail;
+    /**
+     * Pushes a new element to the beginning of the list.
+     */
+    pub fn push_front<U: Unsize<T>>(&mut self, val: U) {
+        let node = Node::new(val, &self.node_alloc());
+        self.push_front_node(node);
+    }
+
+    /**
+     * Inserts an element at position `index`, shifting all the elements after it towards the
+     * back. Inserting at `len` is the same as `push_back`.
+     *
+     * Panics if `index > len`.
+     */
+    pub fn insert<U: Unsize<T>>(&mut self, index: usize, val: U) {
+        assert!(index <= self.len, "Insertion index out of bounds");
 
-            let node_ptr = Raw::new(into_raw(node));
+        let (prev, curr) = self.nodes_at(index);
+        let node = Node::new(val, &self.node_alloc());
+        self.link_node(prev, curr, node);
+    }
 
-            {
-                let tail = self.tail.as_mut().expect("There should be a tail!");
-                tail.link = tail.link.xor(&node_ptr);
-            }
-            self.tail = node_ptr;
+    /**
+     * Removes and returns the element at position `index`.
+     *
+     * Panics if `index >= len`.
+     */
+    pub fn remove_at(&mut self, index: usize) -> Elem<T, A> {
+        match self.try_remove_at(index) {
+            Some(elem) => elem,
+            None => panic!("Removal index out of bounds")
         }
     }
 
     /**
-     * Pushes a new element to the beginning of the list.
+     * Removes and returns the element at position `index`, or returns `None` if `index` is out
+     * of bounds.
      */
-    pub fn push_front<U: Unsize<T>>(&mut self, val: U) {
-        let mut node = Node::new(val);
-        if self.head.is_null() {
-            let node_ptr = Raw::new(into_raw(node));
-            self.head = node_ptr;
-        } else if self.tail.is_null() {
-            let mut old_head = self.head;
-            self.tail = old_head;
-            node.link = self.tail;
-            let node_ptr = Raw::new(into_raw(node));
-            self.head = node_ptr;
-            let old_head = old_head.as_mut().unwrap();
-            old_head.link = self.head;
-        } else {
-            node.link = self.head;
-            let node_ptr = Raw::new(into_raw(node));
+    pub fn try_remove_at(&mut self, index: usize) -> Option<Elem<T, A>> {
+        if index >= self.len {
+            return None;
+        }
 
-            {
-                let head = self.head.as_mut().unwrap();
-                head.link = head.link.xor(&node_ptr);
-            }
+        let (prev, curr) = self.nodes_at(index);
+        let node = self.unlink_node(prev, curr);
+        Some(self.elem(node))
+    }
 
-            self.head = node_ptr;
+    /**
+     * Pushes an already-boxed element to the end of the list. The value is moved out of the box
+     * into a new node, so this can be used to add trait objects and slices whose concrete type
+     * isn't known.
+     */
+    pub fn push_back_boxed(&mut self, val: Box<T>) {
+        let node = Node::from_box(val, &self.node_alloc());
+        self.push_back_node(node);
+    }
+
+    /**
+     * Pushes an already-boxed element to the beginning of the list. See `push_back_boxed`.
+     */
+    pub fn push_front_boxed(&mut self, val: Box<T>) {
+        let node = Node::from_box(val, &self.node_alloc());
+        self.push_front_node(node);
+    }
+
+    /**
+     * Pushes an element that was removed from a list back onto the end of this list. The node
+     * the element is stored in is reused, so this doesn't allocate.
+     *
+     * Panics if the element came from a list with a different allocator, which couldn't free
+     * its node.
+     */
+    pub fn push_back_elem(&mut self, elem: Elem<T, A>) {
+        self.check_allocator(&elem.alloc);
+        self.push_back_node(elem.into_node());
+    }
+
+    /**
+     * Pushes an element that was removed from a list back onto the beginning of this list. See
+     * `push_back_elem`.
+     */
+    pub fn push_front_elem(&mut self, elem: Elem<T, A>) {
+        self.check_allocator(&elem.alloc);
+        self.push_front_node(elem.into_node());
+    }
+
+    /**
+     * Pushes each boxed element from the iterator onto the end of the list, as `push_back_boxed`
+     * does.
+     */
+    pub fn extend_boxed<I>(&mut self, iter: I) where I: IntoIterator<Item=Box<T>> {
+        for el in iter {
+            self.push_back_boxed(el);
+        }
+    }
+
+    /**
+     * Pushes each element from the iterator onto the end of the list, reusing the nodes they're
+     * stored in. `a.extend_elems(b.drain())` moves all of `b` into `a` without allocating.
+     */
+    pub fn extend_elems<I>(&mut self, iter: I) where I: IntoIterator<Item=Elem<T, A>> {
+        for el in iter {
+            self.push_back_elem(el);
+        }
+    }
+
+    /**
+     * Removes the element at the end of the list and returns its value, freeing the node it was
+     * stored in.
+     */
+    pub fn pop_back_value(&mut self) -> Option<T> where T: Sized {
+        match self.pop_back() {
+            Some(elem) => Some(self.recycle_value(elem)),
+            None => None
+        }
+    }
+
+    /**
+     * Removes the element at the beginning of the list and returns its value, freeing the node
+     * it was stored in.
+     */
+    pub fn pop_front_value(&mut self) -> Option<T> where T: Sized {
+        match self.pop_front() {
+            Some(elem) => Some(self.recycle_value(elem)),
+            None => None
         }
     }
 
     /**
      * Removes and returns the element at the end of the list.
      */
-    pub fn pop_back(&mut self) -> Option<Elem<T>> {
+    pub fn pop_back(&mut self) -> Option<Elem<T, A>> {
         if self.head.is_null() {
             None
         } else if self.tail.is_null() {
-            self.head.take().map(|n| Elem { __node: n })
+            self.len -= 1;
+            self.invalidate_positions();
+            let node = mem::replace(&mut self.head, Raw::null());
+            Some(self.elem(node))
         } else {
+            self.len -= 1;
+            self.invalidate_positions();
             let head_link = self.head.as_ref().unwrap().link;
             let tail_link = self.tail.as_ref().unwrap().link;
 
             if head_link == self.tail && tail_link == self.head {
-                let node = self.tail.take();
+                let node = mem::replace(&mut self.tail, Raw::null());
 
-                let head = self.head.as_mut().unwrap();
-                head.link = Raw::null();
+                {
+                    let head = self.head.as_mut().unwrap();
+                    head.link = Raw::null();
+                }
 
-                node.map(|n| Elem { __node: n })
+                Some(self.elem(node))
             } else {
-                let mut node = self.tail;
+                let node = self.tail;
                 self.tail = node.as_ref().unwrap().link;
 
-                let tail = self.tail.as_mut().unwrap();
-                tail.link = tail.link.xor(&node);
+                {
+                    let tail = self.tail.as_mut().unwrap();
+                    tail.link = tail.link.xor(&node);
+                }
 
-                node.take().map(|n| Elem { __node: n })
+                Some(self.elem(node))
             }
         }
 
@@ -141,757 +567,5703 @@ impl<T: ?Sized> XorList<T> {
     /**
      * Removes and returns the element at the end of the list.
      */
-    pub fn pop_front(&mut self) -> Option<Elem<T>> {
+    pub fn pop_front(&mut self) -> Option<Elem<T, A>> {
         if self.head.is_null() {
             None
         } else if self.tail.is_null() {
-            self.head.take().map(|n| Elem { __node: n })
+            self.len -= 1;
+            self.invalidate_positions();
+            let node = mem::replace(&mut self.head, Raw::null());
+            Some(self.elem(node))
         } else {
+            self.len -= 1;
+            self.invalidate_positions();
             let head_link = self.head.as_ref().unwrap().link;
             let tail_link = self.tail.as_ref().unwrap().link;
 
             if head_link == self.tail && tail_link == self.head {
-                let mut node = self.head;
+                let node = self.head;
                 self.head = self.tail;
                 self.tail = Raw::null();
 
-                let head = self.head.as_mut().unwrap();
-                head.link = Raw::null();
+                {
+                    let head = self.head.as_mut().unwrap();
+                    head.link = Raw::null();
+                }
 
-                node.take().map(|n| Elem { __node: n })
+                Some(self.elem(node))
             } else {
-                let mut node = self.head;
+                let node = self.head;
                 self.head = node.as_ref().unwrap().link;
 
-                let head = self.head.as_mut().unwrap();
-                head.link = head.link.xor(&node);
+                {
+                    let head = self.head.as_mut().unwrap();
+                    head.link = head.link.xor(&node);
+                }
 
-                node.take().map(|n| Elem { __node: n })
+                Some(self.elem(node))
             }
         }
     }
 
-    pub fn iter<'a>(&'a self) -> Iter<'a, T> {
-        Iter {
-            prev: Raw::null(),
-            curr: self.head,
-            phantom: PhantomData
-        }
+    /**
+     * Returns a reference to the element at the beginning of the list.
+     */
+    pub fn front<'a>(&'a self) -> Option<&'a T> {
+        self.head.as_ref().map(|node| &node.data)
     }
 
-    pub fn iter_mut<'a>(&'a mut self) -> IterMut<'a, T> {
-        IterMut {
-            prev: Raw::null(),
-            curr: self.head,
-            phantom: PhantomData
+    /**
+     * Returns a reference to the element at the end of the list.
+     */
+    pub fn back<'a>(&'a self) -> Option<&'a T> {
+        // Single-element lists only have a head
+        if self.tail.is_null() {
+            self.front()
+        } else {
+            self.tail.as_ref().map(|node| &node.data)
         }
     }
 
     /**
-     * Returns a cursor for this list that starts at the beginning of the list.
-     *
-     * See the documentation for `Cursor` for more details.
+     * Returns a mutable reference to the element at the beginning of the list.
      */
-    pub fn cursor<'a>(&'a mut self) -> Cursor<'a, T> {
-        Cursor {
-            prev: Cell::new(Raw::null()),
-            curr: Cell::new(self.head),
-            list: self,
-            phantom: PhantomData
-        }
+    pub fn front_mut<'a>(&'a mut self) -> Option<&'a mut T> {
+        self.head.as_mut().map(|node| &mut node.data)
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.head.is_null()
+    /**
+     * Returns a mutable reference to the element at the end of the list.
+     */
+    pub fn back_mut<'a>(&'a mut self) -> Option<&'a mut T> {
+        // Single-element lists only have a head
+        if self.tail.is_null() {
+            self.front_mut()
+        } else {
+            self.tail.as_mut().map(|node| &mut node.data)
+        }
     }
 
     /**
-     * Removes all the elements from the list.
+     * Returns a reference to the element at position `index`, or `None` if it is out of bounds.
+     * The element is found by walking from whichever end of the list is closer.
      */
-    pub fn clear(&mut self) {
-        while let Some(_) = self.pop_back() { }
+    pub fn get<'a>(&'a self, index: usize) -> Option<&'a T> {
+        if index >= self.len {
+            return None;
+        }
+
+        let (_, curr) = self.nodes_at(index);
+        curr.as_ref().map(|node| {
+            unsafe {
+                mem::transmute(&node.data)
+            }
+        })
     }
-}
 
-impl<T: ?Sized> Drop for XorList<T> {
-    fn drop(&mut self) {
-        self.clear();
-    }
-}
-
-pub struct Iter<'a, T: ?Sized + 'a> {
-    prev: Raw<Node<T>>,
-    curr: Raw<Node<T>>,
-    phantom: PhantomData<&'a XorList<T>>
-}
-
-impl<'a, T:?Sized> Iterator for Iter<'a, T> {
-    type Item = &'a T;
-
-    fn next(&mut self) -> Option<&'a T> {
-        let prev = self.prev;
-        let curr = self.curr;
-        self.prev = curr;
+    /**
+     * Returns a mutable reference to the element at position `index`, or `None` if it is out of
+     * bounds.
+     */
+    pub fn get_mut<'a>(&'a mut self, index: usize) -> Option<&'a mut T> {
+        if index >= self.len {
+            return None;
+        }
 
-        if let Some(node) = curr.as_ref() {
-            let next = prev.xor(&node.link);
-            self.curr = next;
+        let (_, mut curr) = self.nodes_at(index);
+        curr.as_mut().map(|node| {
             unsafe {
-                Some(mem::transmute(&node.data))
+                mem::transmute(&mut node.data)
             }
-        } else {
-            None
-        }
+        })
     }
-}
-
-pub struct IterMut<'a, T: ?Sized + 'a> {
-    prev: Raw<Node<T>>,
-    curr: Raw<Node<T>>,
-    phantom: PhantomData<&'a mut XorList<T>>
-}
-
-impl<'a, T:?Sized> Iterator for IterMut<'a, T> {
-    type Item = &'a mut T;
 
-    fn next(&mut self) -> Option<&'a mut T> {
-        let prev = self.prev;
-        let mut curr = self.curr;
-        self.prev = curr;
+    pub fn iter<'a>(&'a self) -> Iter<'a, T> {
+        Iter {
+            prev: Raw::null(),
+            curr: self.head,
+            phantom: PhantomData
+        }
+    }
 
-        if let Some(node) = curr.as_mut() {
-            let next = prev.xor(&node.link);
-            self.curr = next;
-            unsafe {
-                Some(mem::transmute(&mut node.data))
-            }
-        } else {
-            None
+    pub fn iter_mut<'a>(&'a mut self) -> IterMut<'a, T> {
+        IterMut {
+            prev: Raw::null(),
+            curr: self.head,
+            phantom: PhantomData
         }
     }
-}
 
-pub struct IntoIter<T: ?Sized> {
-    list: XorList<T>
-}
+    /**
+     * Returns an iterator that removes the elements from the list, front to back. Any elements
+     * that haven't been yielded when the iterator is dropped are removed too, so the list is
+     * always left empty.
+     */
+    pub fn drain<'a>(&'a mut self) -> Drain<'a, T, A> {
+        Drain {
+            list: self
+        }
+    }
 
-impl<T: ?Sized> Iterator for IntoIter<T> {
-    type Item = Elem<T>;
+    /**
+     * Returns an iterator that removes and yields the elements for which the predicate returns
+     * `true`. Elements are only removed as they are yielded, so if the iterator is dropped early
+     * the remaining elements stay in the list.
+     */
+    pub fn extract_if<'a, F>(&'a mut self, f: F) -> ExtractIf<'a, T, F, A>
+            where F: FnMut(&mut T) -> bool {
+        ExtractIf {
+            prev: Raw::null(),
+            curr: self.head,
+            list: self,
+            pred: f
+        }
+    }
 
-    fn next(&mut self) -> Option<Elem<T>> {
-        self.list.pop_front()
+    /**
+     * Returns a cursor for this list that starts at the beginning of the list.
+     *
+     * See the documentation for `Cursor` for more details.
+     */
+    pub fn cursor<'a>(&'a mut self) -> Cursor<'a, T, A> {
+        Cursor {
+            prev: Cell::new(Raw::null()),
+            curr: Cell::new(self.head),
+            index: Cell::new(0),
+            list: self,
+            phantom: PhantomData
+        }
     }
-}
 
-impl<T: ?Sized> DoubleEndedIterator for IntoIter<T> {
-    fn next_back(&mut self) -> Option<Elem<T>> {
-        self.list.pop_back()
+    /**
+     * Returns a cursor for this list that starts before the element at `index`. An index past the
+     * end of the list puts the cursor at the end. See `Cursor::seek`.
+     */
+    pub fn cursor_at<'a>(&'a mut self, index: usize) -> Cursor<'a, T, A> {
+        let cursor = self.cursor();
+        cursor.seek(index);
+        cursor
     }
-}
 
-/**
- * A "Cursor" into a list.
- *
- * A `Cursor` is a structure representing a position between two elements in the list. It acts as
- * if there are special sentinel values at either end of the list so it can be placed after the
- * tail of the list or before the head of the list.
- *
- * `Cursor` allows you to traverse the list, insert and remove elements at arbitrary positions in
- * the list, insert other XorLists and split the list at the cursor position.
- */
-pub struct Cursor<'a, T: ?Sized + 'a> {
-    prev: Cell<Raw<Node<T>>>,
-    curr: Cell<Raw<Node<T>>>,
-    list: *mut XorList<T>,
-    phantom: PhantomData<&'a mut XorList<T>>
-}
+    /**
+     * Returns a cursor at a position saved with `Cursor::save`, or `None` if the position is no
+     * longer valid.
+     *
+     * A position is invalid if it was saved from a different list, if any element has been
+     * removed from the list since it was saved, or if an element has been inserted at the
+     * position itself. Inserting elements elsewhere in the list doesn't affect it.
+     *
+     * This walks the list to check the position, so it takes linear time.
+     */
+    pub fn cursor_at_pos<'a>(&'a mut self, pos: CursorPos<T>) -> Option<Cursor<'a, T, A>> {
+        if pos.id != self.id() || pos.gen != self.gen {
+            return None;
+        }
 
-impl<'a, T: ?Sized> Cursor<'a, T> {
+        // No nodes have left the list since the position was saved, so both of its nodes are
+        // still here, but they may not be next to each other any more
+        let mut prev = Raw::null();
+        let mut curr = self.head;
+        let mut index = 0;
+        loop {
+            if prev == pos.prev && curr == pos.curr {
+                break;
+            }
 
-    #[inline]
-    pub fn at_start(&self) -> bool {
-        self.prev.get().is_null()
+            let next = match curr.as_ref() {
+                Some(node) => prev.xor(&node.link),
+                None => return None
+            };
+            prev = curr;
+            curr = next;
+            index += 1;
+        }
+
+        Some(Cursor {
+            prev: Cell::new(prev),
+            curr: Cell::new(curr),
+            index: Cell::new(index),
+            list: self,
+            phantom: PhantomData
+        })
     }
 
-    #[inline]
-    pub fn at_end(&self) -> bool {
-        self.curr.get().is_null()
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     /**
-     * Move to the cursor forwards one position and return a reference to the element that was
-     * skipped over.
+     * Returns the number of elements in the list.
      */
-    pub fn next<'b>(&'b self) -> Option<&'b T> {
-        let prev = self.prev.get();
-        let curr = self.curr.get();
-        self.prev.set(curr);
-
-        if let Some(node) = curr.as_ref() {
-            let next = prev.xor(&node.link);
-            self.curr.set(next);
-            unsafe {
-                Some(mem::transmute(&node.data))
-            }
-        } else {
-            None
-        }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
     }
 
     /**
-     * Move to the cursor backwards one position and return a reference to the element that was
-     * skipped over.
+     * Checks that the list's links are consistent, returning `false` if they've been corrupted.
+     * This walks the whole list in both directions, checking that each walk ends at the other end
+     * of the list after `len` elements, and that a single-element list has only a head with a
+     * null link.
+     *
+     * This is meant for debugging; it's used in debug builds to check the list after cursor
+     * operations that relink nodes. A corrupted link can point anywhere, so it can only catch
+     * corruption that still leaves the links pointing at nodes in the list.
      */
-    pub fn prev<'b>(&'b self) -> Option<&'b T> {
-        let prev = self.prev.get();
-        let curr = self.curr.get();
-        self.curr.set(prev);
+    pub fn is_consistent(&self) -> bool {
+        if self.head.is_null() {
+            return self.tail.is_null() && self.len == 0;
+        }
 
-        if let Some(node) = prev.as_ref() {
-            let prev = curr.xor(&node.link);
-            self.prev.set(prev);
-            unsafe {
-                Some(mem::transmute(&node.data))
-            }
-        } else {
-            None
+        if self.tail.is_null() {
+            return self.len == 1 && self.head.as_ref().unwrap().link.is_null();
+        }
+
+        if self.head == self.tail || self.len < 2 {
+            return false;
         }
+
+        self.walk_from(self.head) == Some(self.tail) && self.walk_from(self.tail) == Some(self.head)
     }
 
     /**
-     * Skip forward `n` positions, or until the end of the list, whichever
-     * is sooner.
+     * Returns a description of the list's nodes and links, for debugging. The first line gives
+     * the list's head, tail and length, followed by a line for each node giving its address,
+     * its stored link and the previous and next addresses derived from it:
+     *
+     * ```text
+     * XorList head=0x1000 tail=0x1040 len=3
+     * [0] 0x1000 link=0x1020 prev=0x0 next=0x1020 (head)
+     * [1] 0x1020 link=0x1040 prev=0x1000 next=0x1040
+     * [2] 0x1040 link=0x1020 prev=0x1020 next=0x0 (tail)
+     * ```
+     *
+     * The walk stops after `len` nodes, so it ends even if the links form a cycle, and a final
+     * line notes if the links end early or carry on past the end. Links that point outside the
+     * list can't be detected, so following one is still undefined behaviour.
      */
-    pub fn skip_forwards(&self, n: usize) {
-        if n == 0 { return; }
-        let mut i = 0;
-        while let Some(_) = self.next() {
-            i += 1;
-            if i >= n { break; }
+    pub fn dump_links(&self) -> String {
+        use core::fmt::Write;
+
+        let mut out = String::new();
+        let _ = writeln!(out, "XorList head={:p} tail={:p} len={}",
+                         self.head.ptr() as *const u8, self.tail.ptr() as *const u8, self.len);
+
+        let (visited, next) = self.walk_links(|i, prev, curr, next| {
+            let link = curr.as_ref().unwrap().link;
+            let _ = write!(out, "[{}] {:p} link={:p} prev={:p} next={:p}", i,
+                           curr.ptr() as *const u8, link.ptr() as *const u8,
+                           prev.ptr() as *const u8, next.ptr() as *const u8);
+            if curr == self.head {
+                out.push_str(" (head)");
+            }
+            if curr == self.tail {
+                out.push_str(" (tail)");
+            }
+            out.push('\n');
+        });
+
+        if visited < self.len {
+            let _ = writeln!(out, "links end after {} of {} nodes", visited, self.len);
+        } else if !next.is_null() {
+            let _ = writeln!(out, "links continue past {} nodes to {:p}", visited,
+                             next.ptr() as *const u8);
         }
-    }
 
+        out
+    }
 
     /**
-     * Skip backward `n` positions, or until the start of the list, whichever
-     * is sooner.
+     * Returns the same information as `dump_links` as a Graphviz graph, with an edge from each
+     * node to the next one derived from its link.
      */
-    pub fn skip_backwards(&self, n: usize) {
-        if n == 0 { return; }
-        let mut i = 0;
-        while let Some(_) = self.next() {
-            i += 1;
-            if i >= n { break; }
+    pub fn dump_dot(&self) -> String {
+        use core::fmt::Write;
+
+        let mut out = String::new();
+        out.push_str("digraph XorList {\n");
+        let _ = writeln!(out, "    label=\"len={}\";", self.len);
+
+        let (visited, next) = self.walk_links(|i, _, curr, next| {
+            let link = curr.as_ref().unwrap().link;
+            let _ = writeln!(out, "    \"{:p}\" [label=\"[{}] {:p}\\nlink={:p}\"];",
+                             curr.ptr() as *const u8, i, curr.ptr() as *const u8,
+                             link.ptr() as *const u8);
+            if !next.is_null() {
+                let _ = writeln!(out, "    \"{:p}\" -> \"{:p}\";",
+                                 curr.ptr() as *const u8, next.ptr() as *const u8);
+            }
+        });
+
+        if !self.head.is_null() {
+            let _ = writeln!(out, "    head [shape=plaintext];\n    head -> \"{:p}\";",
+                             self.head.ptr() as *const u8);
+        }
+        if !self.tail.is_null() {
+            let _ = writeln!(out, "    tail [shape=plaintext];\n    tail -> \"{:p}\";",
+                             self.tail.ptr() as *const u8);
+        }
+        if visited == self.len && !next.is_null() {
+            let _ = writeln!(out, "    \"{:p}\" [color=red];", next.ptr() as *const u8);
         }
+
+        out.push_str("}\n");
+        out
     }
 
     /**
-     * Move the cursor to the beginning of the list.
+     * Returns `true` if the list contains an element equal to `x`.
      */
-    pub fn seek_to_start(&self) {
-        unsafe {
-            self.prev.set(Raw::null());
-            self.curr.set((*self.list).head);
-        }
+    pub fn contains(&self, x: &T) -> bool where T: PartialEq {
+        self.iter().any(|el| el == x)
     }
 
     /**
-     * Move the cursor to the end of the list.
+     * Returns `true` if any element in the list matches the given predicate. This is useful for
+     * lists of trait objects, which often can't be compared with `contains`.
      */
-    pub fn seek_to_end(&self) {
-        unsafe {
-            self.prev.set((*self.list).tail);
-            self.curr.set(Raw::null());
-        }
+    pub fn contains_by<F: FnMut(&T) -> bool>(&self, pred: F) -> bool {
+        self.iter().any(pred)
     }
 
     /**
-     * Returns an immutable reference to element after the cursor.
+     * Retains only the elements for which the predicate returns `true`, removing and dropping
+     * the rest. The elements are visited in order.
      */
-    pub fn peek<'b>(&'b self) -> Option<&'b T> {
-        self.curr.get().as_ref().map(|node| {
-            unsafe {
-                mem::transmute(&node.data)
-            }
-        })
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.retain_mut(|el| f(el))
     }
 
     /**
-     * Returns a mutable reference to the element after the cursor.
+     * Like `retain`, but the predicate is given a mutable reference to each element.
      */
-    pub fn peek_mut<'b>(&'b mut self) -> Option<&'b mut T> {
-        self.curr.get().as_mut().map(|node| {
-            unsafe {
-                mem::transmute(&mut node.data)
+    pub fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        let mut prev = Raw::null();
+        let mut curr = self.head;
+
+        loop {
+            let keep = match curr.as_mut() {
+                Some(node) => f(&mut node.data),
+                None => break
+            };
+
+            let next = prev.xor(&curr.as_ref().unwrap().link);
+            if keep {
+                prev = curr;
+            } else {
+                let node = self.unlink_node(prev, curr);
+                self.free_node(node);
             }
-        })
+            curr = next;
+        }
     }
 
     /**
-     * Removes the element after the cursor and returns it.
+     * Splits the list in two at the given index, returning everything from `at` onwards. The
+     * split point is found by walking from whichever end of the list is closer.
+     *
+     * Panics if `at > len`.
      */
-    pub fn remove(&mut self) -> Option<Elem<T>> {
-        unsafe {
-            if (*self.list).head == self.curr.get() {
-                let elem = (*self.list).pop_front();
-                self.curr.set((*self.list).head);
-                return elem;
-            } else if (*self.list).tail == self.curr.get() {
-                self.curr.set(Raw::null());
-                return (*self.list).pop_back();
-            }
+    pub fn split_off(&mut self, at: usize) -> XorList<T, A> {
+        assert!(at <= self.len, "Cannot split off at a nonexistent index");
+
+        if at == 0 {
+            let mut list = XorList::new_in(self.alloc.clone());
+            self.swap_nodes(&mut list);
+            return list;
+        } else if at == self.len {
+            return XorList::new_in(self.alloc.clone());
         }
 
-        let mut prev = self.prev.get();
-        let curr_ptr = self.curr.get();
-        let curr = self.curr.get().take();
-        self.curr.set(Raw::null());
+        let (prev, curr) = self.nodes_at(at);
+        self.split_nodes(prev, curr, at)
+    }
 
+    /**
+     * Shortens the list to `len` elements, dropping the rest. Does nothing if the list is
+     * already shorter than `len`.
+     */
+    pub fn truncate(&mut self, len: usize) {
+        if len == 0 {
+            self.clear();
+            return;
+        } else if len >= self.len {
+            return;
+        }
 
-        curr.map(|node| {
-            let mut next = prev.xor(&node.link);
+        let (mut prev, mut curr) = self.nodes_at(len);
 
-            // Calculate the new link values, based on this:
-            //
-            //         |
-            //         v
-            // A   B   C   D   E
-            //
-            // Where we're removing C
+        // Sever the suffix once, leaving `curr` as the head of a chain of its own
+        {
+            let prev_node = prev.as_mut().unwrap();
+            prev_node.link = prev_node.link.xor(&curr);
+        }
+        {
+            let curr_node = curr.as_mut().unwrap();
+            curr_node.link = curr_node.link.xor(&prev);
+        }
 
-            if let Some(prev_node) = prev.as_mut() {
-                // Link for B need to be A ^ D
-                // A ^ D = ((A ^ C) ^ C) ^ D
-                let new_link = prev_node.link.xor(&curr_ptr).xor(&next);
-                prev_node.link = new_link;
-            }
-            if let Some(next_node) = next.as_mut() {
-                // Link for D need to be B ^ E
-                // B ^ E = ((C ^ E) ^ C) ^ B
-                let new_link = next_node.link.xor(&curr_ptr).xor(&prev);
-                next_node.link = new_link;
-            }
+        // Single-element lists don't have a tail
+        self.tail = if prev == self.head { Raw::null() } else { prev };
+        self.len = len;
+        self.invalidate_positions();
 
-            self.curr.set(next);
+        unsafe {
+            free_chain(curr, &self.node_alloc());
+        }
+    }
 
-            Elem { __node: node }
-        })
+    /**
+     * Reverses the order of the list. Since the links in an XOR list work the same in both
+     * directions, this just swaps the head and the tail, so it's a constant-time operation.
+     */
+    pub fn reverse(&mut self) {
+        // Single-element lists only have a head
+        if !self.tail.is_null() {
+            mem::swap(&mut self.head, &mut self.tail);
+        }
     }
 
     /**
-     * Inserts the given value at the cursor position, leaving the cursor after the inserted value.
+     * Rotates the list so that the element at position `n` becomes the head. `n` wraps around
+     * if it's larger than the length of the list.
      */
-    pub fn insert_before<U: Unsize<T>>(&self, val: U) {
-        unsafe {
-            if (*self.list).head == self.curr.get() {
-                // We're at the head of the list, push to the front
-                (*self.list).push_front(val);
-                self.prev.set((*self.list).head);
-            } else if self.curr.get().is_null() {
-                // We're at the tail of the list, push to the back
-                (*self.list).push_back(val);
-                self.prev.set((*self.list).tail);
-            } else {
-                // We're somewhere in the middle
+    pub fn rotate_left(&mut self, n: usize) {
+        if self.len < 2 { return; }
 
-                debug_assert!(!self.curr.get().is_null());
-                debug_assert!(!self.prev.get().is_null());
+        let n = n % self.len;
+        if n == 0 { return; }
 
-                let node = Node::new(val);
+        let (mut prev, mut curr) = self.nodes_at(n);
+        let mut head = self.head;
+        let mut tail = self.tail;
 
-                let prev = self.prev.get();
-                let curr = self.curr.get();
+        // Join the ends together, making the list a ring
+        {
+            let tail_node = tail.as_mut().unwrap();
+            tail_node.link = tail_node.link.xor(&head);
+        }
+        {
+            let head_node = head.as_mut().unwrap();
+            head_node.link = head_node.link.xor(&tail);
+        }
 
-                self.prev.set(self.insert_between(prev, curr, node));
-            }
+        // Then break the ring at the new position
+        {
+            let prev_node = prev.as_mut().unwrap();
+            prev_node.link = prev_node.link.xor(&curr);
         }
+        {
+            let curr_node = curr.as_mut().unwrap();
+            curr_node.link = curr_node.link.xor(&prev);
+        }
+
+        self.head = curr;
+        self.tail = prev;
     }
 
+    /**
+     * Rotates the list so that the element `n` positions from the end becomes the head. `n`
+     * wraps around if it's larger than the length of the list.
+     */
+    pub fn rotate_right(&mut self, n: usize) {
+        if self.len < 2 { return; }
+
+        let n = n % self.len;
+        if n == 0 { return; }
+
+        let len = self.len;
+        self.rotate_left(len - n);
+    }
 
     /**
-     * Inserts the given value at the cursor position, leaving the cursor before the inserted value.
+     * Swaps the elements at positions `i` and `j` by relinking their nodes.
+     *
+     * Panics if either index is out of bounds.
      */
-    pub fn insert_after<U: Unsize<T>>(&self, val: U) {
-        unsafe {
-            if (*self.list).head == self.curr.get() {
-                // We're at the head of the list, push to the front
-                (*self.list).push_front(val);
-                self.curr.set((*self.list).head);
-            } else if self.curr.get().is_null() {
-                // We're at the tail of the list, push to the back
-                (*self.list).push_back(val);
-                self.curr.set((*self.list).tail);
-            } else {
-                // We're somewhere in the middle
+    pub fn swap(&mut self, i: usize, j: usize) {
+        assert!(i < self.len && j < self.len, "Swap index out of bounds");
 
-                debug_assert!(!self.curr.get().is_null());
-                debug_assert!(!self.prev.get().is_null());
+        if i == j { return; }
+        let (i, j) = if i < j { (i, j) } else { (j, i) };
 
-                let node = Node::new(val);
+        let (a_prev, a) = self.nodes_at(i);
+        let (b_prev, b) = self.nodes_at(j);
 
-                let prev = self.prev.get();
-                let curr = self.curr.get();
+        if j == i + 1 {
+            // The nodes are next to each other, so just move `b` in front of `a`
+            let b_node = self.unlink_node(a, b);
+            self.link_node(a_prev, a, b_node);
+        } else {
+            let a_next = a_prev.xor(&a.as_ref().unwrap().link);
+            let b_next = b_prev.xor(&b.as_ref().unwrap().link);
 
-                self.curr.set(self.insert_between(prev, curr, node));
-            }
+            let a_node = self.unlink_node(a_prev, a);
+            let b_node = self.unlink_node(b_prev, b);
+
+            self.link_node(a_prev, a_next, b_node);
+            self.link_node(b_prev, b_next, a_node);
         }
     }
 
-    fn insert_between(&self, mut prev: Raw<Node<T>>, mut next: Raw<Node<T>>,
-                      mut node: Box<Node<T>>) -> Raw<Node<T>> {
-        node.link = prev.xor(&next);
-        let node = Raw::new(into_raw(node));
+    /**
+     * Removes consecutive equal elements, leaving only the first of each run.
+     */
+    pub fn dedup(&mut self) where T: PartialEq {
+        self.dedup_by(|a, b| a == b)
+    }
 
-        if let Some(prev_node) = prev.as_mut() {
-            let new_link = prev_node.link.xor(&next).xor(&node);
-            prev_node.link = new_link;
-        }
+    /**
+     * Removes consecutive elements that the given function considers to be the same. The
+     * function is passed each element along with the element that precedes it in the list, in
+     * that order, and if it returns `true` the element is removed.
+     */
+    pub fn dedup_by<F: FnMut(&mut T, &mut T) -> bool>(&mut self, mut same: F) {
+        let mut prev = self.head;
+        let mut curr = match self.head.as_ref() {
+            Some(head) => head.link,
+            None => return
+        };
 
-        if let Some(next_node) = next.as_mut() {
-            let new_link = next_node.link.xor(&prev).xor(&node);
-            next_node.link = new_link;
+        loop {
+            let remove = match (curr.as_mut(), prev.as_mut()) {
+                (Some(curr_node), Some(prev_node)) => {
+                    same(&mut curr_node.data, &mut prev_node.data)
+                }
+                _ => break
+            };
+
+            let next = prev.xor(&curr.as_ref().unwrap().link);
+            if remove {
+                let node = self.unlink_node(prev, curr);
+                self.free_node(node);
+            } else {
+                prev = curr;
+            }
+            curr = next;
         }
+    }
 
-        return node;
+    /**
+     * Sorts the list. The sort is stable, and works by relinking the nodes, so the elements
+     * themselves are never moved.
+     */
+    pub fn sort(&mut self) where T: Ord {
+        self.sort_by(|a, b| a.cmp(b))
     }
 
     /**
-     * Inserts the given list at the cursor location. The cursor will be placed before the first
-     * inserted element
+     * Sorts the list using the given key extraction function. See `sort_by`.
      */
-    pub fn splice(&mut self, mut list: XorList<T>) {
-        unsafe {
-            // Given list is empty
-            if list.head.is_null() { return; }
+    pub fn sort_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, mut f: F) {
+        self.sort_by(|a, b| f(a).cmp(&f(b)))
+    }
 
-            // Only a single node in the given list
-            if list.tail.is_null() {
-                let node = list.head.take().unwrap();
+    /**
+     * Sorts the list using the given comparison function. This is a stable, bottom-up merge sort
+     * that relinks the nodes rather than moving the elements.
+     *
+     * If the comparison function panics, the elements of the list are leaked.
+     */
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut cmp: F) {
+        if self.len < 2 { return; }
 
-                let prev = self.prev.get();
-                let curr = self.curr.get();
+        let len = self.len;
+        let mut head = self.take_singly_linked();
 
-                let node = self.insert_between(prev, curr, node);
-                self.curr.set(node);
+        // `runs[i]` is either null or a sorted run of `2^i` nodes. Runs with higher indices
+        // contain earlier elements, which is what keeps the sort stable.
+        let mut runs = [Raw::null(); 64];
+        while !head.is_null() {
+            let mut carry = head;
+            {
+                let node = carry.as_mut().unwrap();
+                head = node.link;
+                node.link = Raw::null();
+            }
 
-                // Fix-up the head/tail references in the list
-                if prev.is_null() {
-                    (*self.list).head = node;
-                } else if curr.is_null() {
-                    (*self.list).tail = node;
-                }
+            let mut i = 0;
+            while !runs[i].is_null() {
+                carry = merge_runs(runs[i], carry, &mut cmp);
+                runs[i] = Raw::null();
+                i += 1;
+            }
+            runs[i] = carry;
+        }
 
-                return;
+        let mut sorted = Raw::null();
+        for run in runs.iter() {
+            if !run.is_null() {
+                sorted = merge_runs(*run, sorted, &mut cmp);
             }
+        }
 
-            // This list we have is actually empty, just move the
-            // head/tail pointers over
-            if (*self.list).is_empty() {
-                (*self.list).head = list.head;
-                (*self.list).tail = list.tail;
-                list.head = Raw::null();
-                list.tail = Raw::null();
+        self.restore_singly_linked(sorted, len);
+    }
 
-                self.prev.set(Raw::null());
-                self.curr.set((*self.list).head);
-            }
+    /**
+     * Merges the sorted list `other` into this sorted list, so that the result is sorted. The
+     * merge is stable, with elements from this list coming before equal elements from `other`.
+     * No nodes are allocated or freed.
+     */
+    pub fn merge(&mut self, other: XorList<T, A>) where T: Ord {
+        self.merge_by(other, |a, b| a.cmp(b))
+    }
 
-            let mut list_head = list.head.take().unwrap();
-            let mut list_tail = list.tail.take().unwrap();
+    /**
+     * Merges the list `other` into this one using the given comparison function. Both lists
+     * should already be sorted according to `cmp`. See `merge`.
+     *
+     * If the comparison function panics, the elements of both lists are leaked.
+     */
+    pub fn merge_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut other: XorList<T, A>,
+                                                          mut cmp: F) {
+        self.check_allocator(&other.alloc);
+        if other.is_empty() { return; }
+        if self.is_empty() {
+            self.swap_nodes(&mut other);
+            return;
+        }
 
-            let mut prev = self.prev.get();
-            let mut curr = self.curr.get();
+        let len = self.len + other.len;
+        let a = self.take_singly_linked();
+        let b = other.take_singly_linked();
 
-            list_head.link = list_head.link.xor(&prev);
-            list_tail.link = list_tail.link.xor(&curr);
+        let merged = merge_runs(a, b, &mut cmp);
+        self.restore_singly_linked(merged, len);
+    }
 
-            let head = Raw::new(into_raw(list_head));
-            let tail = Raw::new(into_raw(list_tail));
+    /**
+     * Consumes the list, splitting it into two: the elements for which the predicate returns
+     * `true` and the ones for which it returns `false`. The nodes are moved into the new lists,
+     * so no elements are copied, and their relative order is preserved.
+     */
+    pub fn partition<F: FnMut(&T) -> bool>(mut self, mut f: F) -> (XorList<T, A>, XorList<T, A>) {
+        let mut left = XorList::new_in(self.alloc.clone());
+        let mut right = XorList::new_in(self.alloc.clone());
 
-            if let Some(prev_node) = prev.as_mut() {
-                prev_node.link = prev_node.link.xor(&curr).xor(&head);
+        while let Some(elem) = self.pop_front() {
+            if f(&*elem) {
+                left.push_back_elem(elem);
             } else {
-                (*self.list).head = head;
+                right.push_back_elem(elem);
             }
+        }
 
-            if let Some(curr_node) = curr.as_mut() {
-                curr_node.link = curr_node.link.xor(&prev).xor(&tail);
-            } else {
-                (*self.list).tail = tail;
-            }
+        (left, right)
+    }
 
-            self.curr.set(head);
+    /**
+     * Consumes the list, moving each element into its own box.
+     */
+    pub fn into_boxes(mut self) -> Vec<Box<T>> {
+        self.drain().map(|elem| elem.into_box()).collect()
+    }
+
+    /**
+     * Moves all the elements from `other` to the end of this list, leaving `other` empty.
+     *
+     * This is a constant-time operation. Panics if the lists use different allocators.
+     */
+    pub fn append(&mut self, other: &mut XorList<T, A>) {
+        self.check_allocator(&other.alloc);
+        if other.is_empty() { return; }
+
+        if self.is_empty() {
+            self.swap_nodes(other);
+            return;
+        }
+
+        let mut last = self.back_node();
+        let mut first = other.head;
+
+        {
+            let last_node = last.as_mut().unwrap();
+            last_node.link = last_node.link.xor(&first);
+        }
+        {
+            let first_node = first.as_mut().unwrap();
+            first_node.link = first_node.link.xor(&last);
         }
+
+        self.tail = other.back_node();
+        self.len += other.len;
+
+        other.head = Raw::null();
+        other.tail = Raw::null();
+        other.len = 0;
+        other.invalidate_positions();
     }
 
     /**
-     * Splits the list at the cursor returning the remaining elements in a new list
+     * Moves all the elements from `other` to the beginning of this list, leaving `other` empty.
+     *
+     * This is a constant-time operation.
+     */
+    pub fn prepend(&mut self, other: &mut XorList<T, A>) {
+        other.append(self);
+        self.swap_nodes(other);
+    }
+
+    /**
+     * Removes all the elements from the list.
      */
-    pub fn split(&mut self) -> XorList<T> {
+    pub fn clear(&mut self) {
+        let head = mem::replace(&mut self.head, Raw::null());
+        self.tail = Raw::null();
+        self.len = 0;
+        self.invalidate_positions();
+
         unsafe {
-            let mut new_list = XorList::new();
+            free_chain(head, &self.node_alloc());
+        }
+    }
+}
 
-            // We're at the end of the list, so return the empty list
-            if self.curr.get().is_null() {
-                return new_list;
+impl<T: ?Sized, A: Allocator> XorList<T, A> {
+    fn push_back_node(&mut self, mut node_ptr: Raw<Node<T>>) {
+        self.len += 1;
+
+        if self.head.is_null() {
+            node_ptr.as_mut().unwrap().link = Raw::null();
+            self.head = node_ptr;
+        } else if self.tail.is_null() {
+            node_ptr.as_mut().unwrap().link = self.head;
+            self.tail = node_ptr;
+            let head = self.head.as_mut().expect("There should be a head!");
+            head.link = self.tail;
+        } else {
+            node_ptr.as_mut().unwrap().link = self.tail;
+
+            {
+                let tail = self.tail.as_mut().expect("There should be a tail!");
+                tail.link = tail.link.xor(&node_ptr);
             }
+            self.tail = node_ptr;
+        }
+    }
 
-            // We're at start end of the list, so move the current list
-            // over to the new one
-            if self.prev.get().is_null() {
-                new_list.head = (*self.list).head;
-                new_list.tail = (*self.list).tail;
+    fn push_front_node(&mut self, mut node_ptr: Raw<Node<T>>) {
+        self.len += 1;
 
-                (*self.list).head = Raw::null();
-                (*self.list).tail = Raw::null();
+        if self.head.is_null() {
+            node_ptr.as_mut().unwrap().link = Raw::null();
+            self.head = node_ptr;
+        } else if self.tail.is_null() {
+            let mut old_head = self.head;
+            self.tail = old_head;
+            node_ptr.as_mut().unwrap().link = self.tail;
+            self.head = node_ptr;
+            let old_head = old_head.as_mut().unwrap();
+            old_head.link = self.head;
+        } else {
+            node_ptr.as_mut().unwrap().link = self.head;
 
-                self.curr.set(Raw::null());
+            {
+                let head = self.head.as_mut().unwrap();
+                head.link = head.link.xor(&node_ptr);
+            }
 
-                return new_list;
+            self.head = node_ptr;
+        }
+    }
+
+    /**
+     * Returns the pair of nodes either side of the position before `index`, walking from
+     * whichever end of the list is closer.
+     */
+    fn nodes_at(&self, index: usize) -> (Raw<Node<T>>, Raw<Node<T>>) {
+        debug_assert!(index <= self.len);
+
+        if index <= self.len / 2 {
+            let mut prev = Raw::null();
+            let mut curr = self.head;
+            for _ in 0..index {
+                let next = prev.xor(&curr.as_ref().unwrap().link);
+                prev = curr;
+                curr = next;
             }
+            (prev, curr)
+        } else {
+            let mut prev = self.back_node();
+            let mut curr = Raw::null();
+            for _ in index..self.len {
+                let before = curr.xor(&prev.as_ref().unwrap().link);
+                curr = prev;
+                prev = before;
+            }
+            (prev, curr)
+        }
+    }
 
-            // We're somewhere in the middle
-            let curr = self.curr.get();
-            self.curr.set(Raw::null());
+    /**
+     * Splits the list between `prev` and `curr`, which must both be non-null, returning the
+     * second half. `at` is the number of elements before the split.
+     */
+    fn split_nodes(&mut self, mut prev: Raw<Node<T>>, mut curr: Raw<Node<T>>,
+                   at: usize) -> XorList<T, A> {
+        // Sever the links between the two halves
+        {
+            let prev_node = prev.as_mut().unwrap();
+            prev_node.link = prev_node.link.xor(&curr);
+        }
+        {
+            let curr_node = curr.as_mut().unwrap();
+            curr_node.link = curr_node.link.xor(&prev);
+        }
 
-            new_list.head = curr;
-            new_list.tail = (*self.list).tail;
+        let mut new_list = XorList::new_in(self.alloc.clone());
+        new_list.head = curr;
+        new_list.tail = self.tail;
+        new_list.len = self.len - at;
 
-            (*self.list).tail = self.prev.get();
+        self.tail = prev;
+        self.len = at;
+        self.invalidate_positions();
 
-            return new_list;
+        // Single-element lists don't have a tail
+        if new_list.head == new_list.tail {
+            new_list.tail = Raw::null();
         }
+        if self.head == self.tail {
+            self.tail = Raw::null();
+        }
+
+        new_list
     }
-}
 
-impl<U: ?Sized, T: Unsize<U>> iter::FromIterator<T> for XorList<U> {
-    fn from_iter<I>(iter: I) -> XorList<U> where I: IntoIterator<Item=T> {
-        let mut list = XorList::new();
-        list.extend(iter);
-        return list;
+    /**
+     * Links `node` into the list between `prev` and `next`, either of which may be null to
+     * indicate the start or end of the list. Returns the newly-linked node.
+     */
+    fn link_node(&mut self, mut prev: Raw<Node<T>>, mut next: Raw<Node<T>>,
+                 mut node: Raw<Node<T>>) -> Raw<Node<T>> {
+        node.as_mut().unwrap().link = prev.xor(&next);
+
+        if let Some(prev_node) = prev.as_mut() {
+            prev_node.link = prev_node.link.xor(&next).xor(&node);
+        } else {
+            self.head = node;
+        }
+
+        if let Some(next_node) = next.as_mut() {
+            next_node.link = next_node.link.xor(&prev).xor(&node);
+        } else {
+            self.tail = node;
+        }
+
+        if self.len == 0 {
+            // Single-element lists don't have a tail
+            self.tail = Raw::null();
+        } else if self.tail.is_null() {
+            // We were a single-element list, and we inserted before the head
+            self.tail = next;
+        }
+
+        self.len += 1;
+
+        node
     }
-}
 
-impl<U: ?Sized, T: Unsize<U>> Extend<T> for XorList<U> {
-    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item=T> {
-        for el in iter {
-            self.push_back(el);
+    /**
+     * Unlinks `curr` from the list and returns it. `prev` must be the node before `curr`, or null
+     * if `curr` is the head.
+     */
+    fn unlink_node(&mut self, mut prev: Raw<Node<T>>, mut curr: Raw<Node<T>>) -> Raw<Node<T>> {
+        let mut next = prev.xor(&curr.as_ref().expect("Can't unlink a null node").link);
+
+        // Calculate the new link values, based on this:
+        //
+        //         |
+        //         v
+        // A   B   C   D   E
+        //
+        // Where we're removing C
+
+        if let Some(prev_node) = prev.as_mut() {
+            // Link for B need to be A ^ D
+            prev_node.link = prev_node.link.xor(&curr).xor(&next);
+        } else {
+            self.head = next;
+        }
+
+        if let Some(next_node) = next.as_mut() {
+            // Link for D need to be B ^ E
+            next_node.link = next_node.link.xor(&curr).xor(&prev);
+        } else {
+            self.tail = prev;
+        }
+
+        // Single-element lists don't have a tail
+        if self.head == self.tail {
+            self.tail = Raw::null();
         }
+
+        self.len -= 1;
+        self.invalidate_positions();
+
+        curr.as_mut().unwrap().link = Raw::null();
+        curr
     }
-}
 
-/**
- * A simple wrapper type for removing elements by value.
- */
-pub struct Elem<T: ?Sized> {
-    __node: Box<Node<T>>
-}
+    /**
+     * Wraps an unlinked node in an `Elem` that will free it with this list's allocator.
+     */
+    fn elem(&self, node: Raw<Node<T>>) -> Elem<T, A> {
+        Elem {
+            __node: node,
+            alloc: self.alloc.clone()
+        }
+    }
 
-impl<T: ?Sized> ops::Deref for Elem<T> {
-    type Target = T;
+    /**
+     * Drops the value in an unlinked node and frees the node, or keeps it for reuse.
+     */
+    fn free_node(&self, node: Raw<Node<T>>) {
+        unsafe {
+            Node::free(node, &self.node_alloc());
+        }
+    }
 
-    fn deref<'a>(&'a self) -> &'a T {
-        &self.__node.data
+    /**
+     * Panics if `alloc` isn't the same allocator as the list's, so a node from it can't be
+     * linked into the list and later freed with the wrong one.
+     */
+    fn check_allocator(&self, alloc: &A) {
+        assert!(self.alloc.same_as(alloc),
+                "The nodes came from a list with a different allocator");
     }
-}
 
-impl<T: ?Sized> ops::DerefMut for Elem<T> {
-    fn deref_mut<'a>(&'a mut self) -> &'a mut T {
-        &mut self.__node.data
+    /**
+     * Moves the value out of an element removed from this list, and frees its node or keeps it
+     * for reuse.
+     */
+    fn recycle_value(&self, elem: Elem<T, A>) -> T where T: Sized {
+        unsafe {
+            let node = elem.into_node();
+            let val = ptr::read(&(*node.ptr()).data);
+            Node::dealloc(node, &self.node_alloc());
+            val
+        }
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use std::fmt::{Display, Debug};
+    /**
+     * Returns the allocator for the list's own nodes, which reuses nodes from the pool.
+     */
+    fn node_alloc<'a>(&'a self) -> Recycler<'a, A> {
+        Recycler {
+            pool: &self.pool,
+            alloc: &self.alloc
+        }
+    }
+
+    /**
+     * Swaps the elements of this list with the elements of `other`. Unlike `mem::swap`, each
+     * list keeps its own pool.
+     */
+    fn swap_nodes(&mut self, other: &mut XorList<T, A>) {
+        mem::swap(&mut self.head, &mut other.head);
+        mem::swap(&mut self.tail, &mut other.tail);
+        mem::swap(&mut self.len, &mut other.len);
+        self.invalidate_positions();
+        other.invalidate_positions();
+    }
+
+    /**
+     * Makes any saved cursor positions for this list stale. This must be called whenever nodes
+     * leave the list, since they may be freed and their memory reused for new nodes.
+     */
+    fn invalidate_positions(&mut self) {
+        self.gen = self.gen.wrapping_add(1);
+    }
+
+    /**
+     * Takes all the nodes out of the list and turns them into a singly-linked chain, with each
+     * node's link pointing directly at the next node, returning the first node. This leaves the
+     * list empty, so if anything panics while the nodes are in this state they'll be leaked
+     * rather than freed twice.
+     */
+    fn take_singly_linked(&mut self) -> Raw<Node<T>> {
+        let head = self.head;
+
+        self.head = Raw::null();
+        self.tail = Raw::null();
+        self.len = 0;
+        self.invalidate_positions();
+
+        let mut prev = Raw::null();
+        let mut curr = head;
+        while let Some(node) = curr.as_mut() {
+            let next = prev.xor(&node.link);
+            node.link = next;
+            prev = curr;
+            curr = next;
+        }
+
+        head
+    }
+
+    /**
+     * Rebuilds the XOR links for a chain of `len` nodes created by `take_singly_linked` and puts
+     * them back into this list, which must be empty.
+     */
+    fn restore_singly_linked(&mut self, head: Raw<Node<T>>, len: usize) {
+        debug_assert!(self.is_empty());
+
+        let mut prev = Raw::null();
+        let mut curr = head;
+        while let Some(node) = curr.as_mut() {
+            let next = node.link;
+            node.link = prev.xor(&next);
+            prev = curr;
+            curr = next;
+        }
+
+        self.head = head;
+        self.tail = if len > 1 { prev } else { Raw::null() };
+        self.len = len;
+    }
+
+    /**
+     * Walks `len` nodes from `start`, which must be one of the ends of the list, returning the
+     * last node visited, or `None` if the walk ends early or doesn't end there.
+     */
+    fn walk_from(&self, start: Raw<Node<T>>) -> Option<Raw<Node<T>>> {
+        let mut prev = Raw::null();
+        let mut curr = start;
+        for _ in 0..self.len {
+            let next = match curr.as_ref() {
+                Some(node) => prev.xor(&node.link),
+                None => return None
+            };
+            prev = curr;
+            curr = next;
+        }
+
+        if curr.is_null() { Some(prev) } else { None }
+    }
+
+    /**
+     * Calls `f` with the index, previous, current and next node for each node from the head,
+     * stopping after `len` nodes or at a null link. Returns the number of nodes visited and the
+     * next node after the last one.
+     */
+    fn walk_links<F>(&self, mut f: F) -> (usize, Raw<Node<T>>)
+            where F: FnMut(usize, Raw<Node<T>>, Raw<Node<T>>, Raw<Node<T>>) {
+        let mut prev = Raw::null();
+        let mut curr = self.head;
+        let mut i = 0;
+        while i < self.len {
+            let next = match curr.as_ref() {
+                Some(node) => prev.xor(&node.link),
+                None => break
+            };
+            f(i, prev, curr, next);
+            prev = curr;
+            curr = next;
+            i += 1;
+        }
+
+        (i, curr)
+    }
+
+    /**
+     * Returns the last node in the list. Single-element lists only have a head, so this is
+     * the head in that case.
+     */
+    fn back_node(&self) -> Raw<Node<T>> {
+        if self.tail.is_null() {
+            self.head
+        } else {
+            self.tail
+        }
+    }
+}
+
+/**
+ * Merges two sorted, singly-linked runs of nodes, where each node's link points directly at the
+ * next node. Elements from `a` come first when they compare equal.
+ */
+fn merge_runs<T: ?Sized, F>(mut a: Raw<Node<T>>, mut b: Raw<Node<T>>,
+                            cmp: &mut F) -> Raw<Node<T>> where F: FnMut(&T, &T) -> Ordering {
+    let mut head = Raw::null();
+    let mut tail : Raw<Node<T>> = Raw::null();
+
+    loop {
+        let take_a = match (a.as_ref(), b.as_ref()) {
+            (Some(x), Some(y)) => cmp(&x.data, &y.data) != Ordering::Greater,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break
+        };
+
+        let node = if take_a {
+            let node = a;
+            a = node.as_ref().unwrap().link;
+            node
+        } else {
+            let node = b;
+            b = node.as_ref().unwrap().link;
+            node
+        };
+
+        match tail.as_mut() {
+            Some(tail_node) => tail_node.link = node,
+            None => head = node
+        }
+        tail = node;
+    }
+
+    return head;
+}
+
+/**
+ * Frees the nodes of a chain, front to back, without touching their links. If dropping a value
+ * panics, the rest of the chain is freed while unwinding.
+ */
+struct FreeNodes<'a, T: ?Sized + 'a, A: Allocator + 'a> {
+    prev: Raw<Node<T>>,
+    curr: Raw<Node<T>>,
+    alloc: &'a A
+}
+
+impl<'a, T: ?Sized, A: Allocator> FreeNodes<'a, T, A> {
+    /**
+     * Frees the next node in the chain, returning `false` once the chain is exhausted.
+     */
+    unsafe fn free_next(&mut self) -> bool {
+        let node = self.curr;
+        let next = match node.as_ref() {
+            Some(n) => self.prev.xor(&n.link),
+            None => return false
+        };
+
+        // Step past the node first, so that a panic while dropping its value doesn't free it
+        // again
+        self.prev = node;
+        self.curr = next;
+        Node::free(node, self.alloc);
+        true
+    }
+}
+
+impl<'a, T: ?Sized, A: Allocator> Drop for FreeNodes<'a, T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            while self.free_next() {}
+        }
+    }
+}
+
+/**
+ * Drops and frees every node in the chain starting at `head`. The chain must already be detached
+ * from its list.
+ */
+unsafe fn free_chain<T: ?Sized, A: Allocator>(head: Raw<Node<T>>, alloc: &A) {
+    let mut nodes = FreeNodes {
+        prev: Raw::null(),
+        curr: head,
+        alloc: alloc
+    };
+    while nodes.free_next() {}
+}
+
+impl<T: ?Sized, A: Allocator> Drop for XorList<T, A> {
+    fn drop(&mut self) {
+        // Free the pool first, then free the nodes straight to the allocator rather than
+        // filling the pool back up
+        self.shrink_pool();
+        unsafe {
+            free_chain(self.head, &self.alloc);
+        }
+    }
+}
+
+pub struct Iter<'a, T: ?Sized + 'a> {
+    prev: Raw<Node<T>>,
+    curr: Raw<Node<T>>,
+    phantom: PhantomData<&'a XorList<T>>
+}
+
+// Iter behaves like `&'a XorList<T>`
+unsafe impl<'a, T: ?Sized + Sync> Send for Iter<'a, T> {}
+unsafe impl<'a, T: ?Sized + Sync> Sync for Iter<'a, T> {}
+
+impl<'a, T:?Sized> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let curr = self.curr;
+
+        // Only step when there's a node, so the iterator stays at the end once it gets there
+        if let Some(node) = curr.as_ref() {
+            self.curr = self.prev.xor(&node.link);
+            self.prev = curr;
+            unsafe {
+                Some(mem::transmute(&node.data))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T: ?Sized> iter::FusedIterator for Iter<'a, T> {}
+
+/**
+ * An iterator over mutable references to the elements of a list. It can only be sent to another
+ * thread if the elements can:
+ *
+ * ```compile_fail
+ * # use std::rc::Rc;
+ * # use dynalist::xorlist::IterMut;
+ * fn assert_send<T: Send>() {}
+ * assert_send::<IterMut<Rc<i32>>>();
+ * ```
+ */
+pub struct IterMut<'a, T: ?Sized + 'a> {
+    prev: Raw<Node<T>>,
+    curr: Raw<Node<T>>,
+    phantom: PhantomData<&'a mut XorList<T>>
+}
+
+// IterMut behaves like `&'a mut XorList<T>`
+unsafe impl<'a, T: ?Sized + Send> Send for IterMut<'a, T> {}
+unsafe impl<'a, T: ?Sized + Sync> Sync for IterMut<'a, T> {}
+
+impl<'a, T:?Sized> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        let curr = self.curr;
+        let mut node_ptr = curr;
+
+        if let Some(node) = node_ptr.as_mut() {
+            self.curr = self.prev.xor(&node.link);
+            self.prev = curr;
+            unsafe {
+                Some(mem::transmute(&mut node.data))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T: ?Sized> iter::FusedIterator for IterMut<'a, T> {}
+
+pub struct IntoIter<T: ?Sized, A: Allocator = Global> {
+    list: XorList<T, A>
+}
+
+impl<T: ?Sized, A: Allocator> Iterator for IntoIter<T, A> {
+    type Item = Elem<T, A>;
+
+    fn next(&mut self) -> Option<Elem<T, A>> {
+        self.list.pop_front()
+    }
+}
+
+impl<T: ?Sized, A: Allocator> DoubleEndedIterator for IntoIter<T, A> {
+    fn next_back(&mut self) -> Option<Elem<T, A>> {
+        self.list.pop_back()
+    }
+}
+
+impl<T: ?Sized, A: Allocator> iter::FusedIterator for IntoIter<T, A> {}
+
+pub struct Drain<'a, T: ?Sized + 'a, A: Allocator + 'a = Global> {
+    list: &'a mut XorList<T, A>
+}
+
+impl<'a, T: ?Sized, A: Allocator> Iterator for Drain<'a, T, A> {
+    type Item = Elem<T, A>;
+
+    fn next(&mut self) -> Option<Elem<T, A>> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len(), Some(self.list.len()))
+    }
+}
+
+impl<'a, T: ?Sized, A: Allocator> DoubleEndedIterator for Drain<'a, T, A> {
+    fn next_back(&mut self) -> Option<Elem<T, A>> {
+        self.list.pop_back()
+    }
+}
+
+impl<'a, T: ?Sized, A: Allocator> ExactSizeIterator for Drain<'a, T, A> {}
+
+impl<'a, T: ?Sized, A: Allocator> iter::FusedIterator for Drain<'a, T, A> {}
+
+impl<'a, T: ?Sized, A: Allocator> Drop for Drain<'a, T, A> {
+    fn drop(&mut self) {
+        self.list.clear();
+    }
+}
+
+pub struct ExtractIf<'a, T: ?Sized + 'a, F, A: Allocator + 'a = Global>
+        where F: FnMut(&mut T) -> bool {
+    prev: Raw<Node<T>>,
+    curr: Raw<Node<T>>,
+    list: &'a mut XorList<T, A>,
+    pred: F
+}
+
+impl<'a, T: ?Sized, F, A: Allocator> Iterator for ExtractIf<'a, T, F, A>
+        where F: FnMut(&mut T) -> bool {
+    type Item = Elem<T, A>;
+
+    fn next(&mut self) -> Option<Elem<T, A>> {
+        loop {
+            let matched = match self.curr.as_mut() {
+                Some(node) => (self.pred)(&mut node.data),
+                None => return None
+            };
+
+            let prev = self.prev;
+            let curr = self.curr;
+            self.curr = prev.xor(&curr.as_ref().unwrap().link);
+
+            if matched {
+                let node = self.list.unlink_node(prev, curr);
+                return Some(self.list.elem(node));
+            } else {
+                self.prev = curr;
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.list.len()))
+    }
+}
+
+impl<'a, T: ?Sized, F, A: Allocator> iter::FusedIterator for ExtractIf<'a, T, F, A>
+        where F: FnMut(&mut T) -> bool {}
+
+/**
+ * A "Cursor" into a list.
+ *
+ * A `Cursor` is a structure representing a position between two elements in the list. It acts as
+ * if there are special sentinel values at either end of the list so it can be placed after the
+ * tail of the list or before the head of the list.
+ *
+ * `Cursor` allows you to traverse the list, insert and remove elements at arbitrary positions in
+ * the list, insert other XorLists and split the list at the cursor position.
+ *
+ * Moving the cursor only needs `&self`, so references to elements returned while moving around
+ * can be held together. Every method that changes the list needs `&mut self`, so it can't be
+ * called while any of those references are alive:
+ *
+ * ```compile_fail
+ * # use dynalist::XorList;
+ * let mut list = XorList::from(vec![1, 2, 3]);
+ * let mut cursor = list.cursor();
+ * let r = cursor.peek().unwrap();
+ * cursor.remove();
+ * assert_eq!(*r, 1);
+ * ```
+ *
+ * Mutable references borrow the cursor mutably, so only one can be alive at a time:
+ *
+ * ```compile_fail
+ * # use dynalist::XorList;
+ * let mut list = XorList::from(vec![1, 2, 3]);
+ * let mut cursor = list.cursor();
+ * let a = cursor.peek_mut().unwrap();
+ * let b = cursor.peek_mut().unwrap();
+ * *a += *b;
+ * ```
+ *
+ * And the cursor borrows the list mutably, so the list can't be used while the cursor is alive:
+ *
+ * ```compile_fail
+ * # use dynalist::XorList;
+ * let mut list = XorList::from(vec![1, 2, 3]);
+ * let mut cursor = list.cursor();
+ * assert_eq!(list.len(), 3);
+ * cursor.remove();
+ * ```
+ */
+pub struct Cursor<'a, T: ?Sized + 'a, A: Allocator + 'a = Global> {
+    prev: Cell<Raw<Node<T>>>,
+    curr: Cell<Raw<Node<T>>>,
+    // The number of elements before the cursor
+    index: Cell<usize>,
+    list: *mut XorList<T, A>,
+    phantom: PhantomData<&'a mut XorList<T, A>>
+}
+
+/**
+ * A cursor position saved with `Cursor::save`, which doesn't borrow the list. Use
+ * `XorList::cursor_at_pos` to get a cursor at the position again.
+ */
+pub struct CursorPos<T: ?Sized> {
+    prev: Raw<Node<T>>,
+    curr: Raw<Node<T>>,
+    id: usize,
+    gen: usize
+}
+
+impl<T: ?Sized> Copy for CursorPos<T> {}
+
+impl<T: ?Sized> Clone for CursorPos<T> {
+    fn clone(&self) -> CursorPos<T> { *self }
+}
+
+impl<T: ?Sized> fmt::Debug for CursorPos<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CursorPos {{ list: {}, gen: {} }}", self.id, self.gen)
+    }
+}
+
+impl<'a, T: ?Sized, A: Allocator> Cursor<'a, T, A> {
+    /**
+     * Saves the position of the cursor, so that a cursor can be placed there again after this
+     * one is gone. See `XorList::cursor_at_pos`.
+     */
+    pub fn save(&self) -> CursorPos<T> {
+        unsafe {
+            CursorPos {
+                prev: self.prev.get(),
+                curr: self.curr.get(),
+                id: (*self.list).id(),
+                gen: (*self.list).gen
+            }
+        }
+    }
+
+    /**
+     * Returns the number of elements before the cursor.
+     */
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index.get()
+    }
+
+
+    #[inline]
+    pub fn at_start(&self) -> bool {
+        self.prev.get().is_null()
+    }
+
+    #[inline]
+    pub fn at_end(&self) -> bool {
+        self.curr.get().is_null()
+    }
+
+    /**
+     * Move to the cursor forwards one position and return a reference to the element that was
+     * skipped over.
+     */
+    pub fn next<'b>(&'b self) -> Option<&'b T> {
+        let prev = self.prev.get();
+        let curr = self.curr.get();
+
+        // At the end of the list the cursor stays where it is
+        if let Some(node) = curr.as_ref() {
+            let next = prev.xor(&node.link);
+            self.prev.set(curr);
+            self.curr.set(next);
+            self.index.set(self.index.get() + 1);
+            unsafe {
+                Some(mem::transmute(&node.data))
+            }
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Move to the cursor backwards one position and return a reference to the element that was
+     * skipped over.
+     */
+    pub fn prev<'b>(&'b self) -> Option<&'b T> {
+        let prev = self.prev.get();
+        let curr = self.curr.get();
+
+        // At the start of the list the cursor stays where it is
+        if let Some(node) = prev.as_ref() {
+            self.curr.set(prev);
+            self.prev.set(curr.xor(&node.link));
+            self.index.set(self.index.get() - 1);
+            unsafe {
+                Some(mem::transmute(&node.data))
+            }
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Skip forward `n` positions, or until the end of the list, whichever
+     * is sooner. Returns the number of positions actually moved.
+     */
+    pub fn skip_forwards(&self, n: usize) -> usize {
+        let mut i = 0;
+        while i < n && self.next().is_some() {
+            i += 1;
+        }
+        i
+    }
+
+
+    /**
+     * Skip backward `n` positions, or until the start of the list, whichever
+     * is sooner. Returns the number of positions actually moved.
+     */
+    pub fn skip_backwards(&self, n: usize) -> usize {
+        let mut i = 0;
+        while i < n && self.prev().is_some() {
+            i += 1;
+        }
+        i
+    }
+
+    /**
+     * Moves the cursor forwards until the element after it matches the predicate, leaving the
+     * cursor just before that element and returning `true`. If no element matches, the cursor is
+     * left at the end of the list and `false` is returned.
+     */
+    pub fn seek_forward_until<F: FnMut(&T) -> bool>(&self, mut f: F) -> bool {
+        loop {
+            match self.peek() {
+                Some(el) => if f(el) { return true; },
+                None => return false
+            }
+            self.next();
+        }
+    }
+
+    /**
+     * Moves the cursor backwards until the element before it matches the predicate, leaving the
+     * cursor just after that element and returning `true`. If no element matches, the cursor is
+     * left at the start of the list and `false` is returned.
+     */
+    pub fn seek_backward_until<F: FnMut(&T) -> bool>(&self, mut f: F) -> bool {
+        loop {
+            match self.peek_prev() {
+                Some(el) => if f(el) { return true; },
+                None => return false
+            }
+            self.prev();
+        }
+    }
+
+    /**
+     * Moves the cursor to before the element at `index`, so that `index` elements come before it.
+     * An index past the end of the list moves the cursor to the end.
+     *
+     * The cursor gets there from the start of the list, the end of the list or where it is now,
+     * whichever is closest.
+     */
+    pub fn seek(&self, index: usize) {
+        let len = unsafe { (*self.list).len };
+        let index = cmp::min(index, len);
+        let here = self.index.get();
+        let from_here = if index > here { index - here } else { here - index };
+
+        if index <= from_here && index <= len - index {
+            self.seek_to_start();
+            self.skip_forwards(index);
+        } else if len - index < from_here {
+            self.seek_to_end();
+            self.skip_backwards(len - index);
+        } else if index > here {
+            self.skip_forwards(from_here);
+        } else {
+            self.skip_backwards(from_here);
+        }
+    }
+
+    /**
+     * Move the cursor to the beginning of the list.
+     */
+    pub fn seek_to_start(&self) {
+        unsafe {
+            self.prev.set(Raw::null());
+            self.curr.set((*self.list).head);
+            self.index.set(0);
+        }
+    }
+
+    /**
+     * Move the cursor to the end of the list.
+     */
+    pub fn seek_to_end(&self) {
+        unsafe {
+            self.prev.set((*self.list).back_node());
+            self.curr.set(Raw::null());
+            self.index.set((*self.list).len);
+        }
+    }
+
+    /**
+     * Returns an immutable reference to element after the cursor.
+     */
+    pub fn peek<'b>(&'b self) -> Option<&'b T> {
+        self.curr.get().as_ref().map(|node| {
+            unsafe {
+                mem::transmute(&node.data)
+            }
+        })
+    }
+
+    /**
+     * Returns a mutable reference to the element after the cursor.
+     */
+    pub fn peek_mut<'b>(&'b mut self) -> Option<&'b mut T> {
+        self.curr.get().as_mut().map(|node| {
+            unsafe {
+                mem::transmute(&mut node.data)
+            }
+        })
+    }
+
+    /**
+     * Returns an immutable reference to the element before the cursor.
+     */
+    pub fn peek_prev<'b>(&'b self) -> Option<&'b T> {
+        self.prev.get().as_ref().map(|node| {
+            unsafe {
+                mem::transmute(&node.data)
+            }
+        })
+    }
+
+    /**
+     * Returns an iterator over the elements after the cursor, without moving the cursor.
+     */
+    pub fn remaining<'b>(&'b self) -> Iter<'b, T> {
+        Iter {
+            prev: self.prev.get(),
+            curr: self.curr.get(),
+            phantom: PhantomData
+        }
+    }
+
+    /**
+     * Consumes the cursor, returning an iterator over mutable references to the elements after
+     * it.
+     */
+    pub fn into_remaining(self) -> IterMut<'a, T> {
+        IterMut {
+            prev: self.prev.get(),
+            curr: self.curr.get(),
+            phantom: PhantomData
+        }
+    }
+
+    /**
+     * Takes up to `n` elements after the cursor out as a new list, so they can be worked on
+     * separately and put back with `splice`. The cursor stays where the elements were, so
+     * splicing the list straight back in restores the original list, with the cursor before the
+     * first returned element.
+     *
+     * This is the same as `remove_n`.
+     */
+    pub fn extract(&mut self, n: usize) -> XorList<T, A> {
+        self.remove_n(n)
+    }
+
+    /**
+     * Swaps the elements before and after the cursor, leaving the cursor between them. Returns
+     * `false`, without changing anything, if the cursor is at either end of the list.
+     */
+    pub fn swap_adjacent(&mut self) -> bool {
+        let mut prev = self.prev.get();
+        let mut curr = self.curr.get();
+        if prev.is_null() || curr.is_null() {
+            return false;
+        }
+
+        unsafe {
+            let list = &mut *self.list;
+            let prev_link = prev.as_ref().unwrap().link;
+            let curr_link = curr.as_ref().unwrap().link;
+            let mut before = curr.xor(&prev_link);
+            let mut after = prev.xor(&curr_link);
+
+            // Going from before, prev, curr, after to before, curr, prev, after
+            prev.as_mut().unwrap().link = curr.xor(&after);
+            curr.as_mut().unwrap().link = before.xor(&prev);
+
+            if let Some(before_node) = before.as_mut() {
+                before_node.link = before_node.link.xor(&prev).xor(&curr);
+            } else {
+                list.head = curr;
+            }
+
+            if let Some(after_node) = after.as_mut() {
+                after_node.link = after_node.link.xor(&curr).xor(&prev);
+            } else {
+                list.tail = prev;
+            }
+
+            self.prev.set(curr);
+            self.curr.set(prev);
+            debug_assert!(list.is_consistent());
+        }
+
+        true
+    }
+
+    /**
+     * Removes up to `n` elements after the cursor, returning them as a new list. The cursor stays
+     * between the same elements, apart from the removed ones.
+     *
+     * This has to walk over the elements to find the end of the run, but only the links at
+     * either end of it are changed.
+     */
+    pub fn remove_n(&mut self, n: usize) -> XorList<T, A> {
+        unsafe {
+            let list = &mut *self.list;
+            let mut removed = XorList::new_in(list.alloc.clone());
+
+            let mut prev = self.prev.get();
+            let mut first = self.curr.get();
+            if n == 0 || first.is_null() {
+                return removed;
+            }
+
+            // Find the last node to remove, and the node after it
+            let mut last = prev;
+            let mut after = first;
+            let mut count = 0;
+            while count < n {
+                let next = match after.as_ref() {
+                    Some(node) => last.xor(&node.link),
+                    None => break
+                };
+                last = after;
+                after = next;
+                count += 1;
+            }
+
+            // Cut the run out, joining `prev` and `after`
+            {
+                let first_node = first.as_mut().unwrap();
+                first_node.link = first_node.link.xor(&prev);
+            }
+            {
+                let last_node = last.as_mut().unwrap();
+                last_node.link = last_node.link.xor(&after);
+            }
+
+            if let Some(prev_node) = prev.as_mut() {
+                prev_node.link = prev_node.link.xor(&first).xor(&after);
+            } else {
+                list.head = after;
+            }
+
+            if let Some(after_node) = after.as_mut() {
+                after_node.link = after_node.link.xor(&last).xor(&prev);
+            } else {
+                list.tail = prev;
+            }
+
+            // Single-element lists don't have a tail
+            if list.head == list.tail {
+                list.tail = Raw::null();
+            }
+
+            list.len -= count;
+            list.invalidate_positions();
+
+            removed.head = first;
+            removed.tail = if count > 1 { last } else { Raw::null() };
+            removed.len = count;
+
+            self.curr.set(after);
+            debug_assert!(list.is_consistent() && removed.is_consistent());
+            removed
+        }
+    }
+
+    /**
+     * Removes the element before the cursor and returns it. The cursor stays between the same
+     * elements, apart from the removed one.
+     */
+    pub fn remove_prev(&mut self) -> Option<Elem<T, A>> {
+        let prev = self.prev.get();
+        let prev_prev = match prev.as_ref() {
+            Some(node) => self.curr.get().xor(&node.link),
+            None => return None
+        };
+
+        unsafe {
+            let node = (*self.list).unlink_node(prev_prev, prev);
+            self.prev.set(prev_prev);
+            self.index.set(self.index.get() - 1);
+            debug_assert!((*self.list).is_consistent());
+            Some((*self.list).elem(node))
+        }
+    }
+
+    /**
+     * Removes the element after the cursor and returns it, or returns `None` if the cursor is at
+     * the end of the list. Afterwards the cursor sits where the element was, between the same
+     * neighbours as before.
+     */
+    pub fn remove(&mut self) -> Option<Elem<T, A>> {
+        let prev = self.prev.get();
+        let curr = self.curr.get();
+        let next = match curr.as_ref() {
+            Some(node) => prev.xor(&node.link),
+            None => return None
+        };
+
+        unsafe {
+            let node = (*self.list).unlink_node(prev, curr);
+            self.curr.set(next);
+            debug_assert!((*self.list).is_consistent());
+            Some((*self.list).elem(node))
+        }
+    }
+
+    /**
+     * Inserts the given value at the cursor position, leaving the cursor after the inserted value.
+     * Returns a reference to the inserted value, which borrows the cursor.
+     */
+    pub fn insert_before<'b, U: Unsize<T>>(&'b mut self, val: U) -> &'b mut T {
+        unsafe {
+            let node = Node::new(val, &(*self.list).node_alloc());
+            self.insert_node_before(node);
+            &mut (*node.ptr()).data
+        }
+    }
+
+
+    /**
+     * Inserts the given value at the cursor position, leaving the cursor before the inserted value.
+     * Returns a reference to the inserted value, which borrows the cursor.
+     */
+    pub fn insert_after<'b, U: Unsize<T>>(&'b mut self, val: U) -> &'b mut T {
+        unsafe {
+            let node = Node::new(val, &(*self.list).node_alloc());
+            self.insert_node_after(node);
+            &mut (*node.ptr()).data
+        }
+    }
+
+    /**
+     * Inserts an element that was removed from a list at the cursor position, leaving the cursor
+     * after it. The node the element is stored in is reused, so this doesn't allocate.
+     *
+     * Panics if the element came from a list with a different allocator.
+     */
+    pub fn insert_elem_before(&mut self, elem: Elem<T, A>) {
+        unsafe { (*self.list).check_allocator(&elem.alloc) };
+        self.insert_node_before(elem.into_node());
+    }
+
+    /**
+     * Inserts an element that was removed from a list at the cursor position, leaving the cursor
+     * before it. See `insert_elem_before`.
+     */
+    pub fn insert_elem_after(&mut self, elem: Elem<T, A>) {
+        unsafe { (*self.list).check_allocator(&elem.alloc) };
+        self.insert_node_after(elem.into_node());
+    }
+
+    /**
+     * Replaces the element after the cursor with the given value, leaving the cursor before the
+     * new value, and returns the old element. If the cursor is at the end of the list, the value
+     * is inserted there instead and `None` is returned.
+     */
+    pub fn replace<U: Unsize<T>>(&mut self, val: U) -> Option<Elem<T, A>> {
+        let node = unsafe { Node::new(val, &(*self.list).node_alloc()) };
+        self.replace_node(node)
+    }
+
+    /**
+     * Replaces the element after the cursor with an element that was removed from a list, like
+     * `replace`. The node the element is stored in is reused, so this doesn't allocate.
+     *
+     * Panics if the element came from a list with a different allocator.
+     */
+    pub fn replace_elem(&mut self, elem: Elem<T, A>) -> Option<Elem<T, A>> {
+        unsafe { (*self.list).check_allocator(&elem.alloc) };
+        self.replace_node(elem.into_node())
+    }
+
+    fn replace_node(&mut self, mut node: Raw<Node<T>>) -> Option<Elem<T, A>> {
+        let mut old = self.curr.get();
+        if old.is_null() {
+            self.insert_node_after(node);
+            return None;
+        }
+
+        unsafe {
+            let list = &mut *self.list;
+            let mut prev = self.prev.get();
+            let link = old.as_ref().unwrap().link;
+            let mut next = prev.xor(&link);
+
+            // The new node has the same neighbours as the old one, so it takes the old link, and
+            // the neighbours' links swap the old node for the new one
+            node.as_mut().unwrap().link = link;
+            if let Some(prev_node) = prev.as_mut() {
+                prev_node.link = prev_node.link.xor(&old).xor(&node);
+            }
+            if let Some(next_node) = next.as_mut() {
+                next_node.link = next_node.link.xor(&old).xor(&node);
+            }
+
+            if list.head == old {
+                list.head = node;
+            }
+            if list.tail == old {
+                list.tail = node;
+            }
+            list.invalidate_positions();
+
+            old.as_mut().unwrap().link = Raw::null();
+            self.curr.set(node);
+            debug_assert!(list.is_consistent());
+            Some(list.elem(old))
+        }
+    }
+
+    fn insert_node_before(&mut self, node: Raw<Node<T>>) {
+        unsafe {
+            if (*self.list).head == self.curr.get() {
+                // We're at the head of the list, push to the front
+                (*self.list).push_front_node(node);
+                self.prev.set((*self.list).head);
+            } else if self.curr.get().is_null() {
+                // We're at the tail of the list, push to the back
+                (*self.list).push_back_node(node);
+                self.prev.set((*self.list).tail);
+            } else {
+                // We're somewhere in the middle
+
+                debug_assert!(!self.curr.get().is_null());
+                debug_assert!(!self.prev.get().is_null());
+
+                let prev = self.prev.get();
+                let curr = self.curr.get();
+
+                (*self.list).len += 1;
+                self.prev.set(self.insert_between(prev, curr, node));
+            }
+        }
+        self.index.set(self.index.get() + 1);
+    }
+
+    fn insert_node_after(&mut self, node: Raw<Node<T>>) {
+        unsafe {
+            if (*self.list).head == self.curr.get() {
+                // We're at the head of the list, push to the front
+                (*self.list).push_front_node(node);
+                self.curr.set((*self.list).head);
+            } else if self.curr.get().is_null() {
+                // We're at the tail of the list, push to the back
+                (*self.list).push_back_node(node);
+                self.curr.set((*self.list).tail);
+            } else {
+                // We're somewhere in the middle
+
+                debug_assert!(!self.curr.get().is_null());
+                debug_assert!(!self.prev.get().is_null());
+
+                let prev = self.prev.get();
+                let curr = self.curr.get();
+
+                (*self.list).len += 1;
+                self.curr.set(self.insert_between(prev, curr, node));
+            }
+        }
+    }
+
+    fn insert_between(&self, mut prev: Raw<Node<T>>, mut next: Raw<Node<T>>,
+                      mut node: Raw<Node<T>>) -> Raw<Node<T>> {
+        node.as_mut().unwrap().link = prev.xor(&next);
+
+        if let Some(prev_node) = prev.as_mut() {
+            let new_link = prev_node.link.xor(&next).xor(&node);
+            prev_node.link = new_link;
+        }
+
+        if let Some(next_node) = next.as_mut() {
+            let new_link = next_node.link.xor(&prev).xor(&node);
+            next_node.link = new_link;
+        }
+
+        return node;
+    }
+
+    /**
+     * Inserts the given list at the cursor location, like `splice`, but leaves the cursor after
+     * the last inserted element, so that repeated calls insert lists in order.
+     */
+    pub fn splice_before(&mut self, list: XorList<T, A>) {
+        let last = list.back_node();
+        if last.is_null() { return; }
+
+        let curr = self.curr.get();
+        let len = list.len;
+        self.splice(list);
+
+        self.prev.set(last);
+        self.curr.set(curr);
+        self.index.set(self.index.get() + len);
+        debug_assert!(unsafe { (*self.list).is_consistent() });
+    }
+
+    /**
+     * Inserts the given list at the cursor location. The cursor will be placed before the first
+     * inserted element.
+     *
+     * Panics if the list uses a different allocator from the one the cursor is in.
+     */
+    pub fn splice(&mut self, mut list: XorList<T, A>) {
+        unsafe {
+            (*self.list).check_allocator(&list.alloc);
+
+            // Given list is empty
+            if list.head.is_null() { return; }
+
+            // Only a single node in the given list
+            if list.tail.is_null() {
+                let node = mem::replace(&mut list.head, Raw::null());
+                list.len = 0;
+
+                let prev = self.prev.get();
+                let curr = self.curr.get();
+
+                let node = self.insert_between(prev, curr, node);
+                self.curr.set(node);
+                (*self.list).len += 1;
+
+                // Fix-up the head/tail references in the list
+                if prev.is_null() {
+                    (*self.list).head = node;
+                    // We were a single-element list, so the old head is now the tail
+                    if !curr.is_null() && (*self.list).tail.is_null() {
+                        (*self.list).tail = curr;
+                    }
+                } else if curr.is_null() {
+                    (*self.list).tail = node;
+                }
+
+                debug_assert!((*self.list).is_consistent());
+                return;
+            }
+
+            // This list we have is actually empty, just move the
+            // head/tail pointers over
+            if (*self.list).is_empty() {
+                (*self.list).head = list.head;
+                (*self.list).tail = list.tail;
+                (*self.list).len = list.len;
+                list.head = Raw::null();
+                list.tail = Raw::null();
+                list.len = 0;
+
+                self.prev.set(Raw::null());
+                self.curr.set((*self.list).head);
+
+                debug_assert!((*self.list).is_consistent());
+                return;
+            }
+
+            let mut head = mem::replace(&mut list.head, Raw::null());
+            let mut tail = mem::replace(&mut list.tail, Raw::null());
+
+            (*self.list).len += list.len;
+            list.len = 0;
+
+            let mut prev = self.prev.get();
+            let mut curr = self.curr.get();
+
+            {
+                let head_node = head.as_mut().unwrap();
+                head_node.link = head_node.link.xor(&prev);
+            }
+            {
+                let tail_node = tail.as_mut().unwrap();
+                tail_node.link = tail_node.link.xor(&curr);
+            }
+
+            if let Some(prev_node) = prev.as_mut() {
+                prev_node.link = prev_node.link.xor(&curr).xor(&head);
+            } else {
+                (*self.list).head = head;
+            }
+
+            if let Some(curr_node) = curr.as_mut() {
+                curr_node.link = curr_node.link.xor(&prev).xor(&tail);
+                // We were a single-element list, so the old head is now the tail
+                if (*self.list).tail.is_null() {
+                    (*self.list).tail = curr;
+                }
+            } else {
+                (*self.list).tail = tail;
+            }
+
+            self.curr.set(head);
+            debug_assert!((*self.list).is_consistent());
+        }
+    }
+
+    /**
+     * Splits the list at the cursor returning the remaining elements in a new list
+     */
+    pub fn split(&mut self) -> XorList<T, A> {
+        unsafe {
+            let mut new_list = XorList::new_in((*self.list).alloc.clone());
+
+            // We're at the end of the list, so return the empty list
+            if self.curr.get().is_null() {
+                return new_list;
+            }
+
+            // We're at start end of the list, so move the current list
+            // over to the new one
+            if self.prev.get().is_null() {
+                self.curr.set(Raw::null());
+
+                (*self.list).swap_nodes(&mut new_list);
+                debug_assert!(new_list.is_consistent());
+                return new_list;
+            }
+
+            // We're somewhere in the middle
+            let prev = self.prev.get();
+            let curr = self.curr.get();
+            self.curr.set(Raw::null());
+
+            // Everything before the cursor stays, and the cursor knows how much that is
+            let at = self.index.get();
+            let new_list = (*self.list).split_nodes(prev, curr, at);
+            debug_assert!((*self.list).is_consistent() && new_list.is_consistent());
+            return new_list;
+        }
+    }
+
+    /**
+     * Splits the list at the cursor returning the elements before the cursor in a new list. The
+     * cursor is left at the start of its list, which keeps the elements after it.
+     */
+    pub fn split_before(&mut self) -> XorList<T, A> {
+        unsafe {
+            let mut new_list = XorList::new_in((*self.list).alloc.clone());
+
+            // We're at the start of the list, so return the empty list
+            if self.prev.get().is_null() {
+                return new_list;
+            }
+
+            // We're at the end of the list, so move the current list
+            // over to the new one
+            if self.curr.get().is_null() {
+                self.prev.set(Raw::null());
+                self.index.set(0);
+
+                (*self.list).swap_nodes(&mut new_list);
+                debug_assert!(new_list.is_consistent());
+                return new_list;
+            }
+
+            // We're somewhere in the middle
+            let prev = self.prev.get();
+            let curr = self.curr.get();
+
+            // Split off the end of the list, then swap it back in so this list keeps it. The
+            // cursor's index is the number of elements being moved, so nothing has to be walked.
+            let at = self.index.get();
+            let mut new_list = (*self.list).split_nodes(prev, curr, at);
+            (*self.list).swap_nodes(&mut new_list);
+
+            self.prev.set(Raw::null());
+            self.curr.set((*self.list).head);
+            self.index.set(0);
+            debug_assert!((*self.list).is_consistent() && new_list.is_consistent());
+            return new_list;
+        }
+    }
+}
+
+impl<U: ?Sized, T: Unsize<U>> iter::FromIterator<T> for XorList<U> {
+    fn from_iter<I>(iter: I) -> XorList<U> where I: IntoIterator<Item=T> {
+        let mut list = XorList::new();
+        list.extend(iter);
+        return list;
+    }
+}
+
+impl<U: ?Sized, T: Unsize<U>, A: Allocator> Extend<T> for XorList<U, A> {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item=T> {
+        for el in iter {
+            self.push_back(el);
+        }
+    }
+}
+
+impl<A: Allocator> XorList<Any, A> {
+    /**
+     * Removes the element at the beginning of the list and, if it is a `U`, returns its value.
+     * If it's some other type, the element is returned in `Err` so that it isn't lost. Returns
+     * `None` if the list is empty.
+     */
+    pub fn pop_front_downcast<U: Any>(&mut self) -> Option<Result<U, Elem<Any, A>>> {
+        self.pop_front().map(|elem| elem.downcast_value())
+    }
+
+    /**
+     * Removes the element at the end of the list and, if it is a `U`, returns its value. See
+     * `pop_front_downcast`.
+     */
+    pub fn pop_back_downcast<U: Any>(&mut self) -> Option<Result<U, Elem<Any, A>>> {
+        self.pop_back().map(|elem| elem.downcast_value())
+    }
+}
+
+impl<T> From<Vec<T>> for XorList<T> {
+    fn from(vec: Vec<T>) -> XorList<T> {
+        let mut list = XorList::new();
+        for val in vec {
+            list.push_back_node(Node::new_sized(val, &Global));
+        }
+        return list;
+    }
+}
+
+impl<T, A: Allocator> From<XorList<T, A>> for Vec<T> {
+    fn from(mut list: XorList<T, A>) -> Vec<T> {
+        let mut vec = Vec::with_capacity(list.len());
+        while let Some(val) = list.pop_front_value() {
+            vec.push(val);
+        }
+        return vec;
+    }
+}
+
+impl<T: ?Sized> Default for XorList<T> {
+    fn default() -> XorList<T> {
+        XorList::new()
+    }
+}
+
+impl<T: Clone, A: Allocator> Clone for XorList<T, A> {
+    fn clone(&self) -> XorList<T, A> {
+        let mut list = XorList::new_in(self.alloc.clone());
+        for el in self.iter() {
+            let node = Node::new_sized(el.clone(), &list.node_alloc());
+            list.push_back_node(node);
+        }
+        return list;
+    }
+
+    /**
+     * Overwrites the contents of this list with a copy of `source`, reusing the existing nodes
+     * where possible.
+     */
+    fn clone_from(&mut self, source: &XorList<T, A>) {
+        while self.len() > source.len() {
+            self.pop_back();
+        }
+
+        let mut src = source.iter();
+        for (dst, src) in self.iter_mut().zip(src.by_ref()) {
+            dst.clone_from(src);
+        }
+
+        for el in src {
+            let node = Node::new_sized(el.clone(), &self.node_alloc());
+            self.push_back_node(node);
+        }
+    }
+}
+
+impl<T: ?Sized + PartialEq, A: Allocator> PartialEq for XorList<T, A> {
+    fn eq(&self, other: &XorList<T, A>) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(a, b)| a == b)
+    }
+}
+
+impl<T: ?Sized + Eq, A: Allocator> Eq for XorList<T, A> {}
+
+impl<'a, T: PartialEq, A: Allocator> PartialEq<&'a [T]> for XorList<T, A> {
+    fn eq(&self, other: &&'a [T]) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(a, b)| a == b)
+    }
+}
+
+impl<T: PartialEq, A: Allocator> PartialEq<Vec<T>> for XorList<T, A> {
+    fn eq(&self, other: &Vec<T>) -> bool {
+        *self == &other[..]
+    }
+}
+
+impl<T: ?Sized + Hash, A: Allocator> Hash for XorList<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for el in self.iter() {
+            el.hash(state);
+        }
+    }
+}
+
+/**
+ * A simple wrapper type for removing elements by value. The element keeps a handle to the
+ * allocator of the list it came from, so it can free its node.
+ *
+ * An element owns its value, so it can only be sent to another thread if the value can:
+ *
+ * ```compile_fail
+ * # use std::rc::Rc;
+ * # use dynalist::xorlist::Elem;
+ * fn assert_send<T: Send>() {}
+ * assert_send::<Elem<Rc<i32>>>();
+ * ```
+ */
+pub struct Elem<T: ?Sized, A: Allocator = Global> {
+    __node: Raw<Node<T>>,
+    alloc: A
+}
+
+// An element's node isn't linked into any list, so the element owns it outright.
+unsafe impl<T: ?Sized + Send, A: Allocator + Send> Send for Elem<T, A> {}
+unsafe impl<T: ?Sized + Sync, A: Allocator + Sync> Sync for Elem<T, A> {}
+
+impl<T: ?Sized, A: Allocator> Elem<T, A> {
+    /**
+     * Returns a reference to the value in the element.
+     */
+    pub fn as_ref<'a>(&'a self) -> &'a T {
+        unsafe { &(*self.__node.ptr()).data }
+    }
+
+    /**
+     * Returns a mutable reference to the value in the element.
+     */
+    pub fn as_mut<'a>(&'a mut self) -> &'a mut T {
+        unsafe { &mut (*self.__node.ptr()).data }
+    }
+
+    /**
+     * Moves the value into a new box, freeing the node it was stored in.
+     */
+    pub fn into_box(self) -> Box<T> {
+        unsafe {
+            let (node, alloc) = self.into_parts();
+
+            let data : *mut T = &mut (*node.ptr()).data;
+            let size = mem::size_of_val(&*data);
+            let align = mem::min_align_of_val(&*data);
+
+            let ptr = if size == 0 {
+                align as *mut u8
+            } else {
+                let ptr = allocate(size, align);
+                assert!(!ptr.is_null(), "Out of memory");
+                ptr
+            };
+            ptr::copy_nonoverlapping(data as *const u8, ptr, size);
+
+            // Build a pointer with the same metadata (length or vtable) as the value
+            let mut val : *mut T = data;
+            *(&mut val as *mut *mut T as *mut *mut u8) = ptr;
+
+            // The value has been moved, so free the node without dropping it
+            Node::dealloc(node, &alloc);
+
+            Box::from_raw(val)
+        }
+    }
+
+    /**
+     * Takes the node out of the element, without freeing it.
+     */
+    fn into_node(self) -> Raw<Node<T>> {
+        self.into_parts().0
+    }
+
+    fn into_parts(self) -> (Raw<Node<T>>, A) {
+        unsafe {
+            let node = self.__node;
+            let alloc = ptr::read(&self.alloc);
+            mem::forget(self);
+            (node, alloc)
+        }
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Drop for Elem<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            Node::free(self.__node, &self.alloc);
+        }
+    }
+}
+
+impl<A: Allocator> Elem<Any, A> {
+    /**
+     * Attempts to downcast the element to a concrete type, moving it into a new box.
+     */
+    pub fn downcast<U: Any>(self) -> Result<Box<U>, Elem<Any, A>> {
+        self.downcast_value().map(|val| box val)
+    }
+
+    fn downcast_value<U: Any>(self) -> Result<U, Elem<Any, A>> {
+        if !(*self).is::<U>() {
+            return Err(self);
+        }
+
+        unsafe {
+            let (node, alloc) = self.into_parts();
+
+            let val = ptr::read(&(*node.ptr()).data as *const Any as *const U);
+
+            // The value has been moved, so free the node without dropping it
+            Node::dealloc(node, &alloc);
+
+            Ok(val)
+        }
+    }
+}
+
+impl<T, A: Allocator> Elem<T, A> {
+    /**
+     * Moves the value out of the element, freeing the node it was stored in.
+     */
+    pub fn into_inner(self) -> T {
+        unsafe {
+            let (node, alloc) = self.into_parts();
+            let val = ptr::read(&(*node.ptr()).data);
+            Node::dealloc(node, &alloc);
+            val
+        }
+    }
+}
+
+impl<T: ?Sized, A: Allocator> ops::Deref for Elem<T, A> {
+    type Target = T;
+
+    fn deref<'a>(&'a self) -> &'a T {
+        self.as_ref()
+    }
+}
+
+impl<T: ?Sized, A: Allocator> ops::DerefMut for Elem<T, A> {
+    fn deref_mut<'a>(&'a mut self) -> &'a mut T {
+        self.as_mut()
+    }
+}
+
+impl<T: ?Sized, A: Allocator> AsRef<T> for Elem<T, A> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized, A: Allocator> AsMut<T> for Elem<T, A> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Borrow<T> for Elem<T, A> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, A: Allocator> fmt::Debug for Elem<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display, A: Allocator> fmt::Display for Elem<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + PartialEq, A: Allocator> PartialEq for Elem<T, A> {
+    fn eq(&self, other: &Elem<T, A>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: ?Sized + PartialEq, A: Allocator> PartialEq<T> for Elem<T, A> {
+    fn eq(&self, other: &T) -> bool {
+        **self == *other
+    }
+}
+
+impl<T: ?Sized + Eq, A: Allocator> Eq for Elem<T, A> {}
+
+impl<T: ?Sized + PartialOrd, A: Allocator> PartialOrd for Elem<T, A> {
+    fn partial_cmp(&self, other: &Elem<T, A>) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: ?Sized + Ord, A: Allocator> Ord for Elem<T, A> {
+    fn cmp(&self, other: &Elem<T, A>) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::prelude::v1::*;
+    use super::*;
+    use std::fmt::{Display, Debug};
+    use super::Node;
+    use heap::{Allocator, Global};
+    use std::any::Any;
+    use std::borrow::Borrow;
+    use std::cell::Cell;
+    use std::cmp::Ordering;
+    use std::hash::{Hash, Hasher, SipHasher};
+    use std::rc::Rc;
+
+    /**
+     * Builds a list of sized values, which can't be pushed with `push_back`.
+     */
+    fn sized_list<T>(values: Vec<T>) -> XorList<T> {
+        XorList::from(values)
+    }
+
+    /**
+     * Counts the number of times it has been dropped.
+     */
+    #[derive(Debug)]
+    struct DropCounter(Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn smoketest() {
+        let mut list : XorList<Display> = XorList::new();
+
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+        list.push_back("None");
+        list.push_back("Some(4)");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "1");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "2");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "3");
+
+        let el = list.pop_back().unwrap();
+        assert_eq!(&el.to_string()[..], "Some(4)");
+
+        list.push_back("Some(5)");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "None");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "Some(5)");
+
+        assert!(list.pop_front().is_none());
+
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn droptest() {
+
+        #[derive(Debug)]
+        struct DropTest;
+        static mut DROP_TEST_COUNT : usize = 0;
+        impl DropTest {
+            fn new() -> DropTest {
+                unsafe {
+                    DROP_TEST_COUNT += 1;
+                }
+                DropTest
+            }
+        }
+        impl Drop for DropTest {
+            fn drop(&mut self) {
+                unsafe {
+                    DROP_TEST_COUNT -= 1;
+                }
+            }
+        }
+
+        {
+
+            let mut list : XorList<Debug> = XorList::new();
+
+            list.push_back(DropTest::new());
+            list.push_back(DropTest::new());
+            list.push_back(DropTest::new());
+
+            unsafe {
+                assert_eq!(DROP_TEST_COUNT, 3);
+            }
+
+        }
+
+        unsafe {
+            assert_eq!(DROP_TEST_COUNT, 0);
+        }
+    }
+
+    #[test]
+    fn iter() {
+        let mut list : XorList<Display> = XorList::new();
+
+        list.push_back(0);
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+        list.push_back(4);
+        list.push_back(5);
+
+        for (i, el) in list.iter().enumerate() {
+            assert_eq!(el.to_string(), i.to_string());
+        }
+    }
+
+    #[test]
+    fn cursor_basic() {
+        let mut list : XorList<Display> = XorList::new();
+
+        list.push_back(0);
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+        list.push_back(4);
+        list.push_back(5);
+
+        {
+            let mut cursor = list.cursor();
+            cursor.remove();
+
+            cursor.next();
+            cursor.next();
+
+            cursor.insert_before(6);
+
+            cursor.remove();
+
+            cursor.insert_after(7);
+        }
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "1");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "2");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "6");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "7");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "4");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "5");
+    }
+
+    #[test]
+    fn cursor_splice() {
+        let mut list : XorList<Display> = XorList::new();
+
+        list.push_back(0);
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+
+        {
+            let mut cursor = list.cursor();
+
+            let mut list : XorList<Display> = XorList::new();
+            list.push_back(4);
+            list.push_back(5);
+            list.push_back(6);
+            list.push_back(7);
+
+
+            cursor.next();
+            cursor.next();
+
+            cursor.splice(list);
+        }
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "0");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "1");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "4");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "5");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "6");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "7");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "2");
+
+        let el = list.pop_front().unwrap();
+        assert_eq!(&el.to_string()[..], "3");
+
+    }
+
+    #[test]
+    fn len() {
+        let mut list : XorList<Display> = XorList::new();
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+
+        list.push_back(1);
+        list.push_back(2);
+        list.push_front(0);
+        assert_eq!(list.len(), 3);
+
+        list.pop_back();
+        assert_eq!(list.len(), 2);
+        list.pop_front();
+        assert_eq!(list.len(), 1);
+        list.pop_front();
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+
+        list.pop_front();
+        assert_eq!(list.len(), 0);
+
+        list.extend(0..5);
+        {
+            let mut cursor = list.cursor();
+            cursor.next();
+            cursor.insert_before(6);
+            cursor.insert_after(7);
+            cursor.next();
+            cursor.remove();
+        }
+        assert_eq!(list.len(), 6);
+        assert_eq!(list.len(), list.iter().count());
+    }
+
+    #[test]
+    fn split_len() {
+        // Split in the middle
+        let mut list : XorList<Display> = (0..6).collect();
+        let mut other = {
+            let mut cursor = list.cursor();
+            cursor.skip_forwards(2);
+            cursor.split()
+        };
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(other.len(), 4);
+        assert_eq!(list.iter().count(), 2);
+        assert_eq!(other.iter().count(), 4);
+
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "1");
+        assert_eq!(&other.pop_front().unwrap().to_string()[..], "2");
+        assert_eq!(&other.pop_back().unwrap().to_string()[..], "5");
+
+        // Split at the head
+        let mut list : XorList<Display> = (0..4).collect();
+        let other = list.cursor().split();
+
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+        assert_eq!(other.len(), 4);
+        assert_eq!(other.iter().count(), 4);
+
+        // Split before the tail
+        let mut list : XorList<Display> = (0..4).collect();
+        let mut other = {
+            let mut cursor = list.cursor();
+            cursor.skip_forwards(3);
+            cursor.split()
+        };
+
+        assert_eq!(list.len(), 3);
+        assert_eq!(other.len(), 1);
+        assert_eq!(list.iter().count(), 3);
+        assert_eq!(other.iter().count(), 1);
+
+        list.push_back(4);
+        other.push_back(5);
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "4");
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "2");
+        assert_eq!(&other.pop_front().unwrap().to_string()[..], "3");
+
+        // Split at the tail
+        let mut list : XorList<Display> = (0..4).collect();
+        let other = {
+            let mut cursor = list.cursor();
+            cursor.seek_to_end();
+            cursor.split()
+        };
+
+        assert_eq!(list.len(), 4);
+        assert_eq!(other.len(), 0);
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn splice_len() {
+        let mut list : XorList<Display> = (0..3).collect();
+        {
+            let mut cursor = list.cursor();
+            cursor.next();
+            cursor.splice((3..6).collect());
+            cursor.splice((6..7).collect());
+            cursor.splice(XorList::new());
+        }
+        assert_eq!(list.len(), 7);
+        assert_eq!(list.iter().count(), 7);
+
+        let mut list : XorList<Display> = XorList::new();
+        list.cursor().splice((0..3).collect());
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.iter().count(), 3);
+
+        let mut list : XorList<Display> = XorList::new();
+        list.push_back(0);
+        list.cursor().splice((1..3).collect());
+        assert_eq!(list.len(), 3);
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "0");
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn front_back() {
+        let mut list : XorList<[i32]> = XorList::new();
+        assert!(list.front().is_none());
+        assert!(list.back().is_none());
+
+        list.push_back([1]);
+        assert_eq!(list.front().unwrap(), &[1][..]);
+        assert_eq!(list.back().unwrap(), &[1][..]);
+
+        list.push_back([2, 2]);
+        assert_eq!(list.front().unwrap(), &[1][..]);
+        assert_eq!(list.back().unwrap(), &[2, 2][..]);
+
+        list.push_front([0, 0, 0]);
+        assert_eq!(list.front().unwrap(), &[0, 0, 0][..]);
+        assert_eq!(list.back().unwrap(), &[2, 2][..]);
+
+        for el in list.iter_mut() {
+            el[0] += 10;
+        }
+        assert_eq!(list.front().unwrap(), &[10, 0, 0][..]);
+        assert_eq!(list.back().unwrap(), &[12, 2][..]);
+
+        list.pop_front();
+        list.pop_front();
+        assert_eq!(list.front().unwrap(), &[12, 2][..]);
+        assert_eq!(list.back().unwrap(), &[12, 2][..]);
+    }
+
+    #[test]
+    fn front_back_mut() {
+        let mut list : XorList<[i32]> = XorList::new();
+        assert!(list.front_mut().is_none());
+        assert!(list.back_mut().is_none());
+
+        list.push_back([1]);
+        list.back_mut().unwrap()[0] = 2;
+        assert_eq!(list.iter().next().unwrap(), &[2][..]);
+        list.front_mut().unwrap()[0] = 3;
+        assert_eq!(list.iter().next().unwrap(), &[3][..]);
+
+        list.push_back([4, 4]);
+        list.push_back([5, 5, 5]);
+
+        list.front_mut().unwrap()[0] = 0;
+        list.back_mut().unwrap()[2] = 0;
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next().unwrap(), &[0][..]);
+        assert_eq!(iter.next().unwrap(), &[4, 4][..]);
+        assert_eq!(iter.next().unwrap(), &[5, 5, 0][..]);
+        assert!(iter.next().is_none());
+    }
+
+    fn to_strings<A: Allocator>(list: &XorList<Display, A>) -> Vec<String> {
+        list.iter().map(|el| el.to_string()).collect()
+    }
+
+    #[test]
+    fn append() {
+        let lens = [(0, 0), (0, 1), (1, 0), (1, 1), (0, 3), (3, 0), (1, 3), (3, 1), (2, 2), (3, 4)];
+        for &(a, b) in lens.iter() {
+            let mut list1 : XorList<Display> = (0..a).collect();
+            let mut list2 : XorList<Display> = (a..a+b).collect();
+
+            list1.append(&mut list2);
+
+            assert_eq!(list1.len(), a + b);
+            assert_eq!(list2.len(), 0);
+            assert!(list2.is_empty());
+            assert!(list2.iter().next().is_none());
+
+            let expected : Vec<String> = (0..a+b).map(|i| i.to_string()).collect();
+            assert_eq!(to_strings(&list1), expected);
+
+            // Walk the links backwards too
+            let mut back = Vec::new();
+            while let Some(el) = list1.pop_back() {
+                back.push(el.to_string());
+            }
+            back.reverse();
+            assert_eq!(back, expected);
+        }
+    }
+
+    #[test]
+    fn prepend() {
+        let lens = [(0, 0), (0, 1), (1, 0), (1, 1), (0, 3), (3, 0), (1, 3), (3, 1), (2, 2), (3, 4)];
+        for &(a, b) in lens.iter() {
+            let mut list1 : XorList<Display> = (b..a+b).collect();
+            let mut list2 : XorList<Display> = (0..b).collect();
+
+            list1.prepend(&mut list2);
+
+            assert_eq!(list1.len(), a + b);
+            assert_eq!(list2.len(), 0);
+            assert!(list2.iter().next().is_none());
+
+            let expected : Vec<String> = (0..a+b).map(|i| i.to_string()).collect();
+            assert_eq!(to_strings(&list1), expected);
+
+            // Alternate popping from each end to check the links in both directions
+            let mut front = Vec::new();
+            let mut back = Vec::new();
+            loop {
+                match list1.pop_front() {
+                    Some(el) => front.push(el.to_string()),
+                    None => break
+                }
+                match list1.pop_back() {
+                    Some(el) => back.push(el.to_string()),
+                    None => break
+                }
+            }
+            back.reverse();
+            front.extend(back.into_iter());
+            assert_eq!(front, expected);
+            assert!(list1.is_empty());
+        }
+    }
+
+    #[test]
+    fn eq() {
+        let a = sized_list(vec![1, 2, 3]);
+        let b = sized_list(vec![1, 2, 3]);
+        let c = sized_list(vec![1, 2]);
+        let d = sized_list(vec![1, 2, 4]);
+        let empty : XorList<i32> = XorList::new();
+
+        assert!(a == b);
+        assert!(a != c);
+        assert!(c != a);
+        assert!(a != d);
+        assert!(a != empty);
+        assert!(empty == XorList::new());
+
+        assert!(a == vec![1, 2, 3]);
+        assert!(a != vec![1, 2]);
+        assert!(a != vec![1, 2, 3, 4]);
+        assert!(c == &[1, 2][..]);
+        assert!(empty == vec![]);
+
+        let mut s1 : XorList<[i32]> = XorList::new();
+        s1.push_back([1, 2]);
+        s1.push_back([3]);
+        let mut s2 : XorList<[i32]> = XorList::new();
+        s2.push_back([1, 2]);
+        assert!(s1 != s2);
+        s2.push_back([3]);
+        assert!(s1 == s2);
+    }
+
+    #[test]
+    fn eq_trait_object() {
+        trait Named {
+            fn name(&self) -> String;
+        }
+        impl PartialEq for Named {
+            fn eq(&self, other: &Named) -> bool {
+                self.name() == other.name()
+            }
+        }
+        struct Dog;
+        impl Named for Dog {
+            fn name(&self) -> String { "dog".to_string() }
+        }
+        struct Cat;
+        impl Named for Cat {
+            fn name(&self) -> String { "cat".to_string() }
+        }
+        impl Named for &'static str {
+            fn name(&self) -> String { self.to_string() }
+        }
+
+        let mut a : XorList<Named> = XorList::new();
+        a.push_back(Dog);
+        a.push_back(Cat);
+
+        let mut b : XorList<Named> = XorList::new();
+        b.push_back("dog");
+        b.push_back("cat");
+
+        let mut c : XorList<Named> = XorList::new();
+        c.push_back("dog");
+
+        assert!(a == b);
+        assert!(a != c);
+        c.push_back(Dog);
+        assert!(a != c);
+    }
+
+    #[test]
+    fn clone() {
+        let list = sized_list(vec![1, 2, 3]);
+        let mut copy = list.clone();
+        assert!(copy == list);
+
+        *copy.front_mut().unwrap() = 10;
+        copy.push_back_node(Node::new_sized(4, &Global));
+
+        assert!(list == vec![1, 2, 3]);
+        assert!(copy == vec![10, 2, 3, 4]);
+
+        let empty : XorList<String> = XorList::new();
+        assert!(empty.clone().is_empty());
+    }
+
+    #[test]
+    fn clone_from() {
+        let source = sized_list(vec![4, 5, 6]);
+
+        // Same length, all of the nodes are reused
+        let mut list = sized_list(vec![1, 2, 3]);
+        let before : Vec<*const i32> = list.iter().map(|el| el as *const i32).collect();
+        list.clone_from(&source);
+        assert!(list == vec![4, 5, 6]);
+        {
+            let cursor = list.cursor();
+            for &ptr in before.iter() {
+                assert_eq!(cursor.next().unwrap() as *const i32, ptr);
+            }
+        }
+
+        // Longer destination, trailing nodes are dropped
+        let mut list = sized_list(vec![1, 2, 3, 4, 5]);
+        let before : Vec<*const i32> = list.iter().map(|el| el as *const i32).collect();
+        list.clone_from(&source);
+        assert!(list == vec![4, 5, 6]);
+        {
+            let cursor = list.cursor();
+            for &ptr in before[..3].iter() {
+                assert_eq!(cursor.next().unwrap() as *const i32, ptr);
+            }
+        }
+
+        // Shorter destination, extra nodes are allocated
+        let mut list = sized_list(vec![1]);
+        let first = list.front().unwrap() as *const i32;
+        list.clone_from(&source);
+        assert!(list == vec![4, 5, 6]);
+        assert_eq!(list.front().unwrap() as *const i32, first);
+    }
+
+    fn hash<T: Hash>(val: &T) -> u64 {
+        let mut state = SipHasher::new();
+        val.hash(&mut state);
+        state.finish()
+    }
+
+    #[test]
+    fn hash_list() {
+        let a = sized_list(vec![1, 2, 3]);
+        let b = sized_list(vec![1, 2, 3]);
+        assert_eq!(hash(&a), hash(&b));
+
+        let prefix = sized_list(vec![1, 2]);
+        assert!(hash(&a) != hash(&prefix));
+
+        let empty : XorList<i32> = XorList::new();
+        assert_eq!(hash(&empty), hash(&XorList::<i32>::new()));
+
+        // Without the length prefix these would feed the same values to the hasher
+        let split1 = sized_list(vec![sized_list(vec![1, 2]), sized_list(vec![3])]);
+        let split2 = sized_list(vec![sized_list(vec![1]), sized_list(vec![2, 3])]);
+        assert!(hash(&split1) != hash(&split2));
+
+        let mut s1 : XorList<[i32]> = XorList::new();
+        s1.push_back([1, 2]);
+        s1.push_back([3]);
+        let mut s2 : XorList<[i32]> = XorList::new();
+        s2.push_back([1, 2]);
+        s2.push_back([3]);
+        assert_eq!(hash(&s1), hash(&s2));
+    }
+
+    #[test]
+    fn default() {
+        #[derive(Default)]
+        struct Queues {
+            ready: XorList<Display>,
+            blocked: XorList<[i32]>
+        }
+
+        let mut queues = Queues::default();
+        assert!(queues.ready.is_empty());
+        assert!(queues.blocked.is_empty());
+
+        queues.ready.push_back(1);
+        queues.blocked.push_back([2]);
+        assert_eq!(&queues.ready.pop_front().unwrap().to_string()[..], "1");
+        assert_eq!(&*queues.blocked.pop_front().unwrap(), &[2][..]);
+    }
+
+    #[test]
+    fn static_list() {
+        use std::cell::UnsafeCell;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::thread;
+
+        // A minimal spin lock, since it needs a `const fn` constructor to go in a static
+        struct Locked<T> {
+            locked: AtomicBool,
+            value: UnsafeCell<T>
+        }
+
+        unsafe impl<T: Send> Sync for Locked<T> {}
+
+        impl<T> Locked<T> {
+            fn with<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+                while self.locked.compare_and_swap(false, true, Ordering::Acquire) {
+                    thread::yield_now();
+                }
+                let res = f(unsafe { &mut *self.value.get() });
+                self.locked.store(false, Ordering::Release);
+                res
+            }
+        }
+
+        static QUEUE: Locked<XorList<[i32]>> = Locked {
+            locked: AtomicBool::new(false),
+            value: UnsafeCell::new(XorList::new())
+        };
+
+        QUEUE.with(|list| {
+            assert!(list.is_empty());
+            list.push_back([0]);
+        });
+
+        let threads : Vec<_> = (1..5).map(|i| {
+            thread::spawn(move || {
+                for j in 0..10 {
+                    QUEUE.with(|list| list.push_back([i, j]));
+                }
+            })
+        }).collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        QUEUE.with(|list| {
+            assert_eq!(list.len(), 41);
+            assert_eq!(&*list.pop_front().unwrap(), &[0][..]);
+
+            // Each thread's elements are still in the order it pushed them
+            let mut next = [0; 5];
+            while let Some(el) = list.pop_front() {
+                let i = el[0] as usize;
+                assert_eq!(el[1], next[i]);
+                next[i] += 1;
+            }
+            assert_eq!(next, [0, 10, 10, 10, 10]);
+
+            // The list gets an id when it's first needed, so saved positions still work
+            list.push_back([1]);
+            let pos = list.cursor().save();
+            assert!(list.cursor_at_pos(pos).is_some());
+            list.pop_front();
+            assert!(list.cursor_at_pos(pos).is_none());
+        });
+    }
+
+    #[test]
+    fn contains() {
+        let empty : XorList<i32> = XorList::new();
+        assert!(!empty.contains(&1));
+
+        let single = sized_list(vec![1]);
+        assert!(single.contains(&1));
+        assert!(!single.contains(&2));
+
+        let list = sized_list(vec![1, 2, 3]);
+        assert!(list.contains(&1));
+        assert!(list.contains(&2));
+        assert!(list.contains(&3));
+        assert!(!list.contains(&4));
+
+        let mut slices : XorList<[i32]> = XorList::new();
+        slices.push_back([1, 2]);
+        slices.push_back([3]);
+        slices.push_back([]);
+        assert!(slices.contains(&[1, 2]));
+        assert!(slices.contains(&[3]));
+        assert!(slices.contains(&[]));
+        assert!(!slices.contains(&[1]));
+    }
+
+    #[test]
+    fn contains_by() {
+        let empty : XorList<Display> = XorList::new();
+        assert!(!empty.contains_by(|_| true));
+
+        let mut list : XorList<Display> = XorList::new();
+        list.push_back(1);
+        assert!(list.contains_by(|el| el.to_string() == "1"));
+        assert!(!list.contains_by(|el| el.to_string() == "2"));
+
+        list.push_back("two");
+        list.push_back(3.5);
+        assert!(list.contains_by(|el| el.to_string() == "1"));
+        assert!(list.contains_by(|el| el.to_string() == "two"));
+        assert!(list.contains_by(|el| el.to_string() == "3.5"));
+        assert!(!list.contains_by(|el| el.to_string() == "3"));
+    }
+
+    #[test]
+    fn retain() {
+        let count = Rc::new(Cell::new(0));
+        let make = |n: i32| -> XorList<(i32, DropCounter)> {
+            sized_list((0..n).map(|i| (i, DropCounter(count.clone()))).collect())
+        };
+
+        // Remove none
+        let mut list = make(5);
+        list.retain(|_| true);
+        assert_eq!(list.len(), 5);
+        assert_eq!(count.get(), 0);
+        assert_eq!(list.iter().map(|el| el.0).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        drop(list);
+        count.set(0);
+
+        // Remove all
+        let mut list = make(5);
+        list.retain(|_| false);
+        assert_eq!(list.len(), 0);
+        assert!(list.front().is_none());
+        assert!(list.back().is_none());
+        assert_eq!(count.get(), 5);
+        list.push_back_node(Node::new_sized((9, DropCounter(count.clone())), &Global));
+        assert_eq!(list.iter().map(|el| el.0).collect::<Vec<_>>(), vec![9]);
+        drop(list);
+        count.set(0);
+
+        // Remove alternating, starting at the head
+        let mut list = make(6);
+        list.retain(|&(i, _)| i % 2 == 1);
+        assert_eq!(count.get(), 3);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.iter().map(|el| el.0).collect::<Vec<_>>(), vec![1, 3, 5]);
+        assert_eq!(list.pop_back().unwrap().0, 5);
+        assert_eq!(list.pop_back().unwrap().0, 3);
+        drop(list);
+        count.set(0);
+
+        // Remove alternating, including the tail
+        let mut list = make(6);
+        list.retain(|&(i, _)| i % 2 == 0);
+        assert_eq!(count.get(), 3);
+        assert_eq!(list.iter().map(|el| el.0).collect::<Vec<_>>(), vec![0, 2, 4]);
+        assert_eq!(list.back().unwrap().0, 4);
+        assert_eq!(list.pop_front().unwrap().0, 0);
+        drop(list);
+        count.set(0);
+
+        // Leave only a single element
+        let mut list = make(3);
+        list.retain(|&(i, _)| i == 1);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.front().unwrap().0, 1);
+        assert_eq!(list.back().unwrap().0, 1);
+        drop(list);
+        assert_eq!(count.get(), 3);
+    }
+
+    #[test]
+    fn retain_mut() {
+        let mut list : XorList<[i32]> = XorList::new();
+        list.push_back([1]);
+        list.push_back([2, 2]);
+        list.push_back([3, 3, 3]);
+        list.push_back([4]);
+
+        list.retain_mut(|el| {
+            el[0] *= 10;
+            el.len() > 1
+        });
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next().unwrap(), &[20, 2][..]);
+        assert_eq!(iter.next().unwrap(), &[30, 3, 3][..]);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn drain() {
+        let mut list : XorList<Display> = (0..5).collect();
+
+        {
+            let mut drain = list.drain();
+            assert_eq!(drain.len(), 5);
+            assert_eq!(&drain.next().unwrap().to_string()[..], "0");
+            assert_eq!(&drain.next_back().unwrap().to_string()[..], "4");
+            assert_eq!(&drain.next().unwrap().to_string()[..], "1");
+            assert_eq!(drain.len(), 2);
+        }
+
+        assert!(list.is_empty());
+        assert_eq!(list.len(), 0);
+
+        // The list can be reused afterwards
+        list.push_back(5);
+        list.push_back(6);
+        let drained : Vec<String> = list.drain().map(|el| el.to_string()).collect();
+        assert_eq!(drained, vec!["5", "6"]);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn drain_drop() {
+        let count = Rc::new(Cell::new(0));
+        let mut list : XorList<Debug> = XorList::new();
+        for _ in 0..5 {
+            list.push_back(DropCounter(count.clone()));
+        }
+
+        {
+            let mut drain = list.drain();
+            let el = drain.next().unwrap();
+            assert_eq!(count.get(), 0);
+            drop(el);
+            assert_eq!(count.get(), 1);
+        }
+
+        assert_eq!(count.get(), 5);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn extract_if() {
+        let mut list : XorList<[i32]> = XorList::new();
+        for i in 0..8 {
+            list.push_back([i]);
+        }
+
+        // Head, middle and tail
+        let extracted : Vec<i32> = list.extract_if(|el| [0, 3, 4, 7].contains(&el[0]))
+                                       .map(|el| el[0]).collect();
+        assert_eq!(extracted, vec![0, 3, 4, 7]);
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.iter().map(|el| el[0]).collect::<Vec<_>>(), vec![1, 2, 5, 6]);
+        assert_eq!(list.front().unwrap(), &[1][..]);
+        assert_eq!(list.back().unwrap(), &[6][..]);
+
+        let mut back = Vec::new();
+        while let Some(el) = list.pop_back() {
+            back.push(el[0]);
+        }
+        assert_eq!(back, vec![6, 5, 2, 1]);
+    }
+
+    #[test]
+    fn extract_if_early_drop() {
+        let mut list : XorList<[i32]> = XorList::new();
+        for i in 0..6 {
+            list.push_back([i]);
+        }
+
+        {
+            let mut iter = list.extract_if(|el| {
+                el[0] += 10;
+                el[0] % 2 == 1
+            });
+            assert_eq!(iter.next().unwrap()[0], 11);
+        }
+
+        // Only the elements visited so far have been touched, and only the yielded one removed
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.iter().map(|el| el[0]).collect::<Vec<_>>(), vec![10, 2, 3, 4, 5]);
+
+        // Extracting everything leaves a usable, empty list
+        assert_eq!(list.extract_if(|_| true).count(), 5);
+        assert!(list.is_empty());
+        list.push_back([1]);
+        assert_eq!(list.front().unwrap(), &[1][..]);
+    }
+
+    #[test]
+    fn split_off() {
+        for len in 0..7 {
+            for at in 0..len+1 {
+                let mut list : XorList<Display> = (0..len).collect();
+                let mut other = list.split_off(at);
+
+                assert_eq!(list.len(), at);
+                assert_eq!(other.len(), len - at);
+
+                let expected : Vec<String> = (0..at).map(|i| i.to_string()).collect();
+                assert_eq!(to_strings(&list), expected);
+                let expected : Vec<String> = (at..len).map(|i| i.to_string()).collect();
+                assert_eq!(to_strings(&other), expected);
+
+                // Both halves should be well-formed at their ends
+                list.push_back("a");
+                other.push_front("b");
+                assert_eq!(&list.pop_back().unwrap().to_string()[..], "a");
+                assert_eq!(&other.pop_front().unwrap().to_string()[..], "b");
+                if at > 0 {
+                    let expected = (at - 1).to_string();
+                    assert_eq!(list.pop_back().unwrap().to_string(), expected);
+                }
+                if at < len {
+                    let expected = (len - 1).to_string();
+                    assert_eq!(other.pop_back().unwrap().to_string(), expected);
+                }
+
+                drop(other);
+                drop(list);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn split_off_out_of_range() {
+        let mut list : XorList<Display> = (0..3).collect();
+        list.split_off(4);
+    }
+
+    #[test]
+    fn truncate() {
+        let count = Rc::new(Cell::new(0));
+        let mut list : XorList<Debug> = XorList::new();
+        for _ in 0..6 {
+            list.push_back(DropCounter(count.clone()));
+        }
+
+        list.truncate(10);
+        assert_eq!(list.len(), 6);
+        list.truncate(6);
+        assert_eq!(list.len(), 6);
+        assert_eq!(count.get(), 0);
+
+        list.truncate(4);
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.iter().count(), 4);
+        assert_eq!(count.get(), 2);
+
+        list.truncate(1);
+        assert_eq!(list.len(), 1);
+        assert_eq!(count.get(), 5);
+
+        list.truncate(0);
+        assert!(list.is_empty());
+        assert_eq!(count.get(), 6);
+
+        let mut list : XorList<Display> = (0..5).collect();
+        list.truncate(3);
+        list.push_back(9);
+        assert_eq!(to_strings(&list), vec!["0", "1", "2", "9"]);
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "9");
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "2");
+    }
+
+    #[test]
+    fn insert() {
+        let mut list : XorList<Display> = XorList::new();
+        list.insert(0, 1);
+        assert_eq!(to_strings(&list), vec!["1"]);
+
+        list.insert(0, 0);
+        assert_eq!(to_strings(&list), vec!["0", "1"]);
+
+        list.insert(2, 3);
+        assert_eq!(to_strings(&list), vec!["0", "1", "3"]);
+
+        list.insert(2, 2);
+        list.insert(4, 5);
+        list.insert(4, 4);
+        assert_eq!(to_strings(&list), vec!["0", "1", "2", "3", "4", "5"]);
+        assert_eq!(list.len(), 6);
+
+        list.insert(1, "a");
+        list.insert(5, "b");
+        assert_eq!(to_strings(&list), vec!["0", "a", "1", "2", "3", "b", "4", "5"]);
+
+        let mut back = Vec::new();
+        while let Some(el) = list.pop_back() {
+            back.push(el.to_string());
+        }
+        assert_eq!(back, vec!["5", "4", "b", "3", "2", "1", "a", "0"]);
+
+        // Inserting at the end of a single-element list
+        let mut list : XorList<Display> = XorList::new();
+        list.insert(0, 0);
+        list.insert(1, 1);
+        assert_eq!(to_strings(&list), vec!["0", "1"]);
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "1");
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "0");
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_out_of_range() {
+        let mut list : XorList<Display> = (0..3).collect();
+        list.insert(4, 4);
+    }
+
+    #[test]
+    fn remove_at() {
+        let mut list : XorList<Display> = (0..7).collect();
+        assert_eq!(&list.remove_at(3).to_string()[..], "3");
+        assert_eq!(&list.remove_at(1).to_string()[..], "1");
+        assert_eq!(&list.remove_at(3).to_string()[..], "5");
+        assert_eq!(to_strings(&list), vec!["0", "2", "4", "6"]);
+        assert!(list.try_remove_at(4).is_none());
+        assert_eq!(list.len(), 4);
+
+        let count = Rc::new(Cell::new(0));
+        let mut list : XorList<Debug> = XorList::new();
+        for _ in 0..5 {
+            list.push_back(DropCounter(count.clone()));
+        }
+        for i in 0..5 {
+            list.remove_at(0);
+            assert_eq!(count.get(), i + 1);
+            assert_eq!(list.len(), 4 - i);
+            assert_eq!(list.iter().count(), 4 - i);
+        }
+        assert!(list.is_empty());
+        assert!(list.try_remove_at(0).is_none());
+
+        count.set(0);
+        for _ in 0..5 {
+            list.push_back(DropCounter(count.clone()));
+        }
+        for i in 0..5 {
+            let last = list.len() - 1;
+            list.remove_at(last);
+            assert_eq!(count.get(), i + 1);
+            assert_eq!(list.iter().count(), 4 - i);
+        }
+        assert!(list.is_empty());
+
+        list.push_back(DropCounter(count.clone()));
+        assert_eq!(list.len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn remove_at_out_of_range() {
+        let mut list : XorList<Display> = (0..3).collect();
+        list.remove_at(3);
+    }
+
+    #[test]
+    fn get() {
+        let mut list : XorList<[i32]> = XorList::new();
+        assert!(list.get(0).is_none());
+        assert!(list.get_mut(0).is_none());
+
+        for i in 0..5 {
+            list.push_back([i, i]);
+        }
+
+        for i in 0..5 {
+            assert_eq!(list.get(i as usize).unwrap(), &[i, i][..]);
+        }
+        assert!(list.get(5).is_none());
+        assert!(list.get(100).is_none());
+
+        list.get_mut(0).unwrap()[0] = 10;
+        list.get_mut(3).unwrap()[1] = 13;
+        list.get_mut(4).unwrap()[0] = 14;
+        assert!(list.get_mut(5).is_none());
+
+        let values : Vec<Vec<i32>> = list.iter().map(|el| el.to_vec()).collect();
+        assert_eq!(values, vec![vec![10, 0], vec![1, 1], vec![2, 2], vec![3, 13], vec![14, 4]]);
+
+        let mut list : XorList<Display> = XorList::new();
+        list.push_back("a");
+        assert_eq!(&list.get(0).unwrap().to_string()[..], "a");
+        assert!(list.get(1).is_none());
+    }
+
+    /**
+     * A simple deterministic shuffle, using a linear congruential generator.
+     */
+    fn shuffle<T>(values: &mut [T], mut seed: u32) {
+        for i in (1..values.len()).rev() {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let j = (seed >> 16) as usize % (i + 1);
+            values.swap(i, j);
+        }
+    }
+
+    #[test]
+    fn sort() {
+        let mut list : XorList<i32> = XorList::new();
+        list.sort();
+        assert!(list.is_empty());
+
+        for len in 1..40 {
+            let mut expected : Vec<i32> = (0..len).collect();
+
+            let mut reversed = sized_list(expected.iter().cloned().rev().collect());
+            reversed.sort();
+            assert!(reversed == expected);
+
+            let mut sorted = sized_list(expected.clone());
+            sorted.sort();
+            assert!(sorted == expected);
+
+            let mut equal = sized_list(vec![7; len as usize]);
+            equal.sort();
+            assert!(equal == vec![7; len as usize]);
+
+            let mut values = expected.clone();
+            shuffle(&mut values, len as u32);
+            let mut shuffled = sized_list(values);
+            shuffled.sort();
+            assert!(shuffled == expected);
+            assert_eq!(shuffled.len(), len as usize);
+
+            // Make sure the links are consistent in both directions
+            let mut back = Vec::new();
+            while let Some(el) = shuffled.pop_back() {
+                back.push(*el);
+            }
+            expected.reverse();
+            assert_eq!(back, expected);
+        }
+    }
+
+    #[test]
+    fn sort_by() {
+        let mut list = sized_list(vec![3, 1, 2]);
+        list.sort_by(|a, b| b.cmp(a));
+        assert!(list == vec![3, 2, 1]);
+
+        let mut list : XorList<[i32]> = XorList::new();
+        list.push_back([1, 2, 3]);
+        list.push_back([4]);
+        list.push_back([5, 6]);
+        list.sort_by_key(|el| el.len());
+        let values : Vec<Vec<i32>> = list.iter().map(|el| el.to_vec()).collect();
+        assert_eq!(values, vec![vec![4], vec![5, 6], vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn sort_stable() {
+        let mut pairs : Vec<(i32, usize)> = (0..100).map(|i| (i % 7, 0)).collect();
+        shuffle(&mut pairs, 42);
+        for (i, pair) in pairs.iter_mut().enumerate() {
+            pair.1 = i;
+        }
+
+        let mut list = sized_list(pairs.clone());
+        list.sort_by_key(|&(key, _)| key);
+
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        assert!(list == pairs);
+    }
+
+    #[test]
+    fn merge() {
+        let cases : Vec<(Vec<i32>, Vec<i32>)> = vec![
+            (vec![], vec![]),
+            (vec![1, 2, 3], vec![]),
+            (vec![], vec![1, 2, 3]),
+            (vec![1], vec![2]),
+            (vec![2], vec![1]),
+            (vec![1, 3, 5, 7], vec![2, 4, 6]),
+            (vec![1, 2, 3], vec![4, 5, 6, 7, 8]),
+            (vec![4, 5, 6, 7, 8], vec![1, 2, 3]),
+            (vec![1, 1, 2, 9, 9], vec![1, 2, 2, 3, 10, 11])
+        ];
+
+        for &(ref a, ref b) in cases.iter() {
+            let mut expected = a.clone();
+            expected.extend(b.iter().cloned());
+            expected.sort();
+
+            let mut list = sized_list(a.clone());
+            list.merge(sized_list(b.clone()));
+
+            assert!(list == expected);
+            assert_eq!(list.len(), a.len() + b.len());
+
+            let mut back = Vec::new();
+            while let Some(el) = list.pop_back() {
+                back.push(*el);
+            }
+            back.reverse();
+            assert_eq!(back, expected);
+        }
+    }
+
+    #[test]
+    fn merge_stable() {
+        let mut list = sized_list(vec![(1, 'a'), (2, 'a'), (2, 'b'), (4, 'a')]);
+        let other = sized_list(vec![(1, 'c'), (2, 'c'), (3, 'c'), (4, 'c'), (5, 'c')]);
+
+        list.merge_by(other, |a, b| a.0.cmp(&b.0));
+
+        assert!(list == vec![(1, 'a'), (1, 'c'), (2, 'a'), (2, 'b'), (2, 'c'), (3, 'c'),
+                             (4, 'a'), (4, 'c'), (5, 'c')]);
+
+        let count = Rc::new(Cell::new(0));
+        {
+            let mut list = sized_list(vec![(1, DropCounter(count.clone())),
+                                           (3, DropCounter(count.clone()))]);
+            let other = sized_list(vec![(2, DropCounter(count.clone()))]);
+            list.merge_by(other, |a, b| a.0.cmp(&b.0));
+            assert_eq!(count.get(), 0);
+            assert_eq!(list.len(), 3);
+        }
+        assert_eq!(count.get(), 3);
+    }
+
+    #[test]
+    fn dedup() {
+        let mut list : XorList<i32> = XorList::new();
+        list.dedup();
+        assert!(list.is_empty());
+
+        let mut list = sized_list(vec![1]);
+        list.dedup();
+        assert!(list == vec![1]);
+
+        let mut list = sized_list(vec![3, 3, 3, 3]);
+        list.dedup();
+        assert!(list == vec![3]);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.back(), Some(&3));
+
+        let mut list = sized_list(vec![1, 1, 2, 3, 3, 1, 4, 4, 4]);
+        list.dedup();
+        assert!(list == vec![1, 2, 3, 1, 4]);
+        assert_eq!(list.back(), Some(&4));
+        list.push_back_node(Node::new_sized(5, &Global));
+        assert_eq!(*list.pop_back().unwrap(), 5);
+        assert_eq!(*list.pop_back().unwrap(), 4);
+
+        let mut list : XorList<[i32]> = XorList::new();
+        list.push_back([1, 2]);
+        list.push_back([1, 2]);
+        list.push_back([1]);
+        list.push_back([1, 2]);
+        list.dedup();
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn dedup_by() {
+        let count = Rc::new(Cell::new(0));
+        {
+            let values = vec![1, 1, 2, 2, 2, 3, 4, 4];
+            let mut list = sized_list(values.into_iter().map(|i| (i, 0, DropCounter(count.clone())))
+                                            .collect());
+
+            // Count the removed duplicates on the element that's kept
+            list.dedup_by(|a, b| {
+                if a.0 == b.0 {
+                    b.1 += 1;
+                    true
+                } else {
+                    false
+                }
+            });
+
+            assert_eq!(count.get(), 4);
+            assert_eq!(list.len(), 4);
+            let values : Vec<(i32, i32)> = list.iter().map(|&(a, b, _)| (a, b)).collect();
+            assert_eq!(values, vec![(1, 1), (2, 2), (3, 0), (4, 1)]);
+        }
+        assert_eq!(count.get(), 8);
+    }
+
+    #[test]
+    fn reverse() {
+        let mut list : XorList<Display> = XorList::new();
+        list.reverse();
+        assert!(list.is_empty());
+
+        list.push_back(0);
+        list.reverse();
+        assert_eq!(to_strings(&list), vec!["0"]);
+        list.push_back(1);
+        assert_eq!(to_strings(&list), vec!["0", "1"]);
+        list.reverse();
+        assert_eq!(to_strings(&list), vec!["1", "0"]);
+
+        list.push_back(2);
+        list.push_back(3);
+        list.reverse();
+        assert_eq!(to_strings(&list), vec!["3", "2", "0", "1"]);
+        list.reverse();
+        assert_eq!(to_strings(&list), vec!["1", "0", "2", "3"]);
+
+        list.reverse();
+        list.push_front(4);
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "1");
+        list.reverse();
+        list.push_front(5);
+        assert_eq!(to_strings(&list), vec!["5", "0", "2", "3", "4"]);
+        assert_eq!(&list.pop_back().unwrap().to_string()[..], "4");
+        list.reverse();
+        assert_eq!(to_strings(&list), vec!["3", "2", "0", "5"]);
+        assert_eq!(list.len(), 4);
+    }
+
+    #[test]
+    fn rotate() {
+        let mut list : XorList<i32> = XorList::new();
+        list.rotate_left(3);
+        list.rotate_right(3);
+        assert!(list.is_empty());
+
+        let mut list = sized_list(vec![1]);
+        list.rotate_left(3);
+        list.rotate_right(5);
+        assert!(list == vec![1]);
+
+        for len in 2..8 {
+            for n in 0..len * 2 + 1 {
+                let values : Vec<i32> = (0..len as i32).collect();
+
+                let mut expected = values.clone();
+                let split = n % len;
+                let mut left = expected.split_off(split);
+                left.extend(expected.into_iter());
+
+                let mut list = sized_list(values.clone());
+                list.rotate_left(n);
+                assert!(list == left);
+                list.push_back_node(Node::new_sized(100, &Global));
+                assert_eq!(*list.pop_back().unwrap(), 100);
+                assert_eq!(*list.pop_back().unwrap(), *left.last().unwrap());
+
+                let mut expected = values.clone();
+                let split = (len - n % len) % len;
+                let mut right = expected.split_off(split);
+                right.extend(expected.into_iter());
+
+                let mut list = sized_list(values.clone());
+                list.rotate_right(n);
+                assert!(list == right);
+                list.push_front_node(Node::new_sized(100, &Global));
+                assert_eq!(*list.pop_front().unwrap(), 100);
+                assert_eq!(*list.pop_front().unwrap(), right[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn swap() {
+        for len in 1..7 {
+            for i in 0..len {
+                for j in 0..len {
+                    let mut expected : Vec<usize> = (0..len).collect();
+                    expected.swap(i, j);
+
+                    let mut list = sized_list((0..len).collect());
+                    let first = list.front().unwrap() as *const usize;
+                    list.swap(i, j);
+
+                    assert!(list == expected);
+                    assert_eq!(list.len(), len);
+
+                    // The nodes themselves should have moved, not their contents
+                    let moved = list.get(if i == 0 { j } else if j == 0 { i } else { 0 }).unwrap();
+                    assert_eq!(moved as *const usize, first);
+
+                    let mut back = Vec::new();
+                    while let Some(el) = list.pop_back() {
+                        back.push(*el);
+                    }
+                    back.reverse();
+                    assert_eq!(back, expected);
+                }
+            }
+        }
+
+        let mut list : XorList<Display> = (0..4).collect();
+        list.swap(3, 2);
+        list.swap(0, 3);
+        assert_eq!(to_strings(&list), vec!["2", "1", "3", "0"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn swap_out_of_range() {
+        let mut list : XorList<Display> = (0..3).collect();
+        list.swap(0, 3);
+    }
+
+    #[test]
+    fn partition() {
+        let list : XorList<Display> = (0..9).collect();
+        let (even, odd) = list.partition(|el| el.to_string().parse::<i32>().unwrap() % 2 == 0);
+        assert_eq!(to_strings(&even), vec!["0", "2", "4", "6", "8"]);
+        assert_eq!(to_strings(&odd), vec!["1", "3", "5", "7"]);
+        assert_eq!(even.len() + odd.len(), 9);
+
+        let list : XorList<Display> = (0..3).collect();
+        let (all, none) = list.partition(|_| true);
+        assert_eq!(all.len(), 3);
+        assert!(none.is_empty());
+
+        let count = Rc::new(Cell::new(0));
+        {
+            let values = (0..10).map(|i| (i, DropCounter(count.clone()))).collect();
+            let list = sized_list(values);
+            let nodes : Vec<*const (i32, DropCounter)> = list.iter().map(|el| el as *const _)
+                                                             .collect();
+
+            let (mut small, big) = list.partition(|&(i, _)| i < 4);
+            assert_eq!(count.get(), 0);
+            assert_eq!(small.len(), 4);
+            assert_eq!(big.len(), 6);
+
+            // No element has moved in memory
+            let after : Vec<*const (i32, DropCounter)> = small.iter().chain(big.iter())
+                                                                .map(|el| el as *const _).collect();
+            assert_eq!(after, nodes);
+
+            assert_eq!(small.pop_back().unwrap().0, 3);
+            assert_eq!(count.get(), 1);
+        }
+        assert_eq!(count.get(), 10);
+    }
+
+    #[test]
+    fn push_boxed() {
+        let mut list : XorList<Display> = XorList::new();
+        let boxes : Vec<Box<Display>> = vec![box 1, box "two", box 3.5f64, box 'c'];
+        for b in boxes.into_iter() {
+            list.push_back_boxed(b);
+        }
+        list.push_front_boxed(box 0u8);
+        list.push_back(5);
+
+        assert_eq!(to_strings(&list), vec!["0", "1", "two", "3.5", "c", "5"]);
+
+        let mut list : XorList<[u8]> = XorList::new();
+        let buffer : Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+        list.push_back_boxed(buffer.clone().into_boxed_slice());
+        list.push_front_boxed(vec![].into_boxed_slice());
+        list.push_back_boxed(vec![1, 2, 3].into_boxed_slice());
+
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.get(0).unwrap(), &[][..]);
+        assert_eq!(list.get(1).unwrap(), &buffer[..]);
+        assert_eq!(list.get(2).unwrap(), &[1, 2, 3][..]);
+
+        let mut list : XorList<[u64]> = XorList::new();
+        list.push_back_boxed(vec![1, 2, 3].into_boxed_slice());
+        assert_eq!(list.front().unwrap(), &[1, 2, 3][..]);
+    }
+
+    #[test]
+    fn push_boxed_drop() {
+        let count = Rc::new(Cell::new(0));
+        {
+            let mut list : XorList<Debug> = XorList::new();
+            let b : Box<Debug> = box DropCounter(count.clone());
+            list.push_back_boxed(b);
+            let b : Box<Debug> = box (1u8, DropCounter(count.clone()));
+            list.push_front_boxed(b);
+            assert_eq!(count.get(), 0);
+
+            list.pop_back();
+            assert_eq!(count.get(), 1);
+        }
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn push_elem() {
+        let mut list1 : XorList<Display> = (0..4).collect();
+        let mut list2 : XorList<Display> = XorList::new();
+
+        let addr = |el: &Display| el as *const Display as *const () as usize;
+        let addrs : Vec<usize> = list1.iter().map(|el| addr(el)).collect();
+
+        let el = list1.pop_front().unwrap();
+        list2.push_back_elem(el);
+        let el = list1.pop_back().unwrap();
+        list2.push_front_elem(el);
+        let el = list1.pop_front().unwrap();
+        list2.push_back_elem(el);
+
+        assert_eq!(to_strings(&list1), vec!["2"]);
+        assert_eq!(to_strings(&list2), vec!["3", "0", "1"]);
+        assert_eq!(list1.len(), 1);
+        assert_eq!(list2.len(), 3);
+
+        // The nodes were reused
+        let moved : Vec<usize> = list2.iter().map(|el| addr(el)).collect();
+        assert_eq!(moved, vec![addrs[3], addrs[0], addrs[1]]);
+
+        let count = Rc::new(Cell::new(0));
+        {
+            let mut list1 : XorList<Debug> = XorList::new();
+            let mut list2 : XorList<Debug> = XorList::new();
+            list1.push_back(DropCounter(count.clone()));
+            list1.push_back(DropCounter(count.clone()));
+
+            while let Some(el) = list1.pop_back() {
+                list2.push_front_elem(el);
+            }
+            assert_eq!(count.get(), 0);
+            assert_eq!(list2.len(), 2);
+        }
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn pop_value() {
+        let mut list = sized_list(vec![String::from("a"), String::from("b"), String::from("c")]);
+        assert_eq!(list.pop_front_value(), Some(String::from("a")));
+        assert_eq!(list.pop_back_value(), Some(String::from("c")));
+        assert_eq!(list.pop_back_value(), Some(String::from("b")));
+        assert_eq!(list.pop_back_value(), None);
+        assert_eq!(list.pop_front_value(), None);
+        assert!(list.is_empty());
+
+        let count = Rc::new(Cell::new(0));
+        let mut list = sized_list(vec![DropCounter(count.clone()), DropCounter(count.clone())]);
+
+        let value = list.pop_front_value().unwrap();
+        assert_eq!(count.get(), 0);
+        assert_eq!(list.len(), 1);
+        drop(value);
+        assert_eq!(count.get(), 1);
+
+        let elem = list.pop_back().unwrap();
+        let value = elem.into_inner();
+        assert_eq!(count.get(), 1);
+        assert!(list.is_empty());
+        drop(value);
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn pop_value_frees_node_first() {
+        // Records how many nodes had been freed when it was dropped
+        struct FreesAtDrop<'a>(&'a CountingAlloc, &'a Cell<usize>);
+        impl<'a> Drop for FreesAtDrop<'a> {
+            fn drop(&mut self) {
+                self.1.set(self.0.frees.get());
+            }
+        }
+
+        let counter = CountingAlloc::new();
+        let front_seen = Cell::new(0);
+        let back_seen = Cell::new(0);
+        let inner_seen = Cell::new(0);
+        let mut list = XorList::new_in(&counter);
+        for seen in [&front_seen, &back_seen, &inner_seen].iter() {
+            let node = Node::new_sized(FreesAtDrop(&counter, *seen), list.allocator());
+            list.push_back_node(node);
+        }
+        assert_eq!(counter.frees.get(), 0);
+
+        // Each node is freed as soon as its value has been moved out, so the allocator has
+        // already seen the free by the time the value is dropped
+        let value = list.pop_front_value().unwrap();
+        assert_eq!(counter.frees.get(), 1);
+        drop(value);
+        assert_eq!(front_seen.get(), 1);
+
+        let value = list.pop_back_value().unwrap();
+        assert_eq!(counter.frees.get(), 2);
+        drop(value);
+        assert_eq!(back_seen.get(), 2);
+
+        let value = list.pop_back().unwrap().into_inner();
+        assert_eq!(counter.frees.get(), 3);
+        drop(value);
+        assert_eq!(inner_seen.get(), 3);
+    }
+
+    #[test]
+    fn elem_into_box() {
+        let count = Rc::new(Cell::new(0));
+
+        let mut list = sized_list(vec![(1, DropCounter(count.clone())),
+                                       (2, DropCounter(count.clone()))]);
+        let b : Box<(i32, DropCounter)> = list.pop_front().unwrap().into_box();
+        assert_eq!(b.0, 1);
+        assert_eq!(count.get(), 0);
+        drop(b);
+        assert_eq!(count.get(), 1);
+        drop(list);
+        assert_eq!(count.get(), 2);
+
+        count.set(0);
+        let mut list : XorList<Debug> = XorList::new();
+        list.push_back(DropCounter(count.clone()));
+        list.push_back(());
+        let b = list.pop_front().unwrap().into_box();
+        assert_eq!(count.get(), 0);
+        drop(b);
+        assert_eq!(count.get(), 1);
+        let b = list.pop_front().unwrap().into_box();
+        assert_eq!(&format!("{:?}", b)[..], "()");
+
+        let mut list : XorList<Display> = XorList::new();
+        list.push_back(1);
+        list.push_back("two");
+        let b : Box<Display> = list.pop_back().unwrap().into_box();
+        assert_eq!(&b.to_string()[..], "two");
+
+        // Round-trip through a box
+        list.push_back_boxed(b);
+        assert_eq!(to_strings(&list), vec!["1", "two"]);
+
+        let mut list : XorList<[u16]> = XorList::new();
+        list.push_back([1, 2, 3]);
+        list.push_back([]);
+        assert_eq!(&*list.pop_front().unwrap().into_box(), &[1, 2, 3][..]);
+        assert_eq!(&*list.pop_front().unwrap().into_box(), &[][..]);
+    }
+
+    #[test]
+    fn elem_as_ref() {
+        let mut list : XorList<[i32]> = XorList::new();
+        list.push_back([1, 2]);
+        let mut el = list.pop_front().unwrap();
+        el.as_mut()[0] = 5;
+        assert_eq!(el.as_ref(), &[5, 2][..]);
+    }
+
+    #[test]
+    fn elem_traits() {
+        let mut list = sized_list(vec![1, 2, 2]);
+        let a = list.pop_front().unwrap();
+        let b = list.pop_front().unwrap();
+        let c = list.pop_front().unwrap();
+
+        assert_eq!(a, 1);
+        assert_eq!(b, c);
+        assert!(a != b);
+        assert!(a < b);
+        assert!(b <= c);
+        assert_eq!(a.cmp(&b), Ordering::Less);
+        assert_eq!(&format!("{:?}", a)[..], "1");
+        assert_eq!(&format!("{}", b)[..], "2");
+
+        let r : &i32 = AsRef::as_ref(&a);
+        assert_eq!(*r, 1);
+        let r : &i32 = a.borrow();
+        assert_eq!(*r, 1);
+
+        let mut list : XorList<[i32]> = XorList::new();
+        list.push_back([1, 2]);
+        list.push_back([1, 3]);
+        let a = list.pop_front().unwrap();
+        let b = list.pop_front().unwrap();
+        assert!(a == [1, 2][..]);
+        assert!(a != b);
+        assert!(a < b);
+        assert_eq!(&format!("{:?}", b)[..], "[1, 3]");
+
+        let mut list : XorList<Display> = XorList::new();
+        list.push_back("string");
+        list.push_back(1.5);
+        assert_eq!(&format!("{}", list.pop_front().unwrap())[..], "string");
+        assert_eq!(&format!("{:>5}", list.pop_front().unwrap())[..], "  1.5");
+
+        let mut list : XorList<Debug> = XorList::new();
+        list.push_back(Some("x"));
+        assert_eq!(&format!("{:?}", list.pop_front().unwrap())[..], "Some(\"x\")");
+    }
+
+    #[test]
+    fn downcast() {
+        let mut list : XorList<Any> = XorList::new();
+        list.push_back(1i32);
+        list.push_back("two");
+        list.push_back(String::from("three"));
+        list.push_back(4.0f64);
+
+        assert_eq!(list.pop_front_downcast::<i32>().unwrap().unwrap(), 1);
+
+        // A failed downcast hands the element back
+        let el = list.pop_front_downcast::<String>().unwrap().unwrap_err();
+        assert_eq!(list.len(), 2);
+        list.push_front_elem(el);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_front_downcast::<&'static str>().unwrap().unwrap(), "two");
+
+        let el = list.pop_back_downcast::<f32>().unwrap().unwrap_err();
+        assert_eq!(el.downcast::<f64>().unwrap(), box 4.0);
+
+        let el = list.pop_back().unwrap();
+        let el = el.downcast::<i32>().unwrap_err();
+        assert_eq!(&*el.downcast::<String>().unwrap(), "three");
+
+        assert!(list.pop_front_downcast::<i32>().is_none());
+        assert!(list.pop_back_downcast::<i32>().is_none());
+
+        let count = Rc::new(Cell::new(0));
+        {
+            let mut list : XorList<Any> = XorList::new();
+            list.push_back(DropCounter(count.clone()));
+            list.push_back(DropCounter(count.clone()));
+
+            let val = list.pop_front_downcast::<DropCounter>().unwrap().unwrap();
+            assert_eq!(count.get(), 0);
+            drop(val);
+            assert_eq!(count.get(), 1);
+
+            let el = list.pop_front_downcast::<i32>().unwrap().unwrap_err();
+            assert_eq!(count.get(), 1);
+            list.push_back_elem(el);
+        }
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn vec_conversions() {
+        let list = XorList::from(vec![1, 2, 3]);
+        assert_eq!(list.len(), 3);
+        assert!(list == vec![1, 2, 3]);
+        let vec : Vec<i32> = list.into();
+        assert_eq!(vec, vec![1, 2, 3]);
+
+        let list : XorList<String> = XorList::from(vec![]);
+        assert!(list.is_empty());
+        assert_eq!(Vec::from(list), Vec::<String>::new());
+
+        let count = Rc::new(Cell::new(0));
+        let list = XorList::from(vec![DropCounter(count.clone()), DropCounter(count.clone())]);
+        let vec = Vec::from(list);
+        assert_eq!(count.get(), 0);
+        assert_eq!(vec.len(), 2);
+        drop(vec);
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn into_boxes() {
+        let list : XorList<Display> = (0..4).collect();
+        let boxes = list.into_boxes();
+        let strings : Vec<String> = boxes.iter().map(|b| b.to_string()).collect();
+        assert_eq!(strings, vec!["0", "1", "2", "3"]);
+
+        let mut list = XorList::new();
+        for b in boxes.into_iter() {
+            list.push_back_boxed(b);
+        }
+        assert_eq!(to_strings(&list), vec!["0", "1", "2", "3"]);
+
+        let list = XorList::from(vec![1, 2]);
+        let boxes = list.into_boxes();
+        assert_eq!(boxes, vec![box 1, box 2]);
+    }
+
+    #[test]
+    fn extend_boxed() {
+        let boxes : Vec<Box<Display>> = vec![box 1, box "two", box 3.5];
+        let mut list = XorList::<Display>::from_boxes(boxes);
+        assert_eq!(to_strings(&list), ["1", "two", "3.5"]);
+
+        list.extend_boxed(vec![box 'x' as Box<Display>]);
+        assert_eq!(to_strings(&list), ["1", "two", "3.5", "x"]);
+        assert_eq!(list.len(), 4);
+    }
+
+    #[test]
+    fn extend_elems() {
+        let mut a : XorList<Display> = XorList::new();
+        a.push_back(1);
+        let mut b : XorList<Display> = XorList::new();
+        b.push_back("two");
+        b.push_back(3);
+
+        let addrs : Vec<*const u8> = b.iter().map(|x| x as *const Display as *const u8).collect();
+        a.extend_elems(b.drain());
+        assert!(b.is_empty());
+        assert_eq!(to_strings(&a), ["1", "two", "3"]);
+        assert_eq!(a.len(), 3);
+
+        let moved : Vec<*const u8> = a.iter().skip(1)
+            .map(|x| x as *const Display as *const u8).collect();
+        assert_eq!(addrs, moved);
+    }
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    // That `XorList<Rc<i32>>`, `Elem<Rc<i32>>` and friends aren't `Send` or `Sync` is checked by
+    // the `compile_fail` examples on `XorList`, `IterMut` and `Elem`.
+    #[test]
+    fn send_sync() {
+        assert_send::<XorList<i32>>();
+        assert_sync::<XorList<i32>>();
+        assert_send::<XorList<[u8]>>();
+        assert_send::<XorList<Display + Send>>();
+        assert_sync::<XorList<Display + Sync>>();
+
+        assert_send::<Iter<i32>>();
+        assert_sync::<Iter<i32>>();
+        assert_send::<IterMut<i32>>();
+        assert_sync::<IterMut<i32>>();
+        assert_send::<IntoIter<i32>>();
+        assert_sync::<IntoIter<i32>>();
+        assert_send::<Drain<i32>>();
+        assert_send::<Elem<i32>>();
+        assert_sync::<Elem<i32>>();
+    }
+
+    #[test]
+    fn send_to_thread() {
+        use std::thread;
+
+        let list = XorList::from(vec![1, 2, 3]);
+        let sum = thread::spawn(move || list.iter().fold(0, |a, b| a + *b)).join().unwrap();
+        assert_eq!(sum, 6);
+    }
+
+    struct CountingAlloc {
+        allocs: Cell<usize>,
+        frees: Cell<usize>
+    }
+
+    impl CountingAlloc {
+        fn new() -> CountingAlloc {
+            CountingAlloc { allocs: Cell::new(0), frees: Cell::new(0) }
+        }
+    }
+
+    unsafe impl<'a> Allocator for &'a CountingAlloc {
+        unsafe fn allocate(&self, size: usize, align: usize) -> *mut u8 {
+            self.allocs.set(self.allocs.get() + 1);
+            Global.allocate(size, align)
+        }
+
+        unsafe fn deallocate(&self, ptr: *mut u8, size: usize, align: usize) {
+            self.frees.set(self.frees.get() + 1);
+            Global.deallocate(ptr, size, align)
+        }
+
+        fn same_as(&self, other: &&'a CountingAlloc) -> bool {
+            *self as *const CountingAlloc == *other as *const CountingAlloc
+        }
+    }
+
+    #[test]
+    fn custom_allocator() {
+        let counter = CountingAlloc::new();
+        {
+            let mut list : XorList<Display, _> = XorList::new_in(&counter);
+            list.push_back(1);
+            list.push_front("zero");
+            list.push_back_boxed(box 2.5f64 as Box<Display>);
+            list.insert(1, 'a');
+            assert_eq!(counter.allocs.get(), 4);
+            assert_eq!(to_strings(&list), ["zero", "a", "1", "2.5"]);
+
+            // Moving elements around reuses their nodes
+            let elem = list.pop_front().unwrap();
+            list.push_back_elem(elem);
+            list.swap(0, 3);
+            list.reverse();
+            let mut rest = list.split_off(2);
+            list.append(&mut rest);
+            assert_eq!(to_strings(&list), ["a", "2.5", "1", "zero"]);
+            assert_eq!(counter.allocs.get(), 4);
+            assert_eq!(counter.frees.get(), 0);
+
+            drop(list.pop_back());
+            assert_eq!(counter.frees.get(), 1);
+
+            let boxed = list.pop_front().unwrap().into_box();
+            assert_eq!(boxed.to_string(), "a");
+            assert_eq!(counter.frees.get(), 2);
+        }
+        assert_eq!(counter.allocs.get(), 4);
+        assert_eq!(counter.frees.get(), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn push_elem_other_allocator() {
+        let a = CountingAlloc::new();
+        let b = CountingAlloc::new();
+        let mut list_a : XorList<Display, _> = XorList::new_in(&a);
+        let mut list_b : XorList<Display, _> = XorList::new_in(&b);
+        list_a.push_back(1);
+
+        let elem = list_a.pop_front().unwrap();
+        list_b.push_back_elem(elem);
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_elem_other_allocator() {
+        let a = CountingAlloc::new();
+        let b = CountingAlloc::new();
+        let mut list_a : XorList<Display, _> = XorList::new_in(&a);
+        let mut list_b : XorList<Display, _> = XorList::new_in(&b);
+        list_a.push_back(1);
+        list_b.push_back(2);
+
+        let elem = list_a.pop_front().unwrap();
+        let mut cursor = list_b.cursor();
+        cursor.insert_elem_after(elem);
+    }
+
+    #[test]
+    #[should_panic]
+    fn replace_elem_other_allocator() {
+        let a = CountingAlloc::new();
+        let b = CountingAlloc::new();
+        let mut list_a : XorList<Display, _> = XorList::new_in(&a);
+        let mut list_b : XorList<Display, _> = XorList::new_in(&b);
+        list_a.push_back(1);
+        list_b.push_back(2);
+
+        let elem = list_a.pop_front().unwrap();
+        let mut cursor = list_b.cursor();
+        cursor.replace_elem(elem);
+    }
+
+    #[test]
+    #[should_panic]
+    fn append_other_allocator() {
+        let a = CountingAlloc::new();
+        let b = CountingAlloc::new();
+        let mut list_a : XorList<Display, _> = XorList::new_in(&a);
+        let mut list_b : XorList<Display, _> = XorList::new_in(&b);
+        list_a.push_back(1);
+        list_b.push_back(2);
+
+        list_a.append(&mut list_b);
+    }
+
+    #[test]
+    fn custom_allocator_sized() {
+        let counter = CountingAlloc::new();
+        {
+            let mut list = XorList::new_in(&counter);
+            for i in 0..10 {
+                let node = Node::new_sized(i, list.allocator());
+                list.push_back_node(node);
+            }
+
+            let copy = list.clone();
+            assert!(copy == list);
+            assert_eq!(counter.allocs.get(), 20);
+
+            list.retain(|x| x % 2 == 0);
+            assert_eq!(counter.frees.get(), 5);
+
+            assert_eq!(list.pop_front_value(), Some(0));
+            assert_eq!(counter.frees.get(), 6);
+
+            {
+                let mut cursor = list.cursor();
+                cursor.next();
+                cursor.remove();
+            }
+            assert_eq!(counter.frees.get(), 7);
+        }
+        assert_eq!(counter.allocs.get(), counter.frees.get());
+    }
+
+    #[test]
+    fn recycling() {
+        let counter = CountingAlloc::new();
+        {
+            let mut list : XorList<[u8], _> = XorList::with_recycling_in(3, &counter);
+            list.push_back([1u8, 2]);
+            list.push_back([3u8, 4]);
+            assert_eq!(counter.allocs.get(), 2);
+
+            // Nodes of the same layout are reused
+            for _ in 0..100 {
+                list.retain(|x| x[0] != 5);
+                list.push_back([5u8, 6]);
+                let elem = list.pop_front().unwrap();
+                list.push_back_elem(elem);
+            }
+            assert_eq!(list.len(), 3);
+            assert_eq!(counter.allocs.get(), 3);
+            assert_eq!(counter.frees.get(), 0);
+
+            // Ones with a different layout aren't
+            list.push_front([7u8; 9]);
+            list.truncate(2);
+            list.retain(|x| x.len() == 2);
+            assert_eq!(counter.allocs.get(), 4);
+            assert_eq!(counter.frees.get(), 1);
+
+            list.clear();
+            assert_eq!(counter.frees.get(), 1);
+            list.shrink_pool();
+            assert_eq!(counter.frees.get(), 4);
+        }
+        assert_eq!(counter.allocs.get(), counter.frees.get());
+    }
+
+    #[test]
+    fn recycling_steady_state() {
+        let counter = CountingAlloc::new();
+        {
+            let mut list = XorList::with_recycling_in(4, &counter);
+            for i in 0..4 {
+                let node = Node::new_sized(i, &list.node_alloc());
+                list.push_back_node(node);
+            }
+
+            for i in 4..1000 {
+                assert_eq!(list.pop_front_value(), Some(i - 4));
+                let node = Node::new_sized(i, &list.node_alloc());
+                list.push_back_node(node);
+            }
+            assert_eq!(counter.allocs.get(), 4);
+            assert_eq!(counter.frees.get(), 0);
+        }
+        assert_eq!(counter.frees.get(), 4);
+    }
+
+    #[test]
+    fn is_consistent() {
+        let mut list : XorList<Display> = XorList::new();
+        assert!(list.is_consistent());
+        list.push_back(1);
+        assert!(list.is_consistent());
+        list.push_back(2);
+        list.push_back(3);
+        list.push_front(0);
+        assert!(list.is_consistent());
+
+        // Wrong length
+        list.len = 3;
+        assert!(!list.is_consistent());
+        list.len = 5;
+        assert!(!list.is_consistent());
+        list.len = 4;
+
+        // A list that ends early
+        let second = list.head.as_ref().unwrap().link;
+        list.head.as_mut().unwrap().link = Raw::null();
+        assert!(!list.is_consistent());
+        list.head.as_mut().unwrap().link = second;
+        assert!(list.is_consistent());
+
+        // A tail that isn't the last node
+        let tail = list.tail;
+        list.tail = second;
+        assert!(!list.is_consistent());
+        list.tail = tail;
+
+        // A tail with a link that doesn't lead back
+        let tail_link = list.tail.as_ref().unwrap().link;
+        list.tail.as_mut().unwrap().link = list.head;
+        assert!(!list.is_consistent());
+        list.tail.as_mut().unwrap().link = tail_link;
+
+        // A single-element list with a dangling link
+        let mut single : XorList<Display> = XorList::new();
+        single.push_back(1);
+        single.head.as_mut().unwrap().link = tail;
+        assert!(!single.is_consistent());
+        single.head.as_mut().unwrap().link = Raw::null();
+        assert!(single.is_consistent());
+
+        assert!(list.is_consistent());
+    }
+
+    #[test]
+    fn clear_large() {
+        let count = Rc::new(Cell::new(0));
+        let mut list : XorList<Debug> = XorList::new();
+        for _ in 0..1_000_000 {
+            list.push_back(DropCounter(count.clone()));
+        }
+
+        list.clear();
+        assert_eq!(count.get(), 1_000_000);
+        assert!(list.is_empty());
+        assert!(list.is_consistent());
+
+        list.push_back(DropCounter(count.clone()));
+        assert_eq!(list.len(), 1);
+        drop(list);
+        assert_eq!(count.get(), 1_000_001);
+    }
+
+    #[test]
+    fn drop_panic() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+
+        struct Bomb(bool, Arc<AtomicUsize>);
+
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                self.1.fetch_add(1, Ordering::SeqCst);
+                if self.0 {
+                    panic!("boom");
+                }
+            }
+        }
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let c = count.clone();
+        let res = thread::spawn(move || {
+            let mut list = XorList::new();
+            for i in 0..5 {
+                list.push_back_node(Node::new_sized(Bomb(i == 1, c.clone()), &Global));
+            }
+            drop(list);
+        }).join();
+        assert!(res.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 5);
+
+        let c = count.clone();
+        let res = thread::spawn(move || {
+            let mut list = XorList::new();
+            for i in 0..5 {
+                list.push_back_node(Node::new_sized(Bomb(i == 3, c.clone()), &Global));
+            }
+            list.clear();
+        }).join();
+        assert!(res.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn fused() {
+        fn assert_fused<I: iter::FusedIterator>(_: &I) {}
+
+        let mut list : XorList<Display> = XorList::new();
+        list.push_back(1);
+        list.push_back(2);
+
+        {
+            let mut iter = list.iter();
+            assert_fused(&iter);
+            assert_eq!(iter.next().unwrap().to_string(), "1");
+            assert_eq!(iter.next().unwrap().to_string(), "2");
+            for _ in 0..5 {
+                assert!(iter.next().is_none());
+            }
+        }
+
+        {
+            let mut iter = list.iter_mut();
+            assert_fused(&iter);
+            assert!(iter.next().is_some());
+            assert!(iter.next().is_some());
+            for _ in 0..5 {
+                assert!(iter.next().is_none());
+            }
+        }
+
+        {
+            let mut iter = list.extract_if(|_| false);
+            assert_fused(&iter);
+            for _ in 0..5 {
+                assert!(iter.next().is_none());
+            }
+        }
+        assert_eq!(list.len(), 2);
+
+        let mut iter = IntoIter { list: list };
+        assert_fused(&iter);
+        assert_eq!(iter.next_back().unwrap().to_string(), "2");
+        assert_eq!(iter.next().unwrap().to_string(), "1");
+        for _ in 0..5 {
+            assert!(iter.next().is_none());
+            assert!(iter.next_back().is_none());
+        }
+    }
+
+    #[test]
+    fn drain_fused() {
+        let mut list = sized_list(vec![1, 2, 3]);
+
+        {
+            let mut drain = list.drain();
+            assert_eq!(*drain.next_back().unwrap(), 3);
+            assert_eq!(*drain.next().unwrap(), 1);
+            assert_eq!(*drain.next_back().unwrap(), 2);
+            for _ in 0..5 {
+                assert!(drain.next_back().is_none());
+                assert!(drain.next().is_none());
+            }
+            assert_eq!(drain.len(), 0);
+        }
+
+        assert!(list.is_empty());
+        assert!(list.is_consistent());
+    }
+
+    #[test]
+    fn cursor_pos() {
+        let mut list : XorList<Display> = XorList::new();
+        for i in 0..5 {
+            list.push_back(i);
+        }
+
+        let pos = {
+            let cursor = list.cursor();
+            cursor.skip_forwards(2);
+            cursor.save()
+        };
+
+        // Changes elsewhere in the list leave the position alone
+        list.push_back(5);
+        list.push_front(-1);
+        {
+            let mut cursor = list.cursor();
+            cursor.next();
+            cursor.insert_before(10);
+        }
+        list.iter_mut().count();
+
+        {
+            let cursor = list.cursor_at_pos(pos).unwrap();
+            assert_eq!(cursor.peek().unwrap().to_string(), "2");
+            assert_eq!(cursor.prev().unwrap().to_string(), "1");
+        }
+
+        // Positions can be used more than once
+        {
+            let mut cursor = list.cursor_at_pos(pos).unwrap();
+            cursor.insert_before(20);
+        }
+        assert_eq!(to_strings(&list), ["-1", "10", "0", "1", "20", "2", "3", "4", "5"]);
+
+        // The position is now split by the new element
+        assert!(list.cursor_at_pos(pos).is_none());
+
+        let pos = {
+            let cursor = list.cursor();
+            cursor.skip_forwards(5);
+            assert_eq!(cursor.peek().unwrap().to_string(), "2");
+            cursor.save()
+        };
+        assert!(list.cursor_at_pos(pos).is_some());
+
+        // Removing the saved node makes the position stale
+        {
+            let mut cursor = list.cursor_at_pos(pos).unwrap();
+            cursor.remove();
+        }
+        assert!(list.cursor_at_pos(pos).is_none());
+
+        // So does removing anything else
+        let pos = list.cursor().save();
+        list.pop_back();
+        assert!(list.cursor_at_pos(pos).is_none());
+
+        // Positions only work with the list they came from
+        let pos = list.cursor().save();
+        let mut other : XorList<Display> = XorList::new();
+        assert!(other.cursor_at_pos(pos).is_none());
+        assert!(list.cursor_at_pos(pos).is_some());
+
+        // Moving the nodes to another list, and back again, invalidates positions too
+        let pos = list.cursor().save();
+        other.append(&mut list);
+        list.append(&mut other);
+        assert!(list.cursor_at_pos(pos).is_none());
+    }
+
+    #[test]
+    fn cursor_pos_ends() {
+        let mut list = sized_list(vec![1, 2, 3]);
+
+        let start = list.cursor().save();
+        let end = {
+            let cursor = list.cursor();
+            cursor.seek_to_end();
+            cursor.save()
+        };
+
+        let mut empty : XorList<i32> = XorList::new();
+        let empty_pos = empty.cursor().save();
+        assert!(empty.cursor_at_pos(empty_pos).unwrap().at_end());
+
+        assert_eq!(*list.cursor_at_pos(start).unwrap().peek().unwrap(), 1);
+        assert!(list.cursor_at_pos(end).unwrap().at_end());
+
+        assert!(list.cursor_at_pos(empty_pos).is_none());
+        assert!(empty.cursor_at_pos(start).is_none());
+    }
 
     #[test]
-    fn smoketest() {
+    fn dump_links() {
         let mut list : XorList<Display> = XorList::new();
+        assert_eq!(list.dump_links().lines().count(), 1);
 
         list.push_back(1);
         list.push_back(2);
         list.push_back(3);
-        list.push_back("None");
-        list.push_back("Some(4)");
-
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "1");
-
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "2");
-
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "3");
-
-        let el = list.pop_back().unwrap();
-        assert_eq!(&el.to_string()[..], "Some(4)");
 
-        list.push_back("Some(5)");
-
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "None");
+        let dump = list.dump_links();
+        assert!(dump.starts_with("XorList "));
+        assert!(dump.lines().next().unwrap().ends_with("len=3"));
+        let nodes : Vec<&str> = dump.lines().filter(|l| l.starts_with("[")).collect();
+        assert_eq!(nodes.len(), 3);
+        assert!(nodes[0].starts_with("[0] ") && nodes[0].ends_with(" (head)"));
+        assert!(!nodes[1].contains("(head)") && !nodes[1].contains("(tail)"));
+        assert!(nodes[2].starts_with("[2] ") && nodes[2].ends_with(" (tail)"));
+        assert!(nodes[0].contains("prev=0x0 "));
+        assert!(nodes[2].ends_with("next=0x0 (tail)"));
+        assert_eq!(dump.lines().count(), 4);
+
+        let dot = list.dump_dot();
+        assert!(dot.starts_with("digraph XorList {"));
+        assert!(dot.ends_with("}\n"));
+        assert_eq!(dot.lines().filter(|l| l.contains(" -> ")).count(), 4);
+
+        // Make the links loop back from the last node to the first
+        let tail_link = list.tail.as_ref().unwrap().link;
+        let head = list.head;
+        list.tail.as_mut().unwrap().link = tail_link.xor(&head);
+
+        let dump = list.dump_links();
+        assert_eq!(dump.lines().filter(|l| l.starts_with("[")).count(), 3);
+        assert!(dump.lines().last().unwrap().starts_with("links continue past 3 nodes"));
+        assert!(list.dump_dot().contains("[color=red]"));
+
+        list.tail.as_mut().unwrap().link = tail_link;
+        assert!(list.is_consistent());
+    }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "Some(5)");
+    #[test]
+    fn cursor_skip() {
+        let mut list = sized_list(vec![1, 2, 3, 4, 5]);
+        let cursor = list.cursor();
+
+        assert_eq!(cursor.skip_forwards(0), 0);
+        assert_eq!(cursor.skip_forwards(3), 3);
+        assert_eq!(*cursor.peek().unwrap(), 4);
+
+        assert_eq!(cursor.skip_backwards(1), 1);
+        assert_eq!(*cursor.peek().unwrap(), 3);
+
+        // Skipping backwards over the head stops at the start
+        assert_eq!(cursor.skip_backwards(10), 2);
+        assert!(cursor.at_start());
+        assert_eq!(*cursor.peek().unwrap(), 1);
+        assert_eq!(cursor.skip_backwards(1), 0);
+        assert_eq!(*cursor.peek().unwrap(), 1);
+
+        cursor.seek_to_end();
+        assert_eq!(cursor.skip_backwards(2), 2);
+        assert_eq!(*cursor.peek().unwrap(), 4);
+
+        assert_eq!(cursor.skip_forwards(10), 2);
+        assert!(cursor.at_end());
+        assert!(cursor.peek().is_none());
+        assert_eq!(cursor.skip_forwards(1), 0);
+        assert_eq!(cursor.prev(), Some(&5));
+    }
 
-        assert!(list.pop_front().is_none());
+    #[test]
+    fn cursor_remove_prev() {
+        let mut list = sized_list(vec![1, 2, 3]);
+        {
+            let mut cursor = list.cursor();
+            assert!(cursor.remove_prev().is_none());
 
+            // The previous element is the head
+            cursor.next();
+            assert_eq!(*cursor.remove_prev().unwrap(), 1);
+            assert!(cursor.at_start());
+            assert_eq!(cursor.peek(), Some(&2));
+
+            cursor.seek_to_end();
+            assert_eq!(*cursor.remove_prev().unwrap(), 3);
+            assert!(cursor.at_end());
+            assert_eq!(cursor.peek_prev(), Some(&2));
+
+            // The previous element is the only one
+            assert_eq!(*cursor.remove_prev().unwrap(), 2);
+            assert!(cursor.at_start() && cursor.at_end());
+            assert!(cursor.remove_prev().is_none());
+        }
         assert!(list.is_empty());
+        assert!(list.is_consistent());
+
+        let mut list = sized_list(vec![1, 2, 3, 4]);
+        {
+            let mut cursor = list.cursor();
+            cursor.skip_forwards(2);
+            assert_eq!(*cursor.remove_prev().unwrap(), 2);
+            assert_eq!(cursor.peek_prev(), Some(&1));
+            assert_eq!(cursor.peek(), Some(&3));
+        }
+        assert_eq!(Vec::from(list), [1, 3, 4]);
     }
 
     #[test]
-    fn droptest() {
-
-        #[derive(Debug)]
-        struct DropTest;
-        static mut DROP_TEST_COUNT : usize = 0;
-        impl DropTest {
-            fn new() -> DropTest {
-                unsafe {
-                    DROP_TEST_COUNT += 1;
-                }
-                DropTest
-            }
+    fn cursor_remove_prev_dedup() {
+        let count = Rc::new(Cell::new(0));
+        let vals = vec![1, 1, 2, 3, 3, 3, 4, 1, 1];
+        let mut list = XorList::new();
+        for (i, &v) in vals.iter().enumerate() {
+            list.push_back_node(Node::new_sized((v, i, DropCounter(count.clone())), &Global));
         }
-        impl Drop for DropTest {
-            fn drop(&mut self) {
-                unsafe {
-                    DROP_TEST_COUNT -= 1;
+
+        {
+            let mut cursor = list.cursor();
+            cursor.next();
+            while !cursor.at_end() {
+                let dup = cursor.peek_prev().unwrap().0 == cursor.peek().unwrap().0;
+                if dup {
+                    drop(cursor.remove_prev());
                 }
+                cursor.next();
             }
         }
 
-        {
+        assert_eq!(count.get(), 4);
+        assert!(list.is_consistent());
+        // The last element of each run is the one that's kept
+        let kept : Vec<(i32, usize)> = list.iter().map(|&(v, i, _)| (v, i)).collect();
+        assert_eq!(kept, [(1, 1), (2, 2), (3, 5), (4, 6), (1, 8)]);
 
-            let mut list : XorList<Debug> = XorList::new();
+        drop(list);
+        assert_eq!(count.get(), 9);
+    }
 
-            list.push_back(DropTest::new());
-            list.push_back(DropTest::new());
-            list.push_back(DropTest::new());
+    #[test]
+    fn cursor_insert_elem() {
+        let counter = CountingAlloc::new();
+        let mut list : XorList<Display, _> = XorList::new_in(&counter);
+        for i in 0..5 {
+            list.push_back(i);
+        }
+        assert_eq!(counter.allocs.get(), 5);
 
-            unsafe {
-                assert_eq!(DROP_TEST_COUNT, 3);
-            }
+        {
+            let mut cursor = list.cursor();
 
+            // Move the head to the middle
+            let elem = cursor.remove().unwrap();
+            cursor.skip_forwards(2);
+            cursor.insert_elem_before(elem);
+            assert_eq!(cursor.peek().unwrap().to_string(), "3");
+
+            // Move the last element to the front
+            cursor.seek_to_end();
+            cursor.prev();
+            let elem = cursor.remove().unwrap();
+            assert!(cursor.at_end());
+            cursor.seek_to_start();
+            cursor.insert_elem_after(elem);
+            assert!(cursor.at_start());
+            assert_eq!(cursor.peek().unwrap().to_string(), "4");
+
+            // And another one to the end
+            cursor.next();
+            cursor.next();
+            let elem = cursor.remove().unwrap();
+            assert_eq!(elem.to_string(), "2");
+            cursor.seek_to_end();
+            cursor.insert_elem_after(elem);
+            assert_eq!(cursor.peek().unwrap().to_string(), "2");
         }
 
-        unsafe {
-            assert_eq!(DROP_TEST_COUNT, 0);
+        assert_eq!(to_strings(&list), ["4", "1", "0", "3", "2"]);
+        assert!(list.is_consistent());
+        assert_eq!(counter.allocs.get(), 5);
+        assert_eq!(counter.frees.get(), 0);
+
+        // Elements can move between lists too
+        let mut other : XorList<Display, _> = XorList::new_in(&counter);
+        {
+            let mut cursor = list.cursor();
+            let mut other_cursor = other.cursor();
+            while let Some(elem) = cursor.remove() {
+                other_cursor.insert_elem_after(elem);
+            }
         }
+        assert_eq!(to_strings(&other), ["2", "3", "0", "1", "4"]);
+        assert_eq!(counter.allocs.get(), 5);
+        assert_eq!(counter.frees.get(), 0);
     }
 
     #[test]
-    fn iter() {
+    fn cursor_replace() {
         let mut list : XorList<Display> = XorList::new();
+        {
+            // An empty list just gets the value inserted
+            let mut cursor = list.cursor();
+            assert!(cursor.replace(1).is_none());
+            assert_eq!(cursor.peek().unwrap().to_string(), "1");
+
+            // The sole element of a single-element list
+            assert_eq!(cursor.replace("one").unwrap().to_string(), "1");
+            assert!(cursor.at_start());
+            assert_eq!(cursor.peek().unwrap().to_string(), "one");
+        }
+        assert_eq!(to_strings(&list), ["one"]);
+        assert!(list.is_consistent());
 
-        list.push_back(0);
-        list.push_back(1);
         list.push_back(2);
         list.push_back(3);
         list.push_back(4);
-        list.push_back(5);
+        {
+            let mut cursor = list.cursor();
 
-        for (i, el) in list.iter().enumerate() {
-            assert_eq!(el.to_string(), i.to_string());
+            // The head
+            assert_eq!(cursor.replace(1).unwrap().to_string(), "one");
+            assert_eq!(cursor.next().unwrap().to_string(), "1");
+
+            // The middle
+            assert_eq!(cursor.replace('b').unwrap().to_string(), "2");
+            assert_eq!(cursor.peek_prev().unwrap().to_string(), "1");
+            assert_eq!(cursor.next().unwrap().to_string(), "b");
+
+            // The tail
+            cursor.next();
+            assert_eq!(cursor.replace("four").unwrap().to_string(), "4");
+            assert_eq!(cursor.next().unwrap().to_string(), "four");
+            assert!(cursor.at_end());
+
+            // The end of the list
+            assert!(cursor.replace(5).is_none());
+            assert_eq!(cursor.peek().unwrap().to_string(), "5");
         }
+        assert_eq!(to_strings(&list), ["1", "b", "3", "four", "5"]);
+        assert_eq!(list.back().unwrap().to_string(), "5");
+        assert!(list.is_consistent());
     }
 
     #[test]
-    fn cursor_basic() {
-        let mut list : XorList<Display> = XorList::new();
-
-        list.push_back(0);
-        list.push_back(1);
-        list.push_back(2);
-        list.push_back(3);
-        list.push_back(4);
-        list.push_back(5);
+    fn cursor_replace_elem() {
+        let counter = CountingAlloc::new();
+        let mut list : XorList<Display, _> = XorList::new_in(&counter);
+        for i in 0..3 {
+            list.push_back(i);
+        }
 
         {
+            // Swap the first and last elements
             let mut cursor = list.cursor();
-            cursor.remove();
+            cursor.seek_to_end();
+            cursor.prev();
+            let last = cursor.remove().unwrap();
+            cursor.seek_to_start();
+            let first = cursor.replace_elem(last).unwrap();
+            cursor.seek_to_end();
+            assert!(cursor.replace_elem(first).is_none());
+        }
+
+        assert_eq!(to_strings(&list), ["2", "1", "0"]);
+        assert!(list.is_consistent());
+        assert_eq!(counter.allocs.get(), 3);
+        assert_eq!(counter.frees.get(), 0);
+    }
 
+    #[test]
+    fn cursor_split_before() {
+        let mut list = sized_list(vec![1, 2, 3, 4, 5]);
+        let front = {
+            let mut cursor = list.cursor();
+            cursor.skip_forwards(2);
+            let front = cursor.split_before();
+            assert!(cursor.at_start());
+            assert_eq!(cursor.peek(), Some(&3));
+            front
+        };
+        assert!(front.is_consistent() && list.is_consistent());
+        assert_eq!(front.iter().cloned().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(list.iter().cloned().collect::<Vec<_>>(), [3, 4, 5]);
+        assert_eq!(front.len(), 2);
+        assert_eq!(list.len(), 3);
+
+        list.push_front_node(Node::new_sized(0, &Global));
+        assert!(list.is_consistent());
+        assert_eq!(list.iter().cloned().collect::<Vec<_>>(), [0, 3, 4, 5]);
+
+        // At the start nothing moves
+        assert!(list.cursor().split_before().is_empty());
+        assert_eq!(list.len(), 4);
+
+        // At the end everything does
+        let all = {
+            let mut cursor = list.cursor();
+            cursor.seek_to_end();
+            let all = cursor.split_before();
+            assert!(cursor.at_start() && cursor.at_end());
+            all
+        };
+        assert!(list.is_empty() && list.is_consistent());
+        assert_eq!(all.iter().cloned().collect::<Vec<_>>(), [0, 3, 4, 5]);
+
+        // A single-element list, with the cursor on either side
+        let mut single = sized_list(vec![1]);
+        assert!(single.cursor().split_before().is_empty());
+        let moved = {
+            let mut cursor = single.cursor();
             cursor.next();
+            cursor.split_before()
+        };
+        assert!(single.is_empty());
+        assert_eq!(moved.iter().cloned().collect::<Vec<_>>(), [1]);
+        assert!(moved.is_consistent());
+
+        // Leaving a single element behind
+        let mut list = sized_list(vec![1, 2]);
+        let front = {
+            let mut cursor = list.cursor();
             cursor.next();
+            cursor.split_before()
+        };
+        assert!(front.is_consistent() && list.is_consistent());
+        list.push_front_node(Node::new_sized(0, &Global));
+        assert!(list.is_consistent());
+        assert_eq!(list.iter().cloned().collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(front.iter().cloned().collect::<Vec<_>>(), [1]);
+    }
 
-            cursor.insert_before(6);
+    #[test]
+    fn cursor_splice_before() {
+        fn fragment(vals: &[&'static str]) -> XorList<Display> {
+            let mut list : XorList<Display> = XorList::new();
+            for &v in vals {
+                list.push_back(v);
+            }
+            list
+        }
 
-            cursor.remove();
+        // Splicing into an empty list
+        let mut list : XorList<Display> = XorList::new();
+        {
+            let mut cursor = list.cursor();
+            cursor.splice_before(fragment(&["a", "b"]));
+            cursor.splice_before(fragment(&[]));
+            cursor.splice_before(fragment(&["c"]));
+            cursor.splice_before(fragment(&["d", "e", "f"]));
+            assert!(cursor.at_end());
+            assert_eq!(cursor.peek_prev().unwrap().to_string(), "f");
+        }
+        assert_eq!(to_strings(&list), ["a", "b", "c", "d", "e", "f"]);
+        assert!(list.is_consistent());
 
-            cursor.insert_after(7);
+        // Splicing into the middle of a list
+        let mut list = fragment(&["<", ">"]);
+        {
+            let mut cursor = list.cursor();
+            cursor.next();
+            cursor.splice_before(fragment(&["a"]));
+            cursor.splice_before(fragment(&["b", "c"]));
+            assert_eq!(cursor.peek().unwrap().to_string(), ">");
+            cursor.insert_before("d");
         }
+        assert_eq!(to_strings(&list), ["<", "a", "b", "c", "d", ">"]);
+        assert!(list.is_consistent());
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "1");
+        // Splicing at the start of a single-element list
+        let mut list = fragment(&[">"]);
+        {
+            let mut cursor = list.cursor();
+            cursor.splice_before(fragment(&["a", "b"]));
+            cursor.splice_before(fragment(&["c"]));
+            assert_eq!(cursor.peek().unwrap().to_string(), ">");
+        }
+        assert_eq!(to_strings(&list), ["a", "b", "c", ">"]);
+        assert!(list.is_consistent());
+    }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "2");
+    #[test]
+    fn cursor_seek_until() {
+        let mut list = sized_list(vec![1, 3, 5, 7, 5]);
+        let cursor = list.cursor();
+
+        assert!(cursor.seek_forward_until(|&x| x >= 5));
+        assert_eq!(cursor.peek_prev(), Some(&3));
+        assert_eq!(cursor.peek(), Some(&5));
+
+        // The element after the cursor is checked first, so this doesn't move
+        assert!(cursor.seek_forward_until(|&x| x == 5));
+        assert_eq!(cursor.peek_prev(), Some(&3));
+
+        assert!(!cursor.seek_forward_until(|&x| x == 4));
+        assert!(cursor.at_end());
+        assert!(!cursor.seek_forward_until(|_| true));
+
+        assert!(cursor.seek_backward_until(|&x| x == 5));
+        assert_eq!(cursor.peek_prev(), Some(&5));
+        assert!(cursor.at_end());
+
+        assert!(cursor.seek_backward_until(|&x| x < 5));
+        assert_eq!(cursor.peek_prev(), Some(&3));
+        assert_eq!(cursor.peek(), Some(&5));
+
+        assert!(!cursor.seek_backward_until(|&x| x > 7));
+        assert!(cursor.at_start());
+        assert_eq!(cursor.peek(), Some(&1));
+
+        let mut empty : XorList<i32> = XorList::new();
+        let cursor = empty.cursor();
+        assert!(!cursor.seek_forward_until(|_| true));
+        assert!(!cursor.seek_backward_until(|_| true));
+    }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "6");
+    #[test]
+    fn cursor_index() {
+        let mut list : XorList<Display> = XorList::new();
+        for i in 0..5 {
+            list.push_back(i);
+        }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "7");
+        let mut cursor = list.cursor();
+        let mut expected = 0;
+        macro_rules! check {
+            () => {{
+                assert_eq!(cursor.index(), expected);
+                // Walk back to the start to check the index is right
+                let pos = cursor.save();
+                assert_eq!(cursor.skip_backwards(!0), expected);
+                assert_eq!(cursor.skip_forwards(expected), expected);
+                assert!(cursor.save().prev == pos.prev);
+            }}
+        }
+        check!();
+
+        cursor.next(); expected += 1; check!();
+        cursor.skip_forwards(2); expected += 2; check!();
+        cursor.prev(); expected -= 1; check!();
+        cursor.insert_before(10); expected += 1; check!();
+        cursor.insert_after(11); check!();
+        cursor.remove(); check!();
+        cursor.remove_prev(); expected -= 1; check!();
+        cursor.seek_to_end(); expected = 5; check!();
+        cursor.skip_backwards(2); expected -= 2; check!();
+        cursor.replace(12); check!();
+
+        let mut run : XorList<Display> = XorList::new();
+        run.push_back(20);
+        run.push_back(21);
+        cursor.splice(run); check!();
+
+        let mut run : XorList<Display> = XorList::new();
+        run.push_back(22);
+        run.push_back(23);
+        run.push_back(24);
+        cursor.splice_before(run); expected += 3; check!();
+
+        cursor.seek_forward_until(|x| x.to_string() == "12"); expected += 2; check!();
+        cursor.seek_backward_until(|x| x.to_string() == "0"); expected = 1; check!();
+
+        let rest = cursor.split(); check!();
+        assert_eq!(rest.len(), 9);
+        cursor.seek_to_start(); expected = 0; check!();
+        cursor.next(); expected += 1; check!();
+        cursor.splice(rest); check!();
+        cursor.skip_forwards(3); expected += 3; check!();
+        drop(cursor.split_before()); expected = 0; check!();
+        cursor.seek_to_end(); expected = 6; check!();
+        cursor.next(); check!();
+        drop(cursor);
+
+        assert_eq!(to_strings(&list), ["23", "24", "20", "21", "12", "4"]);
+    }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "4");
+    #[test]
+    fn cursor_remaining() {
+        let vec = vec![1, 2, 3, 4, 5, 6];
+        let mut list = sized_list(vec.clone());
+
+        for n in 0..8 {
+            let cursor = list.cursor();
+            let skipped = cursor.skip_forwards(n);
+            let rest : Vec<i32> = cursor.remaining().cloned().collect();
+            assert_eq!(rest, &vec[skipped..]);
+
+            // The cursor doesn't move
+            assert_eq!(cursor.index(), skipped);
+            assert_eq!(cursor.peek(), vec.get(skipped));
+        }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "5");
+        {
+            let cursor = list.cursor();
+            cursor.skip_forwards(2);
+            let evens : Vec<i32> = cursor.remaining().cloned().take_while(|&x| x < 5)
+                                         .filter(|x| x % 2 == 0).collect();
+            assert_eq!(evens, [4]);
+
+            cursor.prev();
+            for x in cursor.into_remaining() {
+                *x *= 10;
+            }
+        }
+        assert_eq!(Vec::from(list), [1, 20, 30, 40, 50, 60]);
     }
 
     #[test]
-    fn cursor_splice() {
+    fn cursor_remove_n() {
+        fn check(vals: Vec<i32>, skip: usize, n: usize, rest: &[i32], removed: &[i32]) {
+            let mut list = sized_list(vals);
+            let taken = {
+                let mut cursor = list.cursor();
+                cursor.skip_forwards(skip);
+                let taken = cursor.remove_n(n);
+                assert_eq!(cursor.index(), skip);
+                assert_eq!(cursor.peek(), rest.get(skip));
+                taken
+            };
+
+            assert!(list.is_consistent() && taken.is_consistent());
+            assert_eq!(list.len(), rest.len());
+            assert_eq!(taken.len(), removed.len());
+            assert_eq!(list.front(), rest.first());
+            assert_eq!(list.back(), rest.last());
+            assert_eq!(taken.front(), removed.first());
+            assert_eq!(taken.back(), removed.last());
+            assert_eq!(Vec::from(list), rest);
+            assert_eq!(Vec::from(taken), removed);
+        }
+
+        // In the middle
+        check(vec![1, 2, 3, 4, 5, 6], 2, 2, &[1, 2, 5, 6], &[3, 4]);
+        check(vec![1, 2, 3, 4, 5, 6], 2, 1, &[1, 2, 4, 5, 6], &[3]);
+
+        // Touching the head
+        check(vec![1, 2, 3, 4], 0, 2, &[3, 4], &[1, 2]);
+        check(vec![1, 2, 3, 4], 0, 3, &[4], &[1, 2, 3]);
+
+        // Touching the tail, with more requested than there are
+        check(vec![1, 2, 3, 4], 1, 3, &[1], &[2, 3, 4]);
+        check(vec![1, 2, 3, 4], 2, 10, &[1, 2], &[3, 4]);
+
+        // Everything
+        check(vec![1, 2, 3], 0, 3, &[], &[1, 2, 3]);
+        check(vec![1], 0, 5, &[], &[1]);
+
+        // Nothing
+        check(vec![1, 2, 3], 1, 0, &[1, 2, 3], &[]);
+        check(vec![1, 2, 3], 3, 2, &[1, 2, 3], &[]);
+        check(vec![], 0, 2, &[], &[]);
+
+        // The remaining list still works after the removal
+        let mut list = sized_list(vec![1, 2, 3, 4, 5]);
+        drop(list.cursor().remove_n(4));
+        list.push_front_node(Node::new_sized(0, &Global));
+        list.push_back_node(Node::new_sized(6, &Global));
+        assert!(list.is_consistent());
+        assert_eq!(Vec::from(list), [0, 5, 6]);
+    }
+
+    #[test]
+    fn cursor_swap_adjacent() {
+        fn check(vals: Vec<i32>, skip: usize, expected: &[i32]) {
+            let mut list = sized_list(vals);
+            {
+                let mut cursor = list.cursor();
+                cursor.skip_forwards(skip);
+                let prev = cursor.peek_prev().cloned();
+                let curr = cursor.peek().cloned();
+
+                assert_eq!(cursor.swap_adjacent(), prev.is_some() && curr.is_some());
+                assert_eq!(cursor.index(), skip);
+                if prev.is_some() && curr.is_some() {
+                    assert_eq!(cursor.peek_prev().cloned(), curr);
+                    assert_eq!(cursor.peek().cloned(), prev);
+                }
+            }
+
+            assert!(list.is_consistent());
+            assert_eq!(list.front(), expected.first());
+            assert_eq!(list.back(), expected.last());
+            assert_eq!(Vec::from(list), expected);
+        }
+
+        // At the head
+        check(vec![1, 2, 3, 4], 1, &[2, 1, 3, 4]);
+        // At the tail
+        check(vec![1, 2, 3, 4], 3, &[1, 2, 4, 3]);
+        // In the middle
+        check(vec![1, 2, 3, 4], 2, &[1, 3, 2, 4]);
+        // A two-element list
+        check(vec![1, 2], 1, &[2, 1]);
+
+        // At the ends of the list nothing happens
+        check(vec![1, 2, 3], 0, &[1, 2, 3]);
+        check(vec![1, 2, 3], 3, &[1, 2, 3]);
+        check(vec![1], 1, &[1]);
+        check(vec![], 0, &[]);
+
+        // Bubbling an element to the front
+        let mut list = sized_list(vec![1, 2, 3, 4, 5]);
+        {
+            let mut cursor = list.cursor();
+            cursor.seek_to_end();
+            cursor.prev();
+            while cursor.swap_adjacent() {
+                cursor.prev();
+            }
+        }
+        assert!(list.is_consistent());
+        assert_eq!(Vec::from(list), [5, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn cursor_borrows() {
         let mut list : XorList<Display> = XorList::new();
+        for i in 0..4 {
+            list.push_back(i);
+        }
 
-        list.push_back(0);
-        list.push_back(1);
-        list.push_back(2);
-        list.push_back(3);
+        let mut cursor = list.cursor();
+
+        // References from navigating and peeking can be held together
+        {
+            let a = cursor.next().unwrap();
+            let b = cursor.peek().unwrap();
+            let c = cursor.next().unwrap();
+            cursor.skip_backwards(1);
+            let d = cursor.peek_prev().unwrap();
+            assert_eq!([a.to_string(), b.to_string(), c.to_string(), d.to_string()],
+                       ["0", "1", "1", "0"]);
+        }
+
+        // Once they're gone the list can be changed through the cursor
+        let el = cursor.remove().unwrap();
+        cursor.insert_before(el.to_string() + "!");
+        assert_eq!(cursor.peek_prev().unwrap().to_string(), "1!");
+
+        // Holding a reference while changing the list doesn't compile, since any change can free
+        // or relink the node the reference points into. That's checked by the `compile_fail`
+        // examples on `Cursor`.
+    }
+
+    #[test]
+    fn cursor_extract() {
+        let vals = vec![1, 2, 3, 4, 5, 6];
+
+        for &(skip, n) in &[(0, 2), (0, 6), (2, 2), (3, 1), (4, 2), (4, 10), (6, 3), (2, 0)] {
+            let mut list = sized_list(vals.clone());
+            {
+                let mut cursor = list.cursor();
+                cursor.skip_forwards(skip);
+                let window = cursor.extract(n);
+                assert_eq!(window.len(), cmp::min(n, vals.len() - skip));
+                assert_eq!(window.iter().cloned().collect::<Vec<_>>(),
+                           &vals[skip..skip + window.len()]);
+
+                cursor.splice(window);
+                assert_eq!(cursor.index(), skip);
+                assert_eq!(cursor.peek(), vals.get(skip));
+            }
+            assert!(list.is_consistent());
+            assert_eq!(list.iter().cloned().collect::<Vec<_>>(), vals);
+        }
 
+        // Working on the window before putting it back
+        let mut list = sized_list(vals.clone());
         {
             let mut cursor = list.cursor();
+            cursor.skip_forwards(1);
+            let mut window = cursor.extract(3);
+            window.reverse();
+            cursor.splice(window);
+            assert_eq!(cursor.peek(), Some(&4));
+        }
+        assert!(list.is_consistent());
+        assert_eq!(Vec::from(list), [1, 4, 3, 2, 5, 6]);
+    }
 
-            let mut list : XorList<Display> = XorList::new();
-            list.push_back(4);
-            list.push_back(5);
-            list.push_back(6);
-            list.push_back(7);
+    #[test]
+    fn cursor_remove_position() {
+        for len in 1..5 {
+            for at in 0..len + 1 {
+                let vals : Vec<String> = (0..len).map(|i| i.to_string()).collect();
+                let mut list : XorList<Display> = XorList::new();
+                for v in &vals {
+                    list.push_back(v.clone());
+                }
 
+                {
+                    let mut cursor = list.cursor();
+                    cursor.skip_forwards(at);
+                    let removed = cursor.remove();
+                    assert_eq!(removed.map(|el| el.to_string()), vals.get(at).cloned());
+                    assert_eq!(cursor.index(), at);
+
+                    cursor.insert_before("x");
+                    cursor.insert_after("y");
+                    assert_eq!(cursor.peek_prev().unwrap().to_string(), "x");
+                    assert_eq!(cursor.peek().unwrap().to_string(), "y");
+                }
 
-            cursor.next();
-            cursor.next();
+                let mut expected = vals.clone();
+                if at < len {
+                    expected.remove(at);
+                }
+                expected.insert(at, "x".to_string());
+                expected.insert(at + 1, "y".to_string());
 
-            cursor.splice(list);
+                assert!(list.is_consistent());
+                assert_eq!(to_strings(&list), expected);
+                assert_eq!(list.back().unwrap().to_string(), *expected.last().unwrap());
+            }
         }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "0");
+        // Removing everything from the front and back
+        let mut list = sized_list(vec![1, 2, 3]);
+        {
+            let mut cursor = list.cursor();
+            assert_eq!(*cursor.remove().unwrap(), 1);
+            assert!(cursor.at_start());
+            cursor.seek_to_end();
+            assert!(cursor.remove().is_none());
+            cursor.prev();
+            assert_eq!(*cursor.remove().unwrap(), 3);
+            assert!(cursor.at_end());
+            assert_eq!(cursor.peek_prev(), Some(&2));
+            cursor.prev();
+            assert_eq!(*cursor.remove().unwrap(), 2);
+            assert!(cursor.at_start() && cursor.at_end());
+            assert!(cursor.remove().is_none());
+        }
+        assert!(list.is_empty() && list.is_consistent());
+    }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "1");
+    #[test]
+    fn cursor_insert_returns_ref() {
+        let mut list : XorList<[i32]> = XorList::new();
+        list.push_back([1]);
+        list.push_back([4]);
+        {
+            let mut cursor = list.cursor();
+            cursor.next();
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "4");
+            cursor.insert_after([0])[0] = 3;
+            cursor.insert_after([0, 0])[1] = 2;
+            assert_eq!(cursor.peek(), Some(&[0, 2][..]));
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "5");
+            cursor.seek_to_end();
+            {
+                let last = cursor.insert_before([5, 6]);
+                last[0] = 0;
+                last.reverse();
+            }
+            assert_eq!(cursor.peek_prev(), Some(&[6, 0][..]));
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "6");
+            cursor.seek_to_start();
+            for x in cursor.insert_before([0; 3]).iter_mut() {
+                *x = -1;
+            }
+        }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "7");
+        assert!(list.is_consistent());
+        let items : Vec<&[i32]> = list.iter().collect();
+        assert_eq!(items, [&[-1, -1, -1][..], &[1], &[0, 2], &[3], &[4], &[6, 0]]);
+    }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "2");
+    #[test]
+    fn cursor_seek() {
+        let vals : Vec<i32> = (0..9).collect();
+        let mut list = sized_list(vals.clone());
+        let len = list.len();
+
+        for &i in &[0, 1, len / 2, len - 1, len, len + 5] {
+            let cursor = list.cursor_at(i);
+            assert_eq!(cursor.index(), cmp::min(i, len));
+            assert_eq!(cursor.peek(), vals.get(i));
+            let before = if i > 0 { vals.get(cmp::min(i, len) - 1) } else { None };
+            assert_eq!(cursor.peek_prev(), before);
+        }
 
-        let el = list.pop_front().unwrap();
-        assert_eq!(&el.to_string()[..], "3");
+        // Seeking from anywhere gets to the same place
+        {
+            let cursor = list.cursor();
+            for &from in &[0, 2, 4, 7, 9] {
+                for to in 0..len + 2 {
+                    cursor.seek(from);
+                    cursor.seek(to);
+                    assert_eq!(cursor.index(), cmp::min(to, len));
+                    assert_eq!(cursor.peek(), vals.get(to));
+                }
+            }
+        }
+
+        // Seeking near the end comes from the tail, but ends up in the same state as walking from
+        // the head
+        let walked = {
+            let cursor = list.cursor();
+            cursor.skip_forwards(len - 1);
+            cursor.save()
+        };
+        let seeked = list.cursor_at(len - 1).save();
+        assert!(walked.prev == seeked.prev && walked.curr == seeked.curr);
 
+        let mut empty : XorList<i32> = XorList::new();
+        let cursor = empty.cursor_at(3);
+        assert!(cursor.at_start() && cursor.at_end());
+        assert_eq!(cursor.index(), 0);
     }
 
+    #[test]
+    fn append_drop() {
+        let count = Rc::new(Cell::new(0));
+        {
+            let mut list1 : XorList<Debug> = XorList::new();
+            let mut list2 : XorList<Debug> = XorList::new();
+
+            list1.push_back(DropCounter(count.clone()));
+            list1.push_back(DropCounter(count.clone()));
+            list2.push_back(DropCounter(count.clone()));
+            list2.push_back(DropCounter(count.clone()));
+            list2.push_back(DropCounter(count.clone()));
 
+            list1.append(&mut list2);
+            assert_eq!(count.get(), 0);
+        }
+        assert_eq!(count.get(), 5);
+    }
 }
@@ -0,0 +1,56 @@
+//! Exercises the list through `core` and `alloc` only, to check that nothing in the public API
+//! needs `std`. Run with `cargo test --no-default-features`.
+
+#![feature(no_std, alloc, core)]
+#![no_std]
+
+extern crate alloc;
+extern crate dynalist;
+
+use core::any::Any;
+use alloc::boxed::Box;
+
+use dynalist::XorList;
+
+#[test]
+fn push_pop() {
+    let mut list : XorList<[u8]> = XorList::new();
+    list.push_back([1u8, 2]);
+    list.push_back([3u8]);
+    list.push_front([0u8; 0]);
+    assert_eq!(list.len(), 3);
+
+    assert_eq!(&*list.pop_front().unwrap(), &[][..]);
+    assert_eq!(&*list.pop_back().unwrap(), &[3][..]);
+    assert_eq!(list.front(), Some(&[1u8, 2][..]));
+}
+
+#[test]
+fn boxed_any() {
+    let mut list : XorList<Any> = XorList::new();
+    list.push_back(1u32);
+    list.push_back_boxed(Box::new(2u64) as Box<Any>);
+
+    assert_eq!(list.pop_front_downcast::<u32>().unwrap().ok(), Some(1));
+    assert_eq!(list.pop_front_downcast::<u64>().unwrap().ok(), Some(2));
+    assert!(list.is_empty());
+}
+
+#[test]
+fn cursor() {
+    let mut list : XorList<[u8]> = XorList::new();
+    list.push_back([1u8]);
+    list.push_back([3u8]);
+
+    {
+        let mut cursor = list.cursor();
+        cursor.next();
+        cursor.insert_before([2u8]);
+    }
+
+    let mut iter = list.iter();
+    assert_eq!(iter.next(), Some(&[1u8][..]));
+    assert_eq!(iter.next(), Some(&[2u8][..]));
+    assert_eq!(iter.next(), Some(&[3u8][..]));
+    assert_eq!(iter.next(), None);
+}